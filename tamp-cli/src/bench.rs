@@ -0,0 +1,150 @@
+use std::fmt::Write as _;
+use std::time::Instant;
+
+use tamp::Config;
+
+use crate::{CliError, codec};
+
+/// Measurements for one configuration.
+pub struct BenchResult {
+    pub window_bits: u8,
+    pub lazy_matching: bool,
+    pub input_bytes: usize,
+    pub compressed_bytes: usize,
+    pub compress_secs: f64,
+    pub decompress_secs: f64,
+    /// Size of the compressor state, window included.
+    pub compressor_bytes: usize,
+    /// Size of the decompressor state, window included.
+    pub decompressor_bytes: usize,
+}
+
+impl BenchResult {
+    /// Uncompressed over compressed size, see `tamp::Ratio::factor`.
+    fn ratio(&self) -> f32 {
+        tamp::Ratio {
+            uncompressed: self.input_bytes,
+            compressed: self.compressed_bytes,
+        }
+        .factor()
+    }
+
+    fn compress_mb_s(&self) -> f64 {
+        self.input_bytes as f64 / self.compress_secs / 1e6
+    }
+
+    fn decompress_mb_s(&self) -> f64 {
+        self.input_bytes as f64 / self.decompress_secs / 1e6
+    }
+}
+
+/// Compresses and decompresses `data` with every window size (8-15), with and
+/// without lazy matching, verifying each round trip.
+pub fn run(data: &[u8], literal_bits: u8) -> Result<Vec<BenchResult>, CliError> {
+    let mut results = Vec::new();
+    for window_bits in 8..=15 {
+        for lazy_matching in [false, true] {
+            let config = Config::new()
+                .window_bits(window_bits)?
+                .literal_bits(literal_bits)?
+                .lazy_matching(lazy_matching);
+
+            let mut compressed = Vec::new();
+            let start = Instant::now();
+            codec::compress(config, None, &mut &data[..], &mut compressed)?;
+            let compress_secs = start.elapsed().as_secs_f64();
+
+            let mut decompressed = Vec::with_capacity(data.len());
+            let start = Instant::now();
+            codec::decompress(None, &mut &compressed[..], &mut decompressed)?;
+            let decompress_secs = start.elapsed().as_secs_f64();
+
+            if decompressed != data {
+                return Err(CliError::Usage(format!(
+                    "round trip mismatch with {} bit window",
+                    window_bits
+                )));
+            }
+
+            let (compressor_bytes, decompressor_bytes) = codec::memory_usage(window_bits)?;
+            results.push(BenchResult {
+                window_bits,
+                lazy_matching,
+                input_bytes: data.len(),
+                compressed_bytes: compressed.len(),
+                compress_secs,
+                decompress_secs,
+                compressor_bytes,
+                decompressor_bytes,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Formats results as a JSON array with one object per configuration.
+pub fn to_json(results: &[BenchResult]) -> String {
+    let mut out = String::from("[\n");
+    for (i, r) in results.iter().enumerate() {
+        let _ = write!(
+            out,
+            "  {{\"window_bits\": {}, \"lazy_matching\": {}, \"input_bytes\": {}, \
+             \"compressed_bytes\": {}, \"ratio\": {:.4}, \"compress_mb_s\": {:.2}, \
+             \"decompress_mb_s\": {:.2}, \"compressor_bytes\": {}, \"decompressor_bytes\": {}}}",
+            r.window_bits,
+            r.lazy_matching,
+            r.input_bytes,
+            r.compressed_bytes,
+            r.ratio(),
+            r.compress_mb_s(),
+            r.decompress_mb_s(),
+            r.compressor_bytes,
+            r.decompressor_bytes,
+        );
+        out.push_str(if i + 1 < results.len() { ",\n" } else { "\n" });
+    }
+    out.push(']');
+    out
+}
+
+/// Formats results as an aligned text table.
+pub fn to_table(results: &[BenchResult]) -> String {
+    let mut out = String::from(
+        "window  lazy   ratio  compress MB/s  decompress MB/s  compressor RAM  decompressor RAM\n",
+    );
+    for r in results {
+        let _ = writeln!(
+            out,
+            "{:>6}  {:<5}  {:>5.3}  {:>13.2}  {:>15.2}  {:>14}  {:>16}",
+            r.window_bits,
+            r.lazy_matching,
+            r.ratio(),
+            r.compress_mb_s(),
+            r.decompress_mb_s(),
+            r.compressor_bytes,
+            r.decompressor_bytes,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_covers_windows_and_lazy() {
+        let data = b"bench sample, bench sample, bench sample\n".repeat(20);
+        let results = run(&data, 8).unwrap();
+        assert_eq!(results.len(), 16);
+        for (i, r) in results.iter().enumerate() {
+            assert_eq!(
+                (r.window_bits, r.lazy_matching),
+                (8 + i as u8 / 2, i % 2 == 1)
+            );
+            assert_eq!(r.input_bytes, data.len());
+        }
+        assert_eq!(to_json(&results).matches("\"window_bits\"").count(), 16);
+        assert_eq!(to_table(&results).lines().count(), 17);
+    }
+}
@@ -0,0 +1,152 @@
+use std::io::{Read, Write};
+use tamp::{Config, DynCompressor, DynDecompressor};
+
+use crate::CliError;
+
+/// Size of the chunks read from the input.
+const CHUNK_SIZE: usize = 4096;
+/// Output staging buffer, large enough for the worst case expansion of a chunk.
+const OUTPUT_SIZE: usize = CHUNK_SIZE * 2;
+
+/// Compresses `input` into `output`, returning (bytes_read, bytes_written).
+pub fn compress(
+    config: Config,
+    dictionary: Option<&[u8]>,
+    input: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(u64, u64), CliError> {
+    let mut compressor = DynCompressor::allocate(config, dictionary)?;
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut buffer = [0u8; OUTPUT_SIZE];
+    let (mut total_read, mut total_written) = (0u64, 0u64);
+
+    loop {
+        let bytes_read = input.read(&mut chunk)?;
+        if bytes_read == 0 {
+            break;
+        }
+        total_read += bytes_read as u64;
+
+        let mut offset = 0;
+        while offset < bytes_read {
+            let (consumed, written) =
+                compressor.compress_chunk(&chunk[offset..bytes_read], &mut buffer)?;
+            output.write_all(&buffer[..written])?;
+            total_written += written as u64;
+            offset += consumed;
+        }
+    }
+
+    let written = compressor.flush(&mut buffer, false)?;
+    output.write_all(&buffer[..written])?;
+    total_written += written as u64;
+    output.flush()?;
+    Ok((total_read, total_written))
+}
+
+/// Decompresses `input` into `output`, returning (bytes_read, bytes_written).
+///
+/// The window size is taken from the stream header. A dictionary is required
+/// when the stream was compressed with a custom dictionary.
+pub fn decompress(
+    dictionary: Option<&[u8]>,
+    input: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(u64, u64), CliError> {
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut bytes_read = input.read(&mut chunk)?;
+    let (config, mut offset) = Config::from_header(&chunk[..bytes_read])?;
+    if config.use_custom_dictionary && dictionary.is_none() {
+        return Err(CliError::Usage(
+            "stream was compressed with a custom dictionary, pass --dictionary".into(),
+        ));
+    }
+    let mut decompressor = DynDecompressor::allocate(config, dictionary)?;
+    let mut buffer = [0u8; OUTPUT_SIZE];
+    let (mut total_read, mut total_written) = (bytes_read as u64, 0u64);
+
+    while bytes_read > 0 {
+        while offset < bytes_read {
+            let (consumed, written) =
+                decompressor.decompress_chunk(&chunk[offset..bytes_read], &mut buffer)?;
+            output.write_all(&buffer[..written])?;
+            total_written += written as u64;
+            offset += consumed;
+            if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+
+        bytes_read = input.read(&mut chunk)?;
+        total_read += bytes_read as u64;
+        offset = 0;
+    }
+
+    output.flush()?;
+    Ok((total_read, total_written))
+}
+
+/// RAM held by a (compressor, decompressor) pair for the given window size.
+pub fn memory_usage(window_bits: u8) -> Result<(usize, usize), CliError> {
+    Ok((
+        DynCompressor::memory_bytes(window_bits)?,
+        DynDecompressor::memory_bytes(window_bits)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..1200u32 {
+            data.extend_from_slice(format!("line {} of the sample file\n", i % 37).as_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_round_trip_all_windows() {
+        let data = sample();
+        for window in 8..=15 {
+            let config = Config::new().window_bits(window).unwrap();
+            let mut stream = Vec::new();
+            let (read, written) = compress(config, None, &mut &data[..], &mut stream).unwrap();
+            assert_eq!((read, written), (data.len() as u64, stream.len() as u64));
+
+            let mut output = Vec::new();
+            let (read, written) = decompress(None, &mut &stream[..], &mut output).unwrap();
+            assert_eq!((read, written), (stream.len() as u64, data.len() as u64));
+            assert_eq!(output, data);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_dictionary() {
+        let data = sample();
+        let dictionary = b"line of the sample file\n".repeat(60);
+        let config = Config::new()
+            .window_bits(10)
+            .unwrap()
+            .custom_dictionary(true);
+        let mut stream = Vec::new();
+        compress(config, Some(&dictionary), &mut &data[..], &mut stream).unwrap();
+
+        let mut output = Vec::new();
+        decompress(Some(&dictionary), &mut &stream[..], &mut output).unwrap();
+        assert_eq!(output, data);
+        assert!(matches!(
+            decompress(None, &mut &stream[..], &mut Vec::new()),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let (small, _) = memory_usage(8).unwrap();
+        let (large, _) = memory_usage(15).unwrap();
+        assert!(large - small >= 32768 - 256);
+        assert!(matches!(memory_usage(16), Err(CliError::Tamp(_))));
+    }
+}
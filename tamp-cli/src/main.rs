@@ -0,0 +1,274 @@
+//! Command line tool to compress, decompress and inspect tamp streams.
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use tamp::Config;
+
+mod bench;
+mod codec;
+
+#[derive(Parser)]
+#[command(name = "tamp", version, about = "Compress and decompress tamp streams")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compress a file (or stdin).
+    Compress {
+        #[command(flatten)]
+        io: IoArgs,
+        /// Window size in bits (8-15).
+        #[arg(short, long, default_value_t = 10)]
+        window: u8,
+        /// Literal size in bits (5-8).
+        #[arg(short, long, default_value_t = 8)]
+        literal: u8,
+        /// Enable lazy matching, where the C library is built with it (not on `thumbv`).
+        #[arg(long)]
+        lazy: bool,
+        /// Initialize the window with a custom dictionary file.
+        #[arg(short, long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Decompress a file (or stdin).
+    Decompress {
+        #[command(flatten)]
+        io: IoArgs,
+        /// Custom dictionary used during compression.
+        #[arg(short, long)]
+        dictionary: Option<PathBuf>,
+    },
+    /// Measure ratio, throughput and memory across window sizes.
+    Bench {
+        /// Uncompressed sample file.
+        input: PathBuf,
+        /// Literal size in bits (5-8).
+        #[arg(short, long, default_value_t = 8)]
+        literal: u8,
+        /// Print a JSON array instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the header and sizes of a compressed file (or stdin).
+    Info {
+        /// Input file, defaults to stdin.
+        input: Option<PathBuf>,
+        /// Custom dictionary used during compression.
+        #[arg(short, long)]
+        dictionary: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct IoArgs {
+    /// Input file, defaults to stdin.
+    input: Option<PathBuf>,
+    /// Output file, defaults to stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Errors reported by the command line tool.
+#[derive(Debug)]
+pub enum CliError {
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// The tamp library rejected the stream or configuration.
+    Tamp(tamp::Error),
+    /// Invalid combination of arguments.
+    Usage(String),
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+impl From<tamp::Error> for CliError {
+    fn from(e: tamp::Error) -> Self {
+        CliError::Tamp(e)
+    }
+}
+
+fn open_input(path: Option<&Path>) -> Result<Box<dyn Read>, CliError> {
+    Ok(match path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    })
+}
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, CliError> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn read_dictionary(path: Option<&Path>) -> Result<Option<Vec<u8>>, CliError> {
+    Ok(path.map(fs::read).transpose()?)
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    match cli.command {
+        Command::Compress {
+            io,
+            window,
+            literal,
+            lazy,
+            dictionary,
+        } => {
+            let dictionary = read_dictionary(dictionary.as_deref())?;
+            let config = Config::new()
+                .window_bits(window)?
+                .literal_bits(literal)?
+                .lazy_matching(lazy)
+                .custom_dictionary(dictionary.is_some());
+            let mut input = open_input(io.input.as_deref())?;
+            let mut output = open_output(io.output.as_deref())?;
+            codec::compress(config, dictionary.as_deref(), &mut input, &mut output)?;
+        }
+        Command::Decompress { io, dictionary } => {
+            let dictionary = read_dictionary(dictionary.as_deref())?;
+            let mut input = open_input(io.input.as_deref())?;
+            let mut output = open_output(io.output.as_deref())?;
+            codec::decompress(dictionary.as_deref(), &mut input, &mut output)?;
+        }
+        Command::Bench {
+            input,
+            literal,
+            json,
+        } => {
+            let data = fs::read(input)?;
+            let results = bench::run(&data, literal)?;
+            if json {
+                println!("{}", bench::to_json(&results));
+            } else {
+                print!("{}", bench::to_table(&results));
+            }
+        }
+        Command::Info { input, dictionary } => {
+            let dictionary = read_dictionary(dictionary.as_deref())?;
+            let data = match &input {
+                Some(path) => fs::read(path)?,
+                None => {
+                    let mut data = Vec::new();
+                    io::stdin().read_to_end(&mut data)?;
+                    data
+                }
+            };
+            let (config, _) = Config::from_header(&data)?;
+            println!(
+                "window: {} bits ({} bytes)",
+                config.window_bits,
+                config.window_size()
+            );
+            println!("literal: {} bits", config.literal_bits);
+            println!("custom dictionary: {}", config.use_custom_dictionary);
+            println!("compressed size: {} bytes", data.len());
+            if !config.use_custom_dictionary || dictionary.is_some() {
+                let (_, size) =
+                    codec::decompress(dictionary.as_deref(), &mut &data[..], &mut io::sink())?;
+                println!("decompressed size: {} bytes", size);
+                if size > 0 {
+                    let ratio = tamp::Ratio {
+                        uncompressed: size as usize,
+                        compressed: data.len(),
+                    };
+                    println!("ratio: {:.3}", ratio.factor());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e {
+                CliError::Io(e) => eprintln!("error: {}", e),
+                CliError::Tamp(e) => eprintln!("error: {}", e),
+                CliError::Usage(msg) => eprintln!("error: {}", msg),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tamp(args: &[&str]) -> Result<(), CliError> {
+        run(Cli::try_parse_from(std::iter::once("tamp").chain(args.iter().copied())).unwrap())
+    }
+
+    #[test]
+    fn test_cli_round_trip() {
+        let dir = std::env::temp_dir().join(format!("tamp-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+        let data = b"command line round trip, command line round trip\n".repeat(40);
+        fs::write(path("input"), &data).unwrap();
+
+        tamp(&[
+            "compress",
+            &path("input"),
+            "-o",
+            &path("input.tamp"),
+            "-w",
+            "9",
+        ])
+        .unwrap();
+        tamp(&["decompress", &path("input.tamp"), "-o", &path("output")]).unwrap();
+        tamp(&["info", &path("input.tamp")]).unwrap();
+        let (config, _) = Config::from_header(&fs::read(path("input.tamp")).unwrap()).unwrap();
+        assert_eq!(config.window_bits, 9);
+        assert_eq!(fs::read(path("output")).unwrap(), data);
+
+        let compress = tamp(&["compress", &path("input"), "-o", &path("bad"), "-w", "16"]);
+        assert!(matches!(compress, Err(CliError::Tamp(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cli_lazy_reaches_compressor() {
+        let dir = std::env::temp_dir().join(format!("tamp-cli-lazy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+        let data = b"bcdefghij abc abcdefghij".repeat(8);
+        fs::write(path("input"), &data).unwrap();
+
+        for lazy in [false, true] {
+            let (input, output) = (path("input"), path("out"));
+            let mut args = vec!["compress", input.as_str(), "-o", output.as_str()];
+            if lazy {
+                args.push("--lazy");
+            }
+            tamp(&args).unwrap();
+
+            let config = Config::new().lazy_matching(lazy);
+            let mut compressor = tamp::Compressor::<1024>::new(config).unwrap();
+            let mut expected = [0u8; 512];
+            let (consumed, mut len) = compressor.compress_chunk(&data, &mut expected).unwrap();
+            assert_eq!(consumed, data.len());
+            len += compressor.flush(&mut expected[len..], false).unwrap();
+            assert_eq!(fs::read(path("out")).unwrap(), &expected[..len]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cli_rejects_bad_arguments() {
+        assert!(Cli::try_parse_from(["tamp", "compress", "--window"]).is_err());
+        assert!(Cli::try_parse_from(["tamp", "unpack"]).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use tamp::{DynDecompressor, Error};
+
+/// Size of the staging buffer decoded output goes through, and of reads from `f`.
+pub const BUFFER_SIZE: usize = 4096;
+
+/// Decodes `input` into `output` until either the input is consumed or
+/// `limit` bytes have been produced. Returns the number of input bytes consumed.
+pub fn decompress_into(
+    decompressor: &mut DynDecompressor<'static>,
+    input: &[u8],
+    output: &mut Vec<u8>,
+    limit: usize,
+) -> Result<usize, Error> {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let mut offset = 0;
+    while output.len() < limit {
+        let cap = (limit - output.len()).min(BUFFER_SIZE);
+        let (consumed, written) =
+            decompressor.decompress_chunk(&input[offset..], &mut buffer[..cap])?;
+        output.extend_from_slice(&buffer[..written]);
+        offset += consumed;
+        if consumed == 0 && written == 0 {
+            break;
+        }
+    }
+    Ok(offset)
+}
@@ -0,0 +1,367 @@
+//! Python bindings for the tamp compression library.
+//!
+//! Mirrors the reference `tamp` Python package: `compress`/`decompress` for
+//! whole buffers and file-like `Compressor`/`Decompressor` classes for streams.
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+use tamp::{Config, DynCompressor, DynDecompressor, Error};
+
+use codec::BUFFER_SIZE;
+
+mod codec;
+
+create_exception!(
+    tamp_rs,
+    ExcessBitsError,
+    PyValueError,
+    "Input contains a byte wider than the configured literal size."
+);
+
+fn to_py_err(e: Error) -> PyErr {
+    match e {
+        Error::ExcessBits => ExcessBitsError::new_err("input byte exceeds literal bits"),
+        e => PyValueError::new_err(format!("{:?}", e)),
+    }
+}
+
+fn make_config(
+    window: u8,
+    literal: u8,
+    custom_dictionary: bool,
+    lazy_matching: bool,
+) -> Result<Config, Error> {
+    Ok(Config::new()
+        .window_bits(window)?
+        .literal_bits(literal)?
+        .custom_dictionary(custom_dictionary)
+        .lazy_matching(lazy_matching))
+}
+
+/// Opens `f` with `mode` if it is a path, returning (file, opened_here).
+fn open_if_path(f: Bound<'_, PyAny>, mode: &str) -> PyResult<(PyObject, bool)> {
+    if f.is_instance_of::<PyString>() || f.hasattr("__fspath__")? {
+        let open = f.py().import("builtins")?.getattr("open")?;
+        Ok((open.call1((f, mode))?.unbind(), true))
+    } else {
+        Ok((f.unbind(), false))
+    }
+}
+
+/// Compresses `data` in one call.
+#[pyfunction]
+#[pyo3(signature = (data, window=10, literal=8, dictionary=None, lazy_matching=false))]
+fn compress<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    window: u8,
+    literal: u8,
+    dictionary: Option<&[u8]>,
+    lazy_matching: bool,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let output = py
+        .allow_threads(|| {
+            let config = make_config(window, literal, dictionary.is_some(), lazy_matching)?;
+            let mut compressor = DynCompressor::allocate(config, dictionary)?;
+            let mut output = Vec::with_capacity(data.len() / 2);
+            compressor.compress_into(data, &mut output)?;
+            compressor.flush_into(&mut output, false)?;
+            Ok(output)
+        })
+        .map_err(to_py_err)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Decompresses a complete stream in one call.
+#[pyfunction]
+#[pyo3(signature = (data, dictionary=None))]
+fn decompress<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    dictionary: Option<&[u8]>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let output = py
+        .allow_threads(|| {
+            let (config, header_len) = Config::from_header(data)?;
+            let mut decompressor = DynDecompressor::allocate(config, dictionary)?;
+            let mut output = Vec::with_capacity(data.len() * 2);
+            codec::decompress_into(
+                &mut decompressor,
+                &data[header_len..],
+                &mut output,
+                usize::MAX,
+            )?;
+            Ok(output)
+        })
+        .map_err(to_py_err)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Compresses data written to it into the file-like object (or path) `f`.
+#[pyclass(unsendable)]
+struct Compressor {
+    f: PyObject,
+    close_f: bool,
+    inner: DynCompressor<'static>,
+    closed: bool,
+}
+
+impl Compressor {
+    fn emit(&self, py: Python<'_>, data: &[u8]) -> PyResult<usize> {
+        if !data.is_empty() {
+            self.f
+                .call_method1(py, "write", (PyBytes::new(py, data),))?;
+        }
+        Ok(data.len())
+    }
+}
+
+#[pymethods]
+impl Compressor {
+    #[new]
+    #[pyo3(signature = (f, *, window=10, literal=8, dictionary=None, lazy_matching=false))]
+    fn new(
+        f: Bound<'_, PyAny>,
+        window: u8,
+        literal: u8,
+        dictionary: Option<&[u8]>,
+        lazy_matching: bool,
+    ) -> PyResult<Self> {
+        let config =
+            make_config(window, literal, dictionary.is_some(), lazy_matching).map_err(to_py_err)?;
+        let inner = DynCompressor::allocate(config, dictionary).map_err(to_py_err)?;
+        let (f, close_f) = open_if_path(f, "wb")?;
+        Ok(Self {
+            f,
+            close_f,
+            inner,
+            closed: false,
+        })
+    }
+
+    /// Compresses `data`, returns the number of compressed bytes written to `f`.
+    fn write(&mut self, py: Python<'_>, data: &[u8]) -> PyResult<usize> {
+        let mut output = Vec::new();
+        self.inner.compress_into(data, &mut output).map_err(to_py_err)?;
+        self.emit(py, &output)
+    }
+
+    /// Flushes buffered data, returns the number of compressed bytes written to `f`.
+    #[pyo3(signature = (write_token=true))]
+    fn flush(&mut self, py: Python<'_>, write_token: bool) -> PyResult<usize> {
+        let mut output = Vec::new();
+        self.inner.flush_into(&mut output, write_token).map_err(to_py_err)?;
+        let written = self.emit(py, &output)?;
+        if self.f.bind(py).hasattr("flush")? {
+            self.f.call_method0(py, "flush")?;
+        }
+        Ok(written)
+    }
+
+    /// Final flush, closes `f` if it was opened from a path.
+    fn close(&mut self, py: Python<'_>) -> PyResult<usize> {
+        if self.closed {
+            return Ok(0);
+        }
+        let written = self.flush(py, false)?;
+        if self.close_f {
+            self.f.call_method0(py, "close")?;
+        }
+        self.closed = true;
+        Ok(written)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        _exc_type: PyObject,
+        _exc: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+/// Decompresses data read from the file-like object (or path) `f`.
+#[pyclass(unsendable)]
+struct Decompressor {
+    f: PyObject,
+    close_f: bool,
+    dictionary: Option<Vec<u8>>,
+    inner: Option<DynDecompressor<'static>>,
+    /// Compressed input read from `f` but not consumed yet.
+    input: Vec<u8>,
+    eof: bool,
+}
+
+impl Decompressor {
+    /// Reads more input from `f`, creating the decompressor once the header is complete.
+    fn fill(&mut self, py: Python<'_>) -> PyResult<()> {
+        let chunk = self.f.call_method1(py, "read", (BUFFER_SIZE,))?;
+        let chunk = chunk.downcast_bound::<PyBytes>(py)?.as_bytes();
+        self.eof = chunk.is_empty();
+        self.input.extend_from_slice(chunk);
+
+        if self.inner.is_none() {
+            let (config, header_len) = match Config::from_header(&self.input) {
+                Ok(header) => header,
+                Err(Error::InputExhausted) => return Ok(()),
+                Err(e) => return Err(to_py_err(e)),
+            };
+            if config.use_custom_dictionary && self.dictionary.is_none() {
+                return Err(PyValueError::new_err(
+                    "stream was compressed with a custom dictionary",
+                ));
+            }
+            let inner = DynDecompressor::allocate(config, self.dictionary.as_deref());
+            self.inner = Some(inner.map_err(to_py_err)?);
+            self.input.drain(..header_len);
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl Decompressor {
+    #[new]
+    #[pyo3(signature = (f, *, dictionary=None))]
+    fn new(f: Bound<'_, PyAny>, dictionary: Option<Vec<u8>>) -> PyResult<Self> {
+        let (f, close_f) = open_if_path(f, "rb")?;
+        Ok(Self {
+            f,
+            close_f,
+            dictionary,
+            inner: None,
+            input: Vec::new(),
+            eof: false,
+        })
+    }
+
+    /// Reads up to `size` decompressed bytes, or everything if `size` is negative.
+    #[pyo3(signature = (size=-1))]
+    fn read<'py>(&mut self, py: Python<'py>, size: isize) -> PyResult<Bound<'py, PyBytes>> {
+        let limit = usize::try_from(size).unwrap_or(usize::MAX);
+        let mut output = Vec::new();
+        while output.len() < limit {
+            if let Some(inner) = &mut self.inner {
+                let consumed = codec::decompress_into(inner, &self.input, &mut output, limit)
+                    .map_err(to_py_err)?;
+                self.input.drain(..consumed);
+            }
+            if output.len() >= limit || self.eof {
+                break;
+            }
+            self.fill(py)?;
+        }
+        Ok(PyBytes::new(py, &output))
+    }
+
+    /// Closes `f` if it was opened from a path.
+    fn close(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.close_f {
+            self.f.call_method0(py, "close")?;
+            self.close_f = false;
+        }
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python<'_>,
+        _exc_type: PyObject,
+        _exc: PyObject,
+        _traceback: PyObject,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+#[pymodule]
+fn tamp_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_class::<Compressor>()?;
+    m.add_class::<Decompressor>()?;
+    m.add("ExcessBitsError", m.py().get_type::<ExcessBitsError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_python(f: impl FnOnce(Python<'_>) -> PyResult<()>) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(f).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_all_windows() {
+        with_python(|py| {
+            let data = b"python bytes, python bytes, python bytearray ".repeat(300);
+            for window in 8..=15 {
+                let stream = compress(py, &data, window, 8, None, false)?;
+                let output = decompress(py, stream.as_bytes(), None)?;
+                assert_eq!(output.as_bytes(), &data[..]);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_round_trip_dictionary() {
+        with_python(|py| {
+            let data = b"sensor=7 value=12.5\n".repeat(50);
+            let dictionary = b"sensor= value=\n".repeat(70);
+            let stream = compress(py, &data, 10, 8, Some(&dictionary), false)?;
+            let output = decompress(py, stream.as_bytes(), Some(&dictionary))?;
+            assert_eq!(output.as_bytes(), &data[..]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_excess_bits_error() {
+        with_python(|py| {
+            let err = compress(py, b"caf\xc3\xa9", 10, 7, None, false).unwrap_err();
+            assert!(err.is_instance_of::<ExcessBitsError>(py));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_file_objects() {
+        with_python(|py| {
+            let bytes_io = py.import("io")?.getattr("BytesIO")?;
+            let data = b"file-like objects in and out ".repeat(400);
+
+            let sink = bytes_io.call0()?;
+            let mut compressor = Compressor::new(sink.clone(), 12, 8, None, false)?;
+            compressor.write(py, &data[..1000])?;
+            compressor.flush(py, true)?;
+            compressor.write(py, &data[1000..])?;
+            compressor.close(py)?;
+            let stream = sink.call_method0("getvalue")?;
+
+            let source = bytes_io.call1((stream,))?;
+            let mut decompressor = Decompressor::new(source, None)?;
+            let head = decompressor.read(py, 5)?;
+            assert_eq!(head.as_bytes(), &data[..5]);
+            let rest = decompressor.read(py, -1)?;
+            assert_eq!(rest.as_bytes(), &data[5..]);
+            assert!(decompressor.read(py, -1)?.as_bytes().is_empty());
+            Ok(())
+        });
+    }
+}
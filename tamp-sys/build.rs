@@ -22,30 +22,93 @@ fn arm_none_eabi_sysroot() -> String {
     .unwrap()
 }
 
-fn main() {
-    let target = std::env::var("TARGET").unwrap();
-    
-    let mut builder = bindgen::Builder::default()
-        .clang_arg(format!("--target={}", target))
-        .clang_arg("-Itamp/tamp/_c_src")
-        .header("wrapper.h")
-        .use_core()
-        .ctypes_prefix("::core::ffi")
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+/// Reads a whitespace-separated list of arguments from an environment variable.
+fn env_args(name: &str) -> Option<Vec<String>> {
+    println!("cargo:rerun-if-env-changed={}", name);
+    env::var(name)
+        .ok()
+        .map(|v| v.split_whitespace().map(str::to_owned).collect())
+}
 
-    if target.starts_with("thumbv") || target.starts_with("xtensa") {
-        // ARM embedded targets (e.g., thumbv7em-none-eabihf)
-        let sysroot = arm_none_eabi_sysroot().trim().to_owned();
-        builder = builder.clang_arg(format!("--sysroot={}", sysroot));
-    } else {
-        // Desktop targets (Windows, Linux, macOS)
-        builder = builder.clang_arg(clang_resource_include());
+/// True when rustc is linking with `-Clinker-plugin-lto`, which is required to
+/// consume the LLVM bitcode produced by `-flto`.
+#[cfg(feature = "lto")]
+fn linker_plugin_lto() -> bool {
+    env::var("CARGO_ENCODED_RUSTFLAGS")
+        .map(|flags| flags.split('\x1f').any(|f| f.ends_with("linker-plugin-lto")))
+        .unwrap_or(false)
+}
+
+/// The `-Ctarget-cpu` passed to rustc, forwarded to the C compiler so both
+/// sides of the FFI boundary use the same instruction set.
+#[cfg(feature = "opt-speed")]
+fn target_cpu() -> Option<String> {
+    let flags = env::var("CARGO_ENCODED_RUSTFLAGS").ok()?;
+    let mut flags = flags.split('\x1f');
+    while let Some(flag) = flags.next() {
+        let value = match flag {
+            "-C" => flags.next().unwrap_or_default(),
+            _ => flag.strip_prefix("-C").unwrap_or(flag),
+        };
+        if let Some(cpu) = value.strip_prefix("target-cpu=") {
+            return Some(cpu.to_owned());
+        }
+    }
+    None
+}
+
+/// The baseline core of a `thumbv*` target, so the C compiler schedules for it and uses the
+/// instructions it has beyond the Thumb subset all Cortex-M share, e.g. CLZ and hardware
+/// division from ARMv7-M on.
+#[cfg(feature = "cortex-m")]
+fn cortex_m_cpu(target: &str) -> Option<&'static str> {
+    let cpu = match target.split('-').next()? {
+        "thumbv6m" => "cortex-m0plus",
+        "thumbv7m" => "cortex-m3",
+        "thumbv7em" => "cortex-m4",
+        "thumbv8m.base" => "cortex-m23",
+        "thumbv8m.main" => "cortex-m33",
+        _ => return None,
+    };
+    Some(cpu)
+}
+
+/// Whether the target is an Espressif chip, Xtensa (`xtensa-esp32s3-espidf`) or RISC-V
+/// (`riscv32imac-esp-espidf`), with or without ESP-IDF.
+#[cfg(feature = "esp32")]
+fn is_espressif() -> bool {
+    env::var("CARGO_CFG_TARGET_VENDOR").as_deref() == Ok("espressif")
+        || env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("espidf")
+}
+
+/// Upstream's ESP32 implementations, which take over from the generic C code when
+/// `TAMP_ESP32` is set: every `.c` / `.cpp` file with `esp32` in its name below `dir`.
+#[cfg(feature = "esp32")]
+fn esp32_sources(dir: &std::path::Path, sources: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            esp32_sources(&path, sources);
+        } else if name.contains("esp32") && (name.ends_with(".c") || name.ends_with(".cpp")) {
+            sources.push(path);
+        }
     }
+}
 
-    let bindings = builder
-        .generate()
-        .expect("Unable to generate bindings");
+/// Whether the C library is built with lazy matching (`TAMP_LAZY_MATCHING`): off on `thumbv`
+/// targets to save code size, on elsewhere. A `-DTAMP_LAZY_MATCHING=` in `TAMP_SYS_CFLAGS`
+/// overrides either.
+fn lazy_matching(target: &str, cflags: Option<&[String]>) -> bool {
+    cflags
+        .into_iter()
+        .flatten()
+        .rev()
+        .find_map(|flag| flag.strip_prefix("-DTAMP_LAZY_MATCHING="))
+        .map_or(!target.starts_with("thumbv"), |value| value != "0")
+}
 
+fn compile_vendored(target: &str, cflags: Option<Vec<String>>, lazy: bool) {
     // Build the C library with size optimizations
     let mut build = cc::Build::new();
     let mut files = vec!["tamp/tamp/_c_src/tamp/common.c"];
@@ -57,22 +120,168 @@ fn main() {
     #[cfg(feature = "decompressor")]
     files.push("tamp/tamp/_c_src/tamp/decompressor.c");
     
-    build.files(&files)
+    build
         .flag("-Wno-type-limits")
-        .include("tamp/tamp/_c_src");
+        .include("tamp/tamp/_c_src")
+        .define("TAMP_LAZY_MATCHING", if lazy { "1" } else { "0" });
     
     // Add size optimization flags for embedded targets
     if target.starts_with("thumbv") {
         build
             .flag("-Os")           // Optimize for size
             .flag("-ffunction-sections")  // Place functions in separate sections
-            .flag("-fdata-sections")      // Place data in separate sections
-            // .flag("-flto")               // Link-time optimization
-            .flag("-DTAMP_LAZY_MATCHING=0"); // Disable lazy matching to save code size
+            .flag("-fdata-sections");     // Place data in separate sections
     }
-    
+
+    // Tune for the specific Cortex-M profile instead of generic Thumb code
+    #[cfg(feature = "cortex-m")]
+    if let Some(cpu) = cortex_m_cpu(target) {
+        build.flag(format!("-mcpu={}", cpu)).flag("-mthumb");
+        // Mainline cores have the instructions that make -O2 pay off; baseline cores
+        // (M0, M0+, M23) usually run from slow flash, where the smaller -Os code wins
+        if !target.starts_with("thumbv6m") && !target.starts_with("thumbv8m.base") {
+            build.flag("-O2");
+        }
+    }
+
+    // Upstream's tuned ESP32 paths, e.g. the SIMD match search on the ESP32-S3. The C files
+    // join the library, the C++ ones go into a second one built below
+    #[cfg(feature = "esp32")]
+    let (esp32_c, esp32_cpp) = if is_espressif() {
+        let mut sources = Vec::new();
+        esp32_sources(std::path::Path::new("tamp/espidf"), &mut sources);
+        if sources.is_empty() {
+            panic!(
+                "the `esp32` feature needs the ESP32 sources in tamp/espidf, update the submodule"
+            );
+        }
+        println!("cargo:rerun-if-changed=tamp/espidf");
+        let (cpp, c): (Vec<_>, Vec<_>) = sources
+            .into_iter()
+            .partition(|path| path.extension().is_some_and(|ext| ext == "cpp"));
+        build.define("TAMP_ESP32", "1");
+        if target.starts_with("xtensa") {
+            build.flag("-mlongcalls");
+        }
+        (c, cpp)
+    } else {
+        panic!("the `esp32` feature needs an Espressif target, not {}", target);
+    };
+
+    // Favour throughput on hosted targets, bare-metal builds keep optimizing for size
+    #[cfg(feature = "opt-speed")]
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("none") {
+        build.opt_level(3);
+        if let Some(cpu) = target_cpu() {
+            if env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch.starts_with("x86")) {
+                build.flag_if_supported(format!("-march={}", cpu));
+            } else {
+                build.flag_if_supported(format!("-mcpu={}", cpu));
+            }
+        }
+    }
+
+    // Cross-language LTO: emit LLVM bitcode so the FFI boundary can be inlined
+    #[cfg(feature = "lto")]
+    if linker_plugin_lto() {
+        if env::var_os("CC").is_none() {
+            build.compiler("clang");
+        }
+        if env::var_os("AR").is_none() {
+            build.archiver("llvm-ar");
+        }
+        build.flag("-flto=thin");
+    } else {
+        println!(
+            "cargo:warning=the `lto` feature needs RUSTFLAGS=-Clinker-plugin-lto, building without LTO"
+        );
+    }
+
+    // User flags come last so they can override the defaults above
+    for flag in cflags.iter().flatten() {
+        build.flag(flag);
+    }
+
+    // The C++ sources get their own build, so only they go through the C++ compiler; same
+    // defines and flags, and linked after libtamp so its references to them resolve
+    #[cfg(feature = "esp32")]
+    let esp32_build = (!esp32_cpp.is_empty()).then(|| {
+        let mut cpp = build.clone();
+        cpp.cpp(true).files(&esp32_cpp);
+        cpp
+    });
+
+    build.files(&files);
+    #[cfg(feature = "esp32")]
+    build.files(&esp32_c);
     build.compile("tamp");
 
+    #[cfg(feature = "esp32")]
+    if let Some(cpp) = esp32_build {
+        cpp.compile("tamp_esp32");
+    }
+}
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap();
+    println!("cargo:rerun-if-env-changed=TAMP_SYS_SYSROOT");
+    let sysroot = env::var("TAMP_SYS_SYSROOT").ok();
+    let clang_args = env_args("TAMP_SYS_CLANG_ARGS");
+    let cflags = env_args("TAMP_SYS_CFLAGS");
+    println!("cargo:rerun-if-env-changed=TAMP_SYS_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=TAMP_SYS_INCLUDE_DIR");
+    let lib_dir = env::var("TAMP_SYS_LIB_DIR").ok();
+    let include_dir =
+        env::var("TAMP_SYS_INCLUDE_DIR").unwrap_or_else(|_| "tamp/tamp/_c_src".to_owned());
+    let system_lib = cfg!(feature = "system-lib") || lib_dir.is_some();
+    // Bindings and library must agree, since the flag adds a field to TampConf
+    let lazy = lazy_matching(&target, cflags.as_deref());
+    println!("cargo:rustc-check-cfg=cfg(tamp_lazy_matching)");
+    if lazy {
+        println!("cargo:rustc-cfg=tamp_lazy_matching");
+    }
+
+    let mut builder = bindgen::Builder::default()
+        .clang_arg(format!("--target={}", target))
+        .clang_arg(format!("-I{}", include_dir))
+        .clang_arg(format!("-DTAMP_LAZY_MATCHING={}", lazy as u8))
+        .header("wrapper.h")
+        .use_core()
+        .ctypes_prefix("::core::ffi")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+
+    if let Some(sysroot) = &sysroot {
+        // Explicit sysroot (Zephyr SDK, Yocto, vendor toolchains)
+        builder = builder.clang_arg(format!("--sysroot={}", sysroot));
+    } else if target.starts_with("thumbv") || target.starts_with("xtensa") {
+        // ARM embedded targets (e.g., thumbv7em-none-eabihf)
+        let sysroot = arm_none_eabi_sysroot().trim().to_owned();
+        builder = builder.clang_arg(format!("--sysroot={}", sysroot));
+    } else if clang_args.is_none() {
+        // Desktop targets (Windows, Linux, macOS)
+        builder = builder.clang_arg(clang_resource_include());
+    }
+
+    if let Some(args) = clang_args {
+        builder = builder.clang_args(args);
+    }
+
+    let bindings = builder
+        .generate()
+        .expect("Unable to generate bindings");
+
+    if system_lib {
+        // Link a prebuilt libtamp (e.g. shipped in a vendor SDK) instead of the vendored sources
+        if let Some(dir) = &lib_dir {
+            println!("cargo:rustc-link-search=native={}", dir);
+        }
+        println!("cargo:rerun-if-env-changed=TAMP_SYS_LIB_KIND");
+        let kind = env::var("TAMP_SYS_LIB_KIND").unwrap_or_else(|_| "static".to_owned());
+        println!("cargo:rustc-link-lib={}=tamp", kind);
+    } else {
+        compile_vendored(&target, cflags, lazy);
+    }
+
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
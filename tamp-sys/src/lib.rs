@@ -3,3 +3,15 @@
 #![allow(non_upper_case_globals)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// True if the C library was built with lazy matching (`TAMP_LAZY_MATCHING`).
+pub const LAZY_MATCHING: bool = cfg!(tamp_lazy_matching);
+
+/// Sets the `lazy_matching` field of `conf`, which only exists when the C library was built
+/// with lazy matching; otherwise `conf` is left unchanged.
+pub fn tamp_conf_set_lazy_matching(conf: &mut TampConf, enabled: bool) {
+    #[cfg(tamp_lazy_matching)]
+    conf.set_lazy_matching(enabled as u16);
+    #[cfg(not(tamp_lazy_matching))]
+    let _ = (conf, enabled);
+}
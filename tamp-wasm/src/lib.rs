@@ -0,0 +1,129 @@
+//! WebAssembly bindings for decompressing tamp streams in the browser.
+//!
+//! Exposes a one-shot `decompress(Uint8Array) -> Uint8Array` and a streaming
+//! `Decompressor` that accepts chunks as they arrive, e.g. from a WebSocket.
+#![deny(missing_docs)]
+
+use tamp::{Config, DynDecompressor, Error};
+use wasm_bindgen::prelude::*;
+
+/// Size of the staging buffer decoded output is written through.
+const OUTPUT_SIZE: usize = 4096;
+
+fn js_error(e: Error) -> JsError {
+    JsError::new(&format!("tamp: {:?}", e))
+}
+
+/// Streaming decompressor, reads the configuration from the stream header.
+#[wasm_bindgen]
+pub struct Decompressor {
+    dictionary: Option<Vec<u8>>,
+    /// Input buffered until the header is complete.
+    pending: Vec<u8>,
+    inner: Option<DynDecompressor<'static>>,
+}
+
+#[wasm_bindgen]
+impl Decompressor {
+    /// Creates a decompressor. The dictionary is required if the stream was
+    /// compressed with a custom dictionary.
+    #[wasm_bindgen(constructor)]
+    pub fn new(dictionary: Option<Vec<u8>>) -> Decompressor {
+        Decompressor {
+            dictionary,
+            pending: Vec::new(),
+            inner: None,
+        }
+    }
+
+    /// Feeds a chunk of compressed data and returns the bytes decoded so far.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, JsError> {
+        let mut output = Vec::new();
+        match &mut self.inner {
+            Some(inner) => drain(inner, chunk, &mut output).map_err(js_error)?,
+            None => {
+                self.pending.extend_from_slice(chunk);
+                let (config, header_len) = match Config::from_header(&self.pending) {
+                    Ok(header) => header,
+                    // Header not complete yet, wait for more input
+                    Err(Error::InputExhausted) => return Ok(output),
+                    Err(e) => return Err(js_error(e)),
+                };
+                if config.use_custom_dictionary && self.dictionary.is_none() {
+                    return Err(JsError::new("tamp: stream requires a custom dictionary"));
+                }
+                let inner = self.inner.insert(
+                    DynDecompressor::allocate(config, self.dictionary.as_deref())
+                        .map_err(js_error)?,
+                );
+                let pending = core::mem::take(&mut self.pending);
+                drain(inner, &pending[header_len..], &mut output).map_err(js_error)?;
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Decodes `input` until it is consumed and no more output is produced.
+fn drain(
+    inner: &mut DynDecompressor<'static>,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let mut buffer = [0u8; OUTPUT_SIZE];
+    loop {
+        let (consumed, written) = inner.decompress_chunk(input, &mut buffer)?;
+        output.extend_from_slice(&buffer[..written]);
+        input = &input[consumed..];
+        if consumed == 0 && written == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Decompresses a complete tamp stream.
+#[wasm_bindgen]
+pub fn decompress(data: &[u8], dictionary: Option<Vec<u8>>) -> Result<Vec<u8>, JsError> {
+    Decompressor::new(dictionary).push(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed(data: &[u8], config: Config, dictionary: Option<&[u8]>) -> Vec<u8> {
+        let mut compressor = tamp::DynCompressor::allocate(config, dictionary).unwrap();
+        let mut stream = Vec::new();
+        compressor.compress_into(data, &mut stream).unwrap();
+        compressor.flush_into(&mut stream, false).unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_decompress_all_windows() {
+        let data = b"websocket frame, websocket frame, websocket payload ".repeat(200);
+        for window in 8..=15 {
+            let config = Config::new().window_bits(window).unwrap();
+            let stream = compressed(&data, config, None);
+            assert_eq!(decompress(&stream, None).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_push_split_header() {
+        let data = b"chunk by chunk, byte by byte".repeat(30);
+        let dictionary = b"chunk by byte ".repeat(80);
+        let config = Config::new()
+            .window_bits(10)
+            .unwrap()
+            .custom_dictionary(true);
+        let stream = compressed(&data, config, Some(&dictionary));
+
+        let mut decompressor = Decompressor::new(Some(dictionary));
+        let mut output = Vec::new();
+        for byte in stream.chunks(1) {
+            output.extend(decompressor.push(byte).unwrap());
+        }
+        assert_eq!(output, data);
+    }
+}
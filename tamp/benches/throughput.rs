@@ -0,0 +1,164 @@
+//! Throughput and ratio over the Canterbury corpus.
+//!
+//! Run with `cargo bench -p tamp`. Compression ratios are printed once per configuration
+//! before the timed runs, since criterion only reports time.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use std::path::Path;
+use tamp::{Config, DynCompressor, DynDecompressor};
+
+const WINDOW_BITS: [u8; 8] = [8, 9, 10, 11, 12, 13, 14, 15];
+const CHUNK_SIZES: [usize; 3] = [16, 256, 4096];
+
+/// Concatenates the Canterbury corpus into one buffer.
+fn corpus() -> Vec<u8> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/canterbury-corpus/canterbury");
+    let mut entries: Vec<_> = std::fs::read_dir(&dir)
+        .expect("Canterbury corpus missing, run `git submodule update --init`")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file() && path.file_name().unwrap() != "SHA1SUM")
+        .collect();
+    entries.sort();
+    entries
+        .iter()
+        .flat_map(|path| std::fs::read(path).unwrap())
+        .collect()
+}
+
+fn config(window_bits: u8, lazy: bool) -> Config {
+    Config::new()
+        .window_bits(window_bits)
+        .unwrap()
+        .lazy_matching(lazy)
+}
+
+/// Compresses `input` feeding it `chunk` bytes at a time.
+fn compress(input: &[u8], config: &Config, chunk: usize) -> Vec<u8> {
+    let mut window = vec![0u8; config.window_size()];
+    let mut compressor = DynCompressor::new(config.clone(), &mut window).unwrap();
+    let mut output = vec![0u8; input.len() + input.len() / 8 + 64];
+    let mut written = 0;
+    for mut piece in input.chunks(chunk) {
+        while !piece.is_empty() {
+            let (consumed, n) = compressor
+                .compress_chunk(piece, &mut output[written..])
+                .unwrap();
+            piece = &piece[consumed..];
+            written += n;
+        }
+    }
+    written += compressor.flush(&mut output[written..], false).unwrap();
+    output.truncate(written);
+    output
+}
+
+fn decompress(stream: &[u8], output: &mut [u8]) -> usize {
+    let mut window = [0u8; 1 << 15];
+    let (config, _) = Config::from_header(stream).unwrap();
+    let (mut decompressor, header) =
+        DynDecompressor::from_header(stream, &mut window[..config.window_size()]).unwrap();
+    let (_, written) = decompressor
+        .decompress_chunk(&stream[header..], output)
+        .unwrap();
+    written
+}
+
+fn bench_compress(c: &mut Criterion) {
+    let input = corpus();
+    let mut group = c.benchmark_group("compress");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    for bits in WINDOW_BITS {
+        for lazy in [false, true] {
+            let config = config(bits, lazy);
+            let ratio = input.len() as f64 / compress(&input, &config, 4096).len() as f64;
+            println!("window_bits={bits} lazy={lazy}: ratio {ratio:.3}");
+            let id = BenchmarkId::new(if lazy { "lazy" } else { "greedy" }, bits);
+            group.bench_with_input(id, &config, |b, config| {
+                b.iter(|| compress(black_box(&input), config, 4096))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_chunk_size(c: &mut Criterion) {
+    let input = corpus();
+    let config = config(10, false);
+    let mut group = c.benchmark_group("compress_chunk_size");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    for chunk in CHUNK_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(chunk), &chunk, |b, &chunk| {
+            b.iter(|| compress(black_box(&input), &config, chunk))
+        });
+    }
+    group.finish();
+}
+
+fn bench_decompress(c: &mut Criterion) {
+    let input = corpus();
+    let mut output = vec![0u8; input.len()];
+    let mut group = c.benchmark_group("decompress");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for bits in WINDOW_BITS {
+        let stream = compress(&input, &config(bits, false), 4096);
+        group.bench_with_input(BenchmarkId::from_parameter(bits), &stream, |b, stream| {
+            b.iter(|| decompress(black_box(stream), &mut output))
+        });
+    }
+    group.finish();
+}
+
+/// Compresses `input` with the pure-Rust compressor and a 1 KiB window.
+#[cfg(feature = "rust-backend")]
+fn compress_rust(input: &[u8], config: &Config) -> Vec<u8> {
+    let mut compressor = tamp::backend::RustCompressor::<1024>::new(config.clone()).unwrap();
+    let mut output = vec![0u8; input.len() + input.len() / 8 + 64];
+    let mut written = 0;
+    for mut piece in input.chunks(4096) {
+        while !piece.is_empty() {
+            let (consumed, n) = compressor
+                .compress_chunk(piece, &mut output[written..])
+                .unwrap();
+            piece = &piece[consumed..];
+            written += n;
+        }
+    }
+    written += compressor.flush(&mut output[written..], false).unwrap();
+    output.truncate(written);
+    output
+}
+
+/// Ratio and speed of the match finders of the pure-Rust compressor, on the first 256 KiB
+/// of the corpus since the exhaustive search is slow.
+#[cfg(feature = "rust-backend")]
+fn bench_match_finder(c: &mut Criterion) {
+    let mut input = corpus();
+    input.truncate(256 * 1024);
+    let mut group = c.benchmark_group("match_finder");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    for (name, search) in [
+        ("exhaustive", tamp::Search::Exhaustive),
+        ("hash_chain", tamp::Search::HashChain),
+        ("fast", tamp::Search::Fast),
+    ] {
+        let config = config(10, false).search(search);
+        let ratio = input.len() as f64 / compress_rust(&input, &config).len() as f64;
+        println!("rust backend, {name}: ratio {ratio:.3}");
+        group.bench_with_input(BenchmarkId::from_parameter(name), &config, |b, config| {
+            b.iter(|| compress_rust(black_box(&input), config))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_compress, bench_chunk_size, bench_decompress);
+#[cfg(feature = "rust-backend")]
+criterion_group!(backend, bench_match_finder);
+#[cfg(feature = "rust-backend")]
+criterion_main!(benches, backend);
+#[cfg(not(feature = "rust-backend"))]
+criterion_main!(benches);
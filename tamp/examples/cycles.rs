@@ -0,0 +1,89 @@
+//! Cortex-M firmware that counts the cycles `compress_chunk` takes, for comparing C builds of
+//! the library on a board, e.g. with and without the `cortex-m` feature. The count is printed
+//! over semihosting, so run it under a debug probe; see "Cortex-M tuning" in the tamp-sys
+//! README for the commands.
+#![cfg_attr(all(target_arch = "arm", target_os = "none"), no_std, no_main)]
+
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+mod firmware {
+    use core::hint::black_box;
+    use core::panic::PanicInfo;
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use cortex_m::peripheral::{Peripherals, SYST, syst::SystClkSource};
+    use cortex_m_rt::{entry, exception};
+    use cortex_m_semihosting::{debug, hprintln};
+
+    /// SysTick wraps while timing.
+    static WRAPS: AtomicU32 = AtomicU32::new(0);
+
+    #[panic_handler]
+    fn panic(info: &PanicInfo) -> ! {
+        hprintln!("{}", info);
+        debug::exit(debug::EXIT_FAILURE);
+        loop {}
+    }
+
+    /// Compresses 2 KiB of text with a noisy stretch; sample setup is timed too, but it is
+    /// the same for every C build.
+    fn compress(output: &mut [u8]) -> usize {
+        let mut sample = [0u8; 2048];
+        for (i, byte) in sample.iter_mut().enumerate() {
+            *byte = if (1024..1280).contains(&i) {
+                ((i as u32).wrapping_mul(2654435761) >> 13) as u8
+            } else {
+                b"the quick brown fox jumps over the lazy dog, "[i % 45]
+            };
+        }
+        let config = tamp::Config::new();
+        let mut compressor = tamp::Compressor::<1024>::new(config).unwrap();
+        let mut input = &sample[..];
+        let mut written = 0;
+        while !input.is_empty() {
+            let (consumed, n) = compressor
+                .compress_chunk(input, &mut output[written..])
+                .unwrap();
+            input = &input[consumed..];
+            written += n;
+        }
+        written + compressor.flush(&mut output[written..], false).unwrap()
+    }
+
+    /// Times `compress` with SysTick on the core clock, counting its wraps in the handler.
+    /// Unlike the DWT cycle counter it exists on every Cortex-M, the M0+ included.
+    fn cycles(mut peripherals: Peripherals, output: &mut [u8]) -> (u32, usize) {
+        const RELOAD: u32 = 0x00ff_ffff;
+        let syst = &mut peripherals.SYST;
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(RELOAD);
+        syst.clear_current();
+        syst.enable_interrupt();
+        syst.enable_counter();
+        let start = SYST::get_current();
+        let len = black_box(compress(output));
+        let end = SYST::get_current();
+        syst.disable_counter();
+        // The counter runs down from RELOAD, each wrap adds RELOAD + 1
+        let wraps = WRAPS.load(Ordering::Relaxed);
+        let ticks = wraps.wrapping_mul(RELOAD + 1).wrapping_add(start).wrapping_sub(end);
+        (ticks, len)
+    }
+
+    #[exception]
+    fn SysTick() {
+        // Only this handler writes, so a load and store is enough without CAS
+        WRAPS.store(WRAPS.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    }
+
+    #[entry]
+    fn main() -> ! {
+        let peripherals = Peripherals::take().unwrap();
+        let mut output = [0u8; 2048];
+        let (cycles, len) = cycles(peripherals, &mut output);
+        hprintln!("compress_chunk: 2048 -> {} bytes in {} cycles", len, cycles);
+        debug::exit(debug::EXIT_SUCCESS);
+        loop {}
+    }
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+fn main() {}
@@ -0,0 +1,6 @@
+/* Generic Cortex-M0+ memory map for the size example; the numbers only need to fit. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 64K
+  RAM : ORIGIN = 0x20000000, LENGTH = 8K
+}
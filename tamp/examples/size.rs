@@ -0,0 +1,64 @@
+//! Minimal Cortex-M firmware for measuring what the codecs cost in flash.
+//!
+//! It only touches the codecs enabled by features, so comparing `.text` across feature sets
+//! shows what each one adds. See "Code size" in the README for the build command.
+//!
+//! Host builds get an empty `main`, which keeps `cargo clippy --all-targets` working.
+#![cfg_attr(all(target_arch = "arm", target_os = "none"), no_std, no_main)]
+
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+mod firmware {
+    use core::hint::black_box;
+    use core::panic::PanicInfo;
+    use cortex_m_rt::entry;
+
+    #[panic_handler]
+    fn panic(_: &PanicInfo) -> ! {
+        loop {}
+    }
+
+    #[entry]
+    fn main() -> ! {
+        let input = black_box([0u8; 64]);
+        let mut stream = [0u8; 128];
+        #[allow(unused_mut)]
+        let mut len = 0;
+
+        #[cfg(feature = "compressor")]
+        {
+            let config = tamp::Config::new().window_bits(8).unwrap();
+            let mut compressor = tamp::Compressor::<256>::new(config).unwrap();
+            let (_, n) = compressor.compress_chunk(&input, &mut stream).unwrap();
+            len = n + compressor.flush(&mut stream[n..], false).unwrap();
+        }
+
+        #[cfg(all(feature = "compressor", feature = "dictionary"))]
+        {
+            let dictionary = black_box([0x20u8; 256]);
+            let config = tamp::Config::new()
+                .window_bits(8)
+                .unwrap()
+                .custom_dictionary(true);
+            let mut compressor =
+                tamp::Compressor::<256>::with_dictionary(config, Some(&dictionary)).unwrap();
+            black_box(compressor.compress_chunk(&input, &mut [0u8; 64]).unwrap());
+        }
+
+        #[cfg(feature = "decompressor")]
+        {
+            let mut output = [0u8; 64];
+            let (mut decompressor, header) =
+                tamp::Decompressor::<256>::from_header(&stream[..len]).unwrap();
+            let (_, n) = decompressor
+                .decompress_chunk(&stream[header..len], &mut output)
+                .unwrap();
+            black_box(&output[..n]);
+        }
+
+        black_box((&stream, len));
+        loop {}
+    }
+}
+
+#[cfg(not(all(target_arch = "arm", target_os = "none")))]
+fn main() {}
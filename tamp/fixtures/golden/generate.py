@@ -0,0 +1,28 @@
+"""Regenerates the golden vectors with the upstream Python implementation.
+
+    pip install tamp
+    python tamp/fixtures/golden/generate.py
+
+Compresses the checked-in `input.bin` into one `w{window}_l{literal}_greedy.tamp` stream per
+configuration. The Rust test suite decodes every stream and checks that compressing
+`input.bin` with the same configuration reproduces it bit for bit.
+"""
+
+import itertools
+import pathlib
+
+import tamp
+
+HERE = pathlib.Path(__file__).parent
+
+
+def main():
+    data = (HERE / "input.bin").read_bytes()
+    for window, literal in itertools.product((8, 10, 12), (7, 8)):
+        name = f"w{window}_l{literal}_greedy.tamp"
+        compressed = tamp.compress(data, window=window, literal=literal, lazy_matching=False)
+        (HERE / name).write_bytes(compressed)
+
+
+if __name__ == "__main__":
+    main()
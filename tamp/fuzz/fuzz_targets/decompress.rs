@@ -0,0 +1,44 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tamp::{Config, Decompressor};
+
+/// Stop after this many output bytes, so highly compressible inputs stay fast.
+const OUTPUT_LIMIT: usize = 1 << 16;
+
+fn decompress<const N: usize>(data: &[u8]) {
+    let Ok((mut decompressor, header)) = Decompressor::<N>::from_header(data) else {
+        return;
+    };
+    let mut input = &data[header..];
+    // Odd-sized output so matches get split across calls
+    let mut output = [0u8; 251];
+    let mut total = 0;
+    while total < OUTPUT_LIMIT {
+        let Ok((consumed, written)) = decompressor.decompress_chunk(input, &mut output) else {
+            return;
+        };
+        input = &input[consumed..];
+        total += written;
+        if consumed == 0 && written == 0 {
+            return;
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((config, _)) = Config::from_header(data) else {
+        return;
+    };
+    match config.window_bits {
+        8 => decompress::<256>(data),
+        9 => decompress::<512>(data),
+        10 => decompress::<1024>(data),
+        11 => decompress::<2048>(data),
+        12 => decompress::<4096>(data),
+        13 => decompress::<8192>(data),
+        14 => decompress::<16384>(data),
+        15 => decompress::<32768>(data),
+        _ => unreachable!("from_header only accepts 8-15 window bits"),
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tamp::Config;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((config, consumed)) = Config::from_header(data) {
+        assert!(consumed <= data.len());
+        assert!((8..=15).contains(&config.window_bits));
+        assert!((5..=8).contains(&config.literal_bits));
+    }
+});
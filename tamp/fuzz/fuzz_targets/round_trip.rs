@@ -0,0 +1,41 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tamp::{Compressor, Config, Decompressor};
+
+fn round_trip<const N: usize>(config: Config, input: &[u8]) {
+    let mut compressor = Compressor::<N>::new(config).unwrap();
+    // Literals cost at most 9 bits each, plus the final flush
+    let mut compressed = vec![0u8; input.len() * 2 + 64];
+    let (consumed, mut len) = compressor.compress_chunk(input, &mut compressed).unwrap();
+    assert_eq!(consumed, input.len());
+    len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+    let (mut decompressor, header) = Decompressor::<N>::from_header(&compressed).unwrap();
+    let mut output = vec![0u8; input.len()];
+    let (_, written) = decompressor
+        .decompress_chunk(&compressed[header..len], &mut output)
+        .unwrap();
+    assert_eq!(&output[..written], input);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, input)) = data.split_first() else {
+        return;
+    };
+    let literal_bits = 5 + (selector >> 2 & 3);
+    let config = Config::new()
+        .window_bits(8 + (selector & 3))
+        .unwrap()
+        .literal_bits(literal_bits)
+        .unwrap()
+        .lazy_matching(selector & 0x10 != 0);
+    let mask = ((1u16 << literal_bits) - 1) as u8;
+    let input: Vec<u8> = input.iter().map(|&b| b & mask).collect();
+    match config.window_bits {
+        8 => round_trip::<256>(config, &input),
+        9 => round_trip::<512>(config, &input),
+        10 => round_trip::<1024>(config, &input),
+        _ => round_trip::<2048>(config, &input),
+    }
+});
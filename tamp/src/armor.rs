@@ -0,0 +1,329 @@
+//! Text armor for compressed streams.
+//!
+//! Some transports only carry printable text: AT command payloads, JSON string fields,
+//! NMEA-style sentences. `ArmorEncoder` turns bytes into line-wrapped Base64 or hex as they
+//! are produced, and `ArmorDecoder` reverses it as text arrives, so neither side has to
+//! hold the whole stream.
+//!
+//! ```ignore
+//! let mut armor = ArmorEncoder::new(Encoding::Base64, 76, |text| modem.send(text));
+//! let mut writer = CompressingFmtWriter::new(&mut compressor, |bytes| armor.write(bytes));
+//! write!(writer, "{reading}")?;
+//! writer.finish()?;
+//! armor.finish();
+//! ```
+
+use crate::Error;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Text encoding used by the armor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Standard Base64 alphabet with `=` padding, 4 characters per 3 bytes.
+    Base64,
+    /// Lowercase hex, 2 characters per byte. Uppercase is accepted when decoding.
+    Hex,
+}
+
+/// Number of characters `ArmorEncoder` produces for `len` bytes, including line breaks.
+/// `line_len` of 0 disables wrapping.
+pub const fn encoded_len(encoding: Encoding, line_len: usize, len: usize) -> usize {
+    let chars = match encoding {
+        Encoding::Base64 => len.div_ceil(3) * 4,
+        Encoding::Hex => len * 2,
+    };
+    if line_len == 0 {
+        chars
+    } else {
+        chars + chars.div_ceil(line_len)
+    }
+}
+
+/// Streaming Base64 or hex encoder, passing text to `sink` in chunks of at most 64 bytes.
+pub struct ArmorEncoder<F: FnMut(&[u8])> {
+    encoding: Encoding,
+    line_len: usize,
+    column: usize,
+    group: [u8; 3],
+    grouped: usize,
+    out: [u8; 64],
+    out_len: usize,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8])> ArmorEncoder<F> {
+    /// Creates an encoder breaking lines with `\n` every `line_len` characters.
+    /// `line_len` of 0 produces a single line.
+    pub fn new(encoding: Encoding, line_len: usize, sink: F) -> Self {
+        Self {
+            encoding,
+            line_len,
+            column: 0,
+            group: [0; 3],
+            grouped: 0,
+            out: [0; 64],
+            out_len: 0,
+            sink,
+        }
+    }
+
+    /// Encodes `bytes`. A partial Base64 group is held back until more bytes or `finish()`.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            match self.encoding {
+                Encoding::Hex => {
+                    self.push(HEX[(byte >> 4) as usize]);
+                    self.push(HEX[(byte & 0xf) as usize]);
+                }
+                Encoding::Base64 => {
+                    self.group[self.grouped] = byte;
+                    self.grouped += 1;
+                    if self.grouped == 3 {
+                        self.push_group();
+                    }
+                }
+            }
+        }
+        self.drain();
+    }
+
+    /// Pads the last Base64 group and ends the final line.
+    pub fn finish(mut self) {
+        if self.grouped > 0 {
+            self.group[self.grouped..].fill(0);
+            self.push_group();
+        }
+        if self.line_len > 0 && self.column > 0 {
+            self.push_raw(b'\n');
+        }
+        self.drain();
+    }
+
+    /// Emits the buffered Base64 group, padding if it holds fewer than 3 bytes.
+    fn push_group(&mut self) {
+        let [a, b, c] = self.group;
+        let sextets = [
+            a >> 2,
+            (a << 4 | b >> 4) & 0x3f,
+            (b << 2 | c >> 6) & 0x3f,
+            c & 0x3f,
+        ];
+        for (i, sextet) in sextets.into_iter().enumerate() {
+            self.push(if i <= self.grouped {
+                BASE64[sextet as usize]
+            } else {
+                b'='
+            });
+        }
+        self.grouped = 0;
+    }
+
+    fn push(&mut self, char: u8) {
+        if self.line_len > 0 && self.column == self.line_len {
+            self.push_raw(b'\n');
+            self.column = 0;
+        }
+        self.push_raw(char);
+        self.column += 1;
+    }
+
+    fn push_raw(&mut self, char: u8) {
+        if self.out_len == self.out.len() {
+            self.drain();
+        }
+        self.out[self.out_len] = char;
+        self.out_len += 1;
+    }
+
+    fn drain(&mut self) {
+        if self.out_len > 0 {
+            (self.sink)(&self.out[..self.out_len]);
+            self.out_len = 0;
+        }
+    }
+}
+
+/// Streaming Base64 or hex decoder, passing bytes to `sink` in chunks of at most 48 bytes.
+///
+/// Whitespace, including line breaks, is skipped anywhere in the text.
+pub struct ArmorDecoder<F: FnMut(&[u8])> {
+    encoding: Encoding,
+    group: [u8; 4],
+    grouped: usize,
+    padding: usize,
+    done: bool,
+    out: [u8; 48],
+    out_len: usize,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8])> ArmorDecoder<F> {
+    /// Creates a decoder passing decoded bytes to `sink`.
+    pub fn new(encoding: Encoding, sink: F) -> Self {
+        Self {
+            encoding,
+            group: [0; 4],
+            grouped: 0,
+            padding: 0,
+            done: false,
+            out: [0; 48],
+            out_len: 0,
+            sink,
+        }
+    }
+
+    /// Decodes `text`, which may be split anywhere.
+    /// Returns `InvalidArmor` on a character outside the alphabet or after Base64 padding.
+    pub fn write(&mut self, text: &[u8]) -> Result<(), Error> {
+        let result = self.decode(text);
+        self.drain();
+        result
+    }
+
+    /// Checks that the text did not end inside a group.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.drain();
+        if self.grouped != 0 {
+            return Err(Error::InvalidArmor);
+        }
+        Ok(())
+    }
+
+    fn decode(&mut self, text: &[u8]) -> Result<(), Error> {
+        for &char in text {
+            if char.is_ascii_whitespace() {
+                continue;
+            }
+            if self.done {
+                return Err(Error::InvalidArmor);
+            }
+            match self.encoding {
+                Encoding::Hex => {
+                    let value = match char {
+                        b'0'..=b'9' => char - b'0',
+                        b'a'..=b'f' => char - b'a' + 10,
+                        b'A'..=b'F' => char - b'A' + 10,
+                        _ => return Err(Error::InvalidArmor),
+                    };
+                    self.group[self.grouped] = value;
+                    self.grouped += 1;
+                    if self.grouped == 2 {
+                        self.push(self.group[0] << 4 | self.group[1]);
+                        self.grouped = 0;
+                    }
+                }
+                Encoding::Base64 => {
+                    let value = if char == b'=' {
+                        if self.grouped < 2 {
+                            return Err(Error::InvalidArmor);
+                        }
+                        self.padding += 1;
+                        0
+                    } else if self.padding > 0 {
+                        return Err(Error::InvalidArmor);
+                    } else {
+                        BASE64
+                            .iter()
+                            .position(|&c| c == char)
+                            .ok_or(Error::InvalidArmor)? as u8
+                    };
+                    self.group[self.grouped] = value;
+                    self.grouped += 1;
+                    if self.grouped == 4 {
+                        let [a, b, c, d] = self.group;
+                        let bytes = [a << 2 | b >> 4, b << 4 | c >> 2, c << 6 | d];
+                        for &byte in &bytes[..3 - self.padding] {
+                            self.push(byte);
+                        }
+                        self.grouped = 0;
+                        self.done = self.padding > 0;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.out_len == self.out.len() {
+            self.drain();
+        }
+        self.out[self.out_len] = byte;
+        self.out_len += 1;
+    }
+
+    fn drain(&mut self) {
+        if self.out_len > 0 {
+            (self.sink)(&self.out[..self.out_len]);
+            self.out_len = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    fn encode(encoding: Encoding, line_len: usize, data: &[u8]) -> Vec<u8> {
+        let mut text = Vec::new();
+        let mut encoder =
+            ArmorEncoder::new(encoding, line_len, |t: &[u8]| text.extend_from_slice(t));
+        for chunk in data.chunks(5) {
+            encoder.write(chunk);
+        }
+        encoder.finish();
+        text
+    }
+
+    fn decode(encoding: Encoding, text: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let mut decoder = ArmorDecoder::new(encoding, |b: &[u8]| data.extend_from_slice(b));
+        for chunk in text.chunks(7) {
+            decoder.write(chunk)?;
+        }
+        decoder.finish()?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_armor_round_trip() {
+        assert_eq!(encode(Encoding::Base64, 0, b"tamp!"), b"dGFtcCE=");
+        assert_eq!(encode(Encoding::Hex, 0, b"\x00\xfe"), b"00fe");
+
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 37 % 256) as u8).collect();
+        for encoding in [Encoding::Base64, Encoding::Hex] {
+            for len in [0, 1, 2, 3, 199, 200] {
+                let text = encode(encoding, 76, &data[..len]);
+                assert_eq!(text.len(), encoded_len(encoding, 76, len));
+                assert!(text.split(|&c| c == b'\n').all(|line| line.len() <= 76));
+                assert_eq!(decode(encoding, &text).unwrap(), &data[..len]);
+            }
+        }
+
+        assert_eq!(decode(Encoding::Hex, b"0A\r\nfF").unwrap(), [0x0a, 0xff]);
+        assert!(matches!(
+            decode(Encoding::Hex, b"0g"),
+            Err(Error::InvalidArmor)
+        ));
+        assert!(matches!(
+            decode(Encoding::Hex, b"abc"),
+            Err(Error::InvalidArmor)
+        ));
+        assert!(matches!(
+            decode(Encoding::Base64, b"dGFt="),
+            Err(Error::InvalidArmor)
+        ));
+        assert!(matches!(
+            decode(Encoding::Base64, b"dG=xdGFt"),
+            Err(Error::InvalidArmor)
+        ));
+        assert!(matches!(
+            decode(Encoding::Base64, b"dA==dGFt"),
+            Err(Error::InvalidArmor)
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+//! Text mode for 7-bit ASCII using `literal_bits = 7`.
+//!
+//! Seven-bit literals save one bit per unmatched byte, but any byte >= 0x80 makes the
+//! compressor fail with `ExcessBits`. These wrappers configure the literal size, check or
+//! sanitize input up front, and hand decompressed text back as `&str`.
+
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::{Config, Error};
+
+/// How the compressor treats bytes outside 7-bit ASCII.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsciiMode {
+    /// Reject input containing them with `Error::NonAscii`.
+    Validate,
+    /// Replace each of them with `?`.
+    Force,
+}
+
+/// Returns `config` with 7-bit literals.
+fn ascii_config(mut config: Config) -> Config {
+    config.literal_bits = 7;
+    config
+}
+
+/// Compressor for 7-bit ASCII text.
+#[cfg(feature = "compressor")]
+pub struct AsciiCompressor<const N: usize> {
+    inner: Compressor<N>,
+    mode: AsciiMode,
+}
+
+#[cfg(feature = "compressor")]
+impl<const N: usize> AsciiCompressor<N> {
+    /// Creates a text compressor. The literal size of `config` is overridden to 7 bits.
+    pub fn new(config: Config, mode: AsciiMode) -> Result<Self, Error> {
+        Ok(Self {
+            inner: Compressor::new(ascii_config(config))?,
+            mode,
+        })
+    }
+
+    /// Compresses `text` into `output`. Returns (input_consumed, output_written); like
+    /// `Compressor::compress_chunk`, a full `output` stops early with the progress made.
+    /// In `Validate` mode nothing is compressed if `text` contains a non-ASCII byte.
+    pub fn compress_chunk(
+        &mut self,
+        text: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        match self.mode {
+            AsciiMode::Validate => {
+                if let Some(pos) = text.iter().position(|b| !b.is_ascii()) {
+                    return Err(Error::NonAscii(pos));
+                }
+                self.inner.compress_chunk(text, output)
+            }
+            AsciiMode::Force => {
+                let (mut consumed, mut written) = (0, 0);
+                let mut buf = [0u8; 64];
+                for chunk in text.chunks(buf.len()) {
+                    for (dst, &src) in buf.iter_mut().zip(chunk) {
+                        *dst = if src.is_ascii() { src } else { b'?' };
+                    }
+                    let (c, n) = self
+                        .inner
+                        .compress_chunk(&buf[..chunk.len()], &mut output[written..])?;
+                    consumed += c;
+                    written += n;
+                    // A full output leaves the rest of `text` for the next call
+                    if c < chunk.len() {
+                        break;
+                    }
+                }
+                Ok((consumed, written))
+            }
+        }
+    }
+
+    /// Flushes remaining data, see `Compressor::flush`.
+    pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        self.inner.flush(output, write_token)
+    }
+
+    /// Returns the underlying compressor.
+    pub fn into_inner(self) -> Compressor<N> {
+        self.inner
+    }
+}
+
+/// Decompressor for 7-bit ASCII text.
+#[cfg(feature = "decompressor")]
+pub struct AsciiDecompressor<const N: usize> {
+    inner: Decompressor<N>,
+}
+
+#[cfg(feature = "decompressor")]
+impl<const N: usize> AsciiDecompressor<N> {
+    /// Creates a text decompressor. The literal size of `config` is overridden to 7 bits.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        Ok(Self {
+            inner: Decompressor::new(ascii_config(config))?,
+        })
+    }
+
+    /// Creates a text decompressor from a stream header, which must declare 7-bit literals.
+    /// Returns (decompressor, bytes_consumed_from_input).
+    pub fn from_header(input: &[u8]) -> Result<(Self, usize), Error> {
+        let (config, consumed) = Config::from_header(input)?;
+        if config.literal_bits != 7 {
+            return Err(Error::InvalidConfig("Stream does not use 7-bit literals"));
+        }
+        Ok((Self::new(config)?, consumed))
+    }
+
+    /// Decompresses `input` into `output` and returns the decoded text.
+    /// Returns (input_consumed, text); fails with `NonAscii` if a custom dictionary injected
+    /// bytes outside 7-bit ASCII.
+    pub fn decompress_str<'o>(
+        &mut self,
+        input: &[u8],
+        output: &'o mut [u8],
+    ) -> Result<(usize, &'o str), Error> {
+        let (consumed, written) = self.inner.decompress_chunk(input, output)?;
+        let text = &output[..written];
+        if let Some(pos) = text.iter().position(|b| !b.is_ascii()) {
+            return Err(Error::NonAscii(pos));
+        }
+        // ASCII is always valid UTF-8
+        Ok((
+            consumed,
+            core::str::from_utf8(text).map_err(|_| Error::NonAscii(0))?,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_ascii_round_trip() {
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor =
+            AsciiCompressor::<256>::new(config.clone(), AsciiMode::Validate).unwrap();
+        let mut output = [0u8; 256];
+        assert!(matches!(
+            compressor.compress_chunk("caf\u{e9}".as_bytes(), &mut output),
+            Err(Error::NonAscii(3))
+        ));
+
+        let mut compressor = AsciiCompressor::<256>::new(config, AsciiMode::Force).unwrap();
+        let (_, mut len) = compressor
+            .compress_chunk("$GPGGA,caf\u{e9},$GPGGA".as_bytes(), &mut output)
+            .unwrap();
+        len += compressor.flush(&mut output[len..], false).unwrap();
+
+        let (mut decompressor, header) = AsciiDecompressor::<256>::from_header(&output).unwrap();
+        let mut text = [0u8; 64];
+        let (_, text) = decompressor
+            .decompress_str(&output[header..len], &mut text)
+            .unwrap();
+        assert_eq!(text, "$GPGGA,caf??,$GPGGA");
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_force_small_output() {
+        let input: [u8; 300] =
+            core::array::from_fn(|i| ((i as u32).wrapping_mul(2654435761) >> 13) as u8);
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = AsciiCompressor::<256>::new(config, AsciiMode::Force).unwrap();
+        let mut stream = [0u8; 512];
+        let (mut consumed, mut len) = (0, 0);
+        while consumed < input.len() {
+            let (c, n) = compressor
+                .compress_chunk(&input[consumed..], &mut stream[len..len + 24])
+                .unwrap();
+            consumed += c;
+            len += n;
+        }
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = AsciiDecompressor::<256>::from_header(&stream).unwrap();
+        let mut text = [0u8; 300];
+        let (_, text) = decompressor
+            .decompress_str(&stream[header..len], &mut text)
+            .unwrap();
+        let expected = input.map(|b| if b.is_ascii() { b } else { b'?' });
+        assert_eq!(text.as_bytes(), expected);
+    }
+}
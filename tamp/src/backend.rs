@@ -0,0 +1,950 @@
+//! Pure-Rust compressor with a pluggable match finder.
+//!
+//! `RustCompressor` produces the same stream format as `Compressor`, but searches the window
+//! with a `MatchFinder` the caller picks, so alternative strategies can be tried without
+//! forking the codec. `new()` picks one of the built-in finders from `Config::search`.
+//! `IndirectCompressor` compresses the same way into a window in external memory, see
+//! `WindowBlocks`.
+//!
+//! Nothing here calls into the C library, so code written against the `Backend` trait can
+//! be tested under Miri: `DefaultBackend` is the C `Compressor` in normal builds and
+//! `RustCompressor` under Miri, and `decompress()` decodes in pure Rust.
+//!
+//! ```ignore
+//! let mut compressor = RustCompressor::<1024>::with_finder(config, MyFinder::default())?;
+//! let (consumed, written) = compressor.compress_chunk(input, &mut output)?;
+//! written += compressor.flush(&mut output[written..], false)?;
+//! ```
+
+use crate::sink::SliceSink;
+use crate::tokens::{Token, TokenEncoder, Tokens};
+use crate::{Config, Error, Search};
+
+/// Window search strategy of a `RustCompressor`.
+pub trait MatchFinder {
+    /// Finds where the window repeats the start of `lookahead`, as (offset, len) with
+    /// `len >= min_len` and `offset + len <= window.len()`. Longer is better; None emits a
+    /// literal. A match whose bytes differ from `lookahead` is discarded.
+    fn find(&mut self, window: &[u8], lookahead: &[u8], min_len: usize) -> Option<(usize, usize)>;
+
+    /// Called after `window[pos]` was overwritten, for finders that index the window.
+    fn update(&mut self, window: &[u8], pos: usize) {
+        let _ = (window, pos);
+    }
+
+    /// Called when the window is initialized, before the first `find()`.
+    fn reset(&mut self, window: &[u8]) {
+        let _ = window;
+    }
+}
+
+/// Compares `lookahead` against every window position. Slow but finds the longest match,
+/// the first one on ties.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BruteForce;
+
+impl MatchFinder for BruteForce {
+    fn find(&mut self, window: &[u8], lookahead: &[u8], min_len: usize) -> Option<(usize, usize)> {
+        let mut best = None;
+        let mut best_len = min_len - 1;
+        for offset in 0..window.len() {
+            let len = common_prefix(&window[offset..], lookahead);
+            if len > best_len {
+                best = Some((offset, len));
+                best_len = len;
+                if len == lookahead.len() {
+                    break;
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Chains at most this many earlier positions per search.
+const CHAIN_DEPTH: usize = 64;
+/// Buckets of the hash chain head table.
+const HASH_BUCKETS: usize = 1024;
+/// Marks an empty chain link.
+const NONE: u16 = u16::MAX;
+
+/// Keeps, for each pair of leading bytes, a chain of the window positions starting with it,
+/// most recent first, and compares only against those.
+///
+/// Uses `2 * N + 2048` bytes. Chains are not cleaned up when the window is overwritten;
+/// stale links only cost comparisons, since every candidate is checked.
+pub struct HashChain<const N: usize> {
+    head: [u16; HASH_BUCKETS],
+    prev: [u16; N],
+}
+
+impl<const N: usize> HashChain<N> {
+    /// Creates empty chains; the compressor fills them from the initial window.
+    pub fn new() -> Self {
+        Self {
+            head: [NONE; HASH_BUCKETS],
+            prev: [NONE; N],
+        }
+    }
+
+    /// Links `pos` into the chain of the two bytes starting there.
+    fn insert(&mut self, window: &[u8], pos: usize) {
+        if pos + 1 < N {
+            let bucket = pair_bucket(window[pos], window[pos + 1]);
+            if self.head[bucket] == pos as u16 {
+                return;
+            }
+            self.prev[pos] = self.head[bucket];
+            self.head[bucket] = pos as u16;
+        }
+    }
+}
+
+impl<const N: usize> Default for HashChain<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MatchFinder for HashChain<N> {
+    fn find(&mut self, window: &[u8], lookahead: &[u8], min_len: usize) -> Option<(usize, usize)> {
+        let [a, b, ..] = *lookahead else {
+            return None;
+        };
+        let mut best = None;
+        let mut best_len = min_len - 1;
+        let mut pos = self.head[pair_bucket(a, b)];
+        for _ in 0..CHAIN_DEPTH {
+            if pos == NONE {
+                break;
+            }
+            let len = common_prefix(&window[pos as usize..], lookahead);
+            if len > best_len {
+                best = Some((pos as usize, len));
+                best_len = len;
+                if len == lookahead.len() {
+                    break;
+                }
+            }
+            pos = self.prev[pos as usize];
+        }
+        best
+    }
+
+    fn update(&mut self, window: &[u8], pos: usize) {
+        // The pair starting one byte earlier is now complete
+        if pos > 0 {
+            self.insert(window, pos - 1);
+        }
+    }
+
+    fn reset(&mut self, window: &[u8]) {
+        self.head.fill(NONE);
+        for pos in 0..N {
+            self.insert(window, pos);
+        }
+    }
+}
+
+/// Compares only against the most recent window position starting with the same two bytes,
+/// the least work per byte. Uses 512 bytes, independent of the window size.
+pub struct SingleProbe {
+    head: [u16; 256],
+}
+
+impl SingleProbe {
+    /// Creates an empty table; the compressor fills it from the initial window.
+    pub fn new() -> Self {
+        Self { head: [NONE; 256] }
+    }
+
+    fn bucket(a: u8, b: u8) -> usize {
+        (a.rotate_left(3) ^ b) as usize
+    }
+}
+
+impl Default for SingleProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MatchFinder for SingleProbe {
+    fn find(&mut self, window: &[u8], lookahead: &[u8], min_len: usize) -> Option<(usize, usize)> {
+        let [a, b, ..] = *lookahead else {
+            return None;
+        };
+        let pos = self.head[Self::bucket(a, b)];
+        if pos == NONE {
+            return None;
+        }
+        let len = common_prefix(&window[pos as usize..], lookahead);
+        (len >= min_len).then_some((pos as usize, len))
+    }
+
+    fn update(&mut self, window: &[u8], pos: usize) {
+        if pos > 0 && pos < window.len() {
+            self.head[Self::bucket(window[pos - 1], window[pos])] = (pos - 1) as u16;
+        }
+    }
+
+    fn reset(&mut self, window: &[u8]) {
+        self.head.fill(NONE);
+        for pos in 1..window.len() {
+            self.update(window, pos);
+        }
+    }
+}
+
+/// The built-in finder `Config::search` selects.
+pub struct Configured<const N: usize> {
+    search: Search,
+    chain: HashChain<N>,
+    probe: SingleProbe,
+}
+
+impl<const N: usize> Configured<N> {
+    /// Creates the finder `search` selects.
+    pub fn new(search: Search) -> Self {
+        Self {
+            search,
+            chain: HashChain::new(),
+            probe: SingleProbe::new(),
+        }
+    }
+
+    /// The selected search.
+    pub fn search(&self) -> Search {
+        self.search
+    }
+}
+
+impl<const N: usize> MatchFinder for Configured<N> {
+    fn find(&mut self, window: &[u8], lookahead: &[u8], min_len: usize) -> Option<(usize, usize)> {
+        match self.search {
+            Search::Exhaustive => BruteForce.find(window, lookahead, min_len),
+            Search::HashChain => self.chain.find(window, lookahead, min_len),
+            Search::Fast => self.probe.find(window, lookahead, min_len),
+        }
+    }
+
+    fn update(&mut self, window: &[u8], pos: usize) {
+        match self.search {
+            Search::Exhaustive => {}
+            Search::HashChain => self.chain.update(window, pos),
+            Search::Fast => self.probe.update(window, pos),
+        }
+    }
+
+    fn reset(&mut self, window: &[u8]) {
+        match self.search {
+            Search::Exhaustive => {}
+            Search::HashChain => self.chain.reset(window),
+            Search::Fast => self.probe.reset(window),
+        }
+    }
+}
+
+/// Hash chain bucket of the byte pair `a`, `b`.
+fn pair_bucket(a: u8, b: u8) -> usize {
+    (((a as usize) << 2) ^ b as usize) % HASH_BUCKETS
+}
+
+/// Length of the common prefix of `a` and `b`.
+pub(crate) fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Streaming compressor implemented in Rust, generic over its match finder.
+///
+/// `N` is the window buffer size in bytes and must equal 2^window_bits. Matches are chosen
+/// greedily; `lazy_matching` is ignored. The default finder reserves room for a hash chain
+/// even when `Config::search` does not ask for one; pass `BruteForce` or `SingleProbe` to
+/// `with_finder()` to save that RAM.
+pub struct RustCompressor<const N: usize, F: MatchFinder = Configured<N>> {
+    window: [u8; N],
+    pos: usize,
+    lookahead: [u8; 16],
+    len: usize,
+    min_len: usize,
+    max_len: usize,
+    encoder: TokenEncoder,
+    finder: F,
+}
+
+impl<const N: usize> RustCompressor<N> {
+    /// Creates a compressor searching as `config.search` selects.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let finder = Configured::new(config.search);
+        Self::with_finder(config, finder)
+    }
+}
+
+impl<const N: usize, F: MatchFinder> RustCompressor<N, F> {
+    /// Creates a compressor searching with `finder`.
+    pub fn with_finder(config: Config, finder: F) -> Result<Self, Error> {
+        Self::with_dictionary(config, finder, None)
+    }
+
+    /// Creates a compressor searching with `finder`, with the window initialized like
+    /// `Compressor::with_dictionary`.
+    pub fn with_dictionary(
+        config: Config,
+        finder: F,
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        if N != config.window_size() {
+            return Err(Error::InvalidConfig("Buffer size N must equal 2^window_bits"));
+        }
+        let mut window = [0u8; N];
+        if !config.use_custom_dictionary {
+            BuiltinDictionary::new().fill(&mut window);
+        } else if dictionary.is_none() {
+            return Err(Error::InvalidConfig("Custom dictionary enabled but none provided"));
+        }
+        if let Some(dict) = dictionary {
+            let len = dict.len().min(N);
+            window[..len].copy_from_slice(&dict[..len]);
+        }
+        let mut compressor = Self {
+            window,
+            pos: 0,
+            lookahead: [0; 16],
+            len: 0,
+            min_len: config.min_match_len(),
+            max_len: config.max_match_len(),
+            encoder: TokenEncoder::new(config)?,
+            finder,
+        };
+        compressor.finder.reset(&compressor.window);
+        Ok(compressor)
+    }
+
+    /// The match finder.
+    pub fn finder(&self) -> &F {
+        &self.finder
+    }
+
+    /// Writes one token for the start of the lookahead to `sink` and updates the window.
+    /// Returns false if `sink` is full.
+    fn step(&mut self, sink: &mut SliceSink) -> Result<bool, Error> {
+        let lookahead = &self.lookahead[..self.len];
+        let found = self
+            .finder
+            .find(&self.window, lookahead, self.min_len)
+            .filter(|&(offset, len)| {
+                (self.min_len..=self.len.min(self.max_len)).contains(&len)
+                    && self.window.get(offset..offset + len) == Some(&lookahead[..len])
+            });
+        let (token, n) = match found {
+            Some((offset, len)) => (
+                Token::Match {
+                    offset: offset as u16,
+                    len: len as u8,
+                },
+                len,
+            ),
+            None => (Token::Literal(self.lookahead[0]), 1),
+        };
+        match self.encoder.push(token, sink) {
+            Err(Error::OutputFull) => return Ok(false),
+            result => result?,
+        }
+        for i in 0..n {
+            self.window[self.pos] = self.lookahead[i];
+            self.finder.update(&self.window, self.pos);
+            self.pos = (self.pos + 1) % N;
+        }
+        self.lookahead.copy_within(n..self.len, 0);
+        self.len -= n;
+        Ok(true)
+    }
+
+    /// Compresses input data into output buffer.
+    /// Returns (input_consumed, output_written). Up to 16 bytes of input are held back for
+    /// matching until more input or `flush()` arrives.
+    pub fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let mut sink = SliceSink::new(output);
+        let mut consumed = 0;
+        loop {
+            let n = (self.max_len - self.len).min(input.len() - consumed);
+            self.lookahead[self.len..self.len + n].copy_from_slice(&input[consumed..][..n]);
+            self.len += n;
+            consumed += n;
+            if self.len < self.max_len || !self.step(&mut sink)? {
+                break;
+            }
+        }
+        Ok((consumed, sink.len()))
+    }
+
+    /// Compresses the buffered input and pads the output to a byte boundary, behind a flush
+    /// marker if `write_token` and the stream was not aligned. Returns bytes written.
+    /// Fails with `OutputFull`, writing nothing, unless `output` has room for the worst
+    /// case of all buffered bytes as literals; 32 bytes always suffice.
+    pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        let literal_bits = 1 + self.encoder.config().literal_bits as usize;
+        let worst = self.encoder.pending_bits() as usize + self.len * literal_bits + 16;
+        if output.len() < worst.div_ceil(8) {
+            return Err(Error::OutputFull);
+        }
+        let mut sink = SliceSink::new(output);
+        while self.len > 0 {
+            if !self.step(&mut sink)? {
+                return Err(Error::OutputFull);
+            }
+        }
+        if write_token && self.encoder.pending_bits() > 0 {
+            self.encoder.push(Token::FlushMarker, &mut sink)?;
+        }
+        self.encoder.finish(&mut sink)?;
+        Ok(sink.len())
+    }
+}
+
+/// The streaming compressor interface shared by the C library's `Compressor` and the
+/// pure-Rust `RustCompressor`, so callers and tests can pick the implementation with a type
+/// parameter.
+pub trait Backend: Sized {
+    /// Creates a compressor with the window initialized from the built-in dictionary.
+    fn new(config: Config) -> Result<Self, Error>;
+
+    /// Compresses input data into output buffer, see `Compressor::compress_chunk`.
+    fn compress_chunk(&mut self, input: &[u8], output: &mut [u8]) -> Result<(usize, usize), Error>;
+
+    /// Flushes remaining data from internal buffers, see `Compressor::flush`.
+    fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error>;
+}
+
+#[cfg(feature = "compressor")]
+impl<const N: usize> Backend for crate::Compressor<N> {
+    fn new(config: Config) -> Result<Self, Error> {
+        crate::Compressor::new(config)
+    }
+
+    fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        crate::Compressor::compress_chunk(self, input, output)
+    }
+
+    fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        crate::Compressor::flush(self, output, write_token)
+    }
+}
+
+impl<const N: usize> Backend for RustCompressor<N> {
+    fn new(config: Config) -> Result<Self, Error> {
+        RustCompressor::new(config)
+    }
+
+    fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        RustCompressor::compress_chunk(self, input, output)
+    }
+
+    fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        RustCompressor::flush(self, output, write_token)
+    }
+}
+
+/// The C `Compressor` in normal builds, `RustCompressor` under Miri, which cannot call into
+/// C, or without the `compressor` feature.
+#[cfg(all(feature = "compressor", not(miri)))]
+pub type DefaultBackend<const N: usize> = crate::Compressor<N>;
+
+/// The C `Compressor` in normal builds, `RustCompressor` under Miri, which cannot call into
+/// C, or without the `compressor` feature.
+#[cfg(any(not(feature = "compressor"), miri))]
+pub type DefaultBackend<const N: usize> = RustCompressor<N>;
+
+/// Decompresses a complete stream, header included, in pure Rust. Returns bytes written.
+///
+/// Meant for tests and tools rather than devices: it decodes the whole input in one call
+/// and keeps an `N` byte window on the stack. Streams with a custom dictionary need a
+/// `Decompressor`. Fails with `OutputFull` if `output` is too small.
+pub fn decompress<const N: usize>(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+    let tokens = Tokens::new(input)?;
+    if N != tokens.config().window_size() {
+        return Err(Error::InvalidConfig("Buffer size N must equal 2^window_bits"));
+    }
+    if tokens.config().use_custom_dictionary {
+        return Err(Error::InvalidConfig("Custom dictionary streams need a Decompressor"));
+    }
+    let mut window = [0u8; N];
+    BuiltinDictionary::new().fill(&mut window);
+    let (mut pos, mut written) = (0, 0);
+    let mut copy = [0u8; 16];
+    for token in tokens {
+        let bytes = match token {
+            Token::Literal(byte) => {
+                copy[0] = byte;
+                &copy[..1]
+            }
+            Token::Match { offset, len } => {
+                let (offset, len) = (offset as usize, len as usize);
+                let source = window
+                    .get(offset..offset + len)
+                    .ok_or(Error::InvalidConfig("Match outside the window"))?;
+                // Copied out first: the match may overlap the bytes it is about to overwrite
+                copy[..len].copy_from_slice(source);
+                &copy[..len]
+            }
+            Token::FlushMarker => continue,
+        };
+        output
+            .get_mut(written..written + bytes.len())
+            .ok_or(Error::OutputFull)?
+            .copy_from_slice(bytes);
+        written += bytes.len();
+        for &byte in bytes {
+            window[pos] = byte;
+            pos = (pos + 1) % N;
+        }
+    }
+    Ok(written)
+}
+
+/// Characters tamp's built-in dictionary is drawn from.
+const COMMON_CHARACTERS: [u8; 16] = *b" \x000ei>to<ans\nr/.";
+
+/// Produces tamp's built-in dictionary piece by piece, the same bytes
+/// `tamp_initialize_dictionary` writes in one go, for windows not addressable as one slice.
+struct BuiltinDictionary {
+    seed: u32,
+    bits: u32,
+    index: usize,
+}
+
+impl BuiltinDictionary {
+    fn new() -> Self {
+        Self {
+            seed: 3_758_097_560,
+            bits: 0,
+            index: 0,
+        }
+    }
+
+    /// Writes the next `buf.len()` dictionary bytes.
+    fn fill(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            if self.index.is_multiple_of(8) {
+                // xorshift32
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 17;
+                self.seed ^= self.seed << 5;
+                self.bits = self.seed;
+            }
+            *byte = COMMON_CHARACTERS[(self.bits & 0x0F) as usize];
+            self.bits >>= 4;
+            self.index += 1;
+        }
+    }
+}
+
+/// Window memory outside the address space, e.g. SPI PSRAM, accessed in ranges.
+pub trait WindowBlocks {
+    /// Error reported by the memory.
+    type Error;
+
+    /// Fills `buf` from the window at `offset`.
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Stores `data` in the window at `offset`.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Errors from an `IndirectCompressor`.
+#[derive(Debug)]
+pub enum IndirectError<E> {
+    /// Compression failed.
+    Tamp(Error),
+    /// The window memory reported an error. The stream is broken from here on.
+    Storage(E),
+}
+
+impl<E> From<Error> for IndirectError<E> {
+    fn from(error: Error) -> Self {
+        Self::Tamp(error)
+    }
+}
+
+/// Pure-Rust compressor whose `N` byte window lives behind `WindowBlocks`, e.g. in the
+/// external PSRAM of an ESP32-class part, keeping only `C + 2048` bytes plus a few dozen in
+/// internal RAM.
+///
+/// The last up to `C` bytes written are cached internally and written back one `C` byte
+/// block at a time; bytes still in the cache are never read back from the storage. Matches
+/// are searched like `Search::Fast`, against the most recent position with the same two
+/// leading bytes, so each token costs at most one `read()` of up to 16 bytes, none when the
+/// candidate is cached. On a SPI bus the per-transaction overhead dominates such a short
+/// read, so throughput is bound by the bus rather than the CPU; measure it on the target.
+/// The ratio is like `Config::fast()`. A larger `C`, a power of two up to `N`, serves more
+/// nearby candidates from the cache and makes the writes longer and rarer.
+pub struct IndirectCompressor<const N: usize, B: WindowBlocks, const C: usize = 256> {
+    blocks: B,
+    /// Window bytes from the start of the current block up to `pos`.
+    cache: [u8; C],
+    pos: usize,
+    head: [u16; HASH_BUCKETS],
+    /// Window byte before `pos`, which pairs with the next byte written.
+    last: u8,
+    lookahead: [u8; 16],
+    len: usize,
+    min_len: usize,
+    max_len: usize,
+    encoder: TokenEncoder,
+}
+
+impl<const N: usize, B: WindowBlocks, const C: usize> IndirectCompressor<N, B, C> {
+    /// Creates a compressor with the window in `blocks`, initialized like `Compressor::new`.
+    pub fn new(config: Config, blocks: B) -> Result<Self, IndirectError<B::Error>> {
+        Self::with_dictionary(config, blocks, None)
+    }
+
+    /// Creates a compressor with the window in `blocks`, initialized like
+    /// `Compressor::with_dictionary`. Writes the whole window once, `C` bytes at a time.
+    pub fn with_dictionary(
+        config: Config,
+        blocks: B,
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, IndirectError<B::Error>> {
+        const { assert!(C.is_power_of_two() && C >= 16, "Cache size C must be a power of two") };
+        if N != config.window_size() {
+            return Err(Error::InvalidConfig("Buffer size N must equal 2^window_bits").into());
+        }
+        if C > N {
+            return Err(Error::InvalidConfig("Cache size C larger than the window").into());
+        }
+        if config.use_custom_dictionary && dictionary.is_none() {
+            return Err(Error::InvalidConfig("Custom dictionary enabled but none provided").into());
+        }
+        let mut compressor = Self {
+            blocks,
+            cache: [0; C],
+            pos: 0,
+            head: [NONE; HASH_BUCKETS],
+            last: 0,
+            lookahead: [0; 16],
+            len: 0,
+            min_len: config.min_match_len(),
+            max_len: config.max_match_len(),
+            encoder: TokenEncoder::new(config.clone())?,
+        };
+        let mut builtin = BuiltinDictionary::new();
+        let dictionary = dictionary.unwrap_or_default();
+        for base in (0..N).step_by(C) {
+            let block = &mut compressor.cache;
+            if config.use_custom_dictionary {
+                block.fill(0);
+            } else {
+                builtin.fill(block);
+            }
+            if let Some(dict) = dictionary.get(base..) {
+                let len = dict.len().min(C);
+                block[..len].copy_from_slice(&dict[..len]);
+            }
+            for (i, &byte) in block.iter().enumerate() {
+                if base + i > 0 {
+                    compressor.head[pair_bucket(compressor.last, byte)] = (base + i - 1) as u16;
+                }
+                compressor.last = byte;
+            }
+            compressor
+                .blocks
+                .write(base, block)
+                .map_err(IndirectError::Storage)?;
+        }
+        Ok(compressor)
+    }
+
+    /// The window memory.
+    pub fn blocks(&self) -> &B {
+        &self.blocks
+    }
+
+    /// Reads window bytes at `offset`, taking those not yet written back from the cache.
+    fn read_window(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), B::Error> {
+        let base = self.pos - self.pos % C;
+        let end = offset + buf.len();
+        if offset >= base && end <= self.pos {
+            buf.copy_from_slice(&self.cache[offset - base..end - base]);
+            return Ok(());
+        }
+        self.blocks.read(offset, buf)?;
+        let (from, to) = (offset.max(base), end.min(self.pos));
+        if from < to {
+            buf[from - offset..to - offset].copy_from_slice(&self.cache[from - base..to - base]);
+        }
+        Ok(())
+    }
+
+    /// Finds a match for the start of the lookahead at the most recent candidate position.
+    fn find(&mut self) -> Result<Option<(usize, usize)>, B::Error> {
+        if self.len < 2 {
+            return Ok(None);
+        }
+        let candidate = self.head[pair_bucket(self.lookahead[0], self.lookahead[1])];
+        if candidate == NONE {
+            return Ok(None);
+        }
+        let offset = candidate as usize;
+        let want = self.len.min(self.max_len).min(N - offset);
+        if want < self.min_len {
+            return Ok(None);
+        }
+        let mut buf = [0u8; 16];
+        self.read_window(offset, &mut buf[..want])?;
+        let len = common_prefix(&buf[..want], &self.lookahead[..want]);
+        Ok((len >= self.min_len).then_some((offset, len)))
+    }
+
+    /// Writes one token for the start of the lookahead to `sink` and updates the window.
+    /// Returns false if `sink` is full.
+    fn step(&mut self, sink: &mut SliceSink) -> Result<bool, IndirectError<B::Error>> {
+        let (token, n) = match self.find().map_err(IndirectError::Storage)? {
+            Some((offset, len)) => (
+                Token::Match {
+                    offset: offset as u16,
+                    len: len as u8,
+                },
+                len,
+            ),
+            None => (Token::Literal(self.lookahead[0]), 1),
+        };
+        match self.encoder.push(token, sink) {
+            Err(Error::OutputFull) => return Ok(false),
+            result => result?,
+        }
+        let lookahead = self.lookahead;
+        for &byte in &lookahead[..n] {
+            self.cache[self.pos % C] = byte;
+            if self.pos > 0 {
+                self.head[pair_bucket(self.last, byte)] = (self.pos - 1) as u16;
+            }
+            self.last = byte;
+            self.pos += 1;
+            if self.pos.is_multiple_of(C) {
+                self.blocks
+                    .write(self.pos - C, &self.cache)
+                    .map_err(IndirectError::Storage)?;
+                self.pos %= N;
+            }
+        }
+        self.lookahead.copy_within(n..self.len, 0);
+        self.len -= n;
+        Ok(true)
+    }
+
+    /// Compresses input data into output buffer, see `RustCompressor::compress_chunk`.
+    pub fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), IndirectError<B::Error>> {
+        let mut sink = SliceSink::new(output);
+        let mut consumed = 0;
+        loop {
+            let n = (self.max_len - self.len).min(input.len() - consumed);
+            self.lookahead[self.len..self.len + n].copy_from_slice(&input[consumed..][..n]);
+            self.len += n;
+            consumed += n;
+            if self.len < self.max_len || !self.step(&mut sink)? {
+                break;
+            }
+        }
+        Ok((consumed, sink.len()))
+    }
+
+    /// Compresses the buffered input and pads the output to a byte boundary, see
+    /// `RustCompressor::flush`; 32 bytes of `output` always suffice.
+    pub fn flush(
+        &mut self,
+        output: &mut [u8],
+        write_token: bool,
+    ) -> Result<usize, IndirectError<B::Error>> {
+        let literal_bits = 1 + self.encoder.config().literal_bits as usize;
+        let worst = self.encoder.pending_bits() as usize + self.len * literal_bits + 16;
+        if output.len() < worst.div_ceil(8) {
+            return Err(Error::OutputFull.into());
+        }
+        let mut sink = SliceSink::new(output);
+        while self.len > 0 {
+            if !self.step(&mut sink)? {
+                return Err(Error::OutputFull.into());
+            }
+        }
+        if write_token && self.encoder.pending_bits() > 0 {
+            self.encoder.push(Token::FlushMarker, &mut sink)?;
+        }
+        self.encoder.finish(&mut sink)?;
+        Ok(sink.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    /// Only finds matches at offsets divisible by 4, to check custom finders are honoured.
+    struct Aligned;
+
+    impl MatchFinder for Aligned {
+        fn find(
+            &mut self,
+            window: &[u8],
+            lookahead: &[u8],
+            min_len: usize,
+        ) -> Option<(usize, usize)> {
+            (0..window.len())
+                .step_by(4)
+                .map(|offset| (offset, common_prefix(&window[offset..], lookahead)))
+                .filter(|&(_, len)| len >= min_len)
+                .max_by_key(|&(_, len)| len)
+        }
+    }
+
+    fn compress<F: MatchFinder>(mut compressor: RustCompressor<256, F>, data: &[u8]) -> Vec<u8> {
+        let mut output = std::vec![0u8; data.len() * 2 + 64];
+        let mut written = 0;
+        for chunk in data.chunks(7) {
+            let (consumed, n) = compressor.compress_chunk(chunk, &mut output[written..]).unwrap();
+            assert_eq!(consumed, chunk.len());
+            written += n;
+        }
+        written += compressor.flush(&mut output[written..], false).unwrap();
+        output.truncate(written);
+        output
+    }
+
+    #[test]
+    fn test_rust_compressor_round_trip() {
+        let data: Vec<u8> = (0..3000usize)
+            .map(|i| b"node=7;rssi=-71;snr=9;"[i % 22] ^ (i / 900) as u8)
+            .collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let brute = compress(RustCompressor::<256>::new(config.clone()).unwrap(), &data);
+        let finder = RustCompressor::with_finder(config.clone(), Aligned).unwrap();
+        let aligned = compress(finder, &data);
+        let chained = RustCompressor::<256>::new(config.search(Search::HashChain)).unwrap();
+        assert_eq!(chained.finder().search(), Search::HashChain);
+        let chained = compress(chained, &data);
+        let fast = Config::fast().window_bits(8).unwrap();
+        let fast = compress(RustCompressor::<256>::new(fast).unwrap(), &data);
+        assert!(brute.len() < data.len() / 4);
+        assert!(brute.len() <= aligned.len());
+        assert!(chained.len() < data.len() / 4);
+        assert!(fast.len() < data.len() / 3);
+
+        for stream in [brute, aligned, chained, fast] {
+            let mut output = std::vec![0u8; 3000];
+            let n = decompress::<256>(&stream, &mut output).unwrap();
+            assert_eq!(&output[..n], &data[..]);
+        }
+    }
+
+    /// Compresses and decodes through `B`, with the C library under Miri only if `B` uses it.
+    fn backend_round_trip<B: Backend>() {
+        let data: Vec<u8> = (0..1000usize).map(|i| b"t=21.5;rh=40;"[i % 13]).collect();
+        let mut compressor = B::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = std::vec![0u8; data.len()];
+        let mut written = 0;
+        for chunk in data.chunks(100) {
+            let (consumed, n) = compressor.compress_chunk(chunk, &mut stream[written..]).unwrap();
+            assert_eq!(consumed, chunk.len());
+            written += n;
+        }
+        written += compressor.flush(&mut stream[written..], false).unwrap();
+        assert!(written < data.len() / 4);
+
+        let mut output = std::vec![0u8; data.len()];
+        let n = decompress::<256>(&stream[..written], &mut output).unwrap();
+        assert_eq!(&output[..n], &data[..]);
+        assert!(matches!(
+            decompress::<256>(&stream[..written], &mut output[..10]),
+            Err(Error::OutputFull)
+        ));
+    }
+
+    #[test]
+    fn test_default_backend() {
+        backend_round_trip::<DefaultBackend<256>>();
+        backend_round_trip::<RustCompressor<256>>();
+    }
+
+    /// A window in RAM standing in for PSRAM, counting the transactions.
+    struct Psram {
+        memory: Vec<u8>,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl WindowBlocks for Psram {
+        type Error = ();
+
+        fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), ()> {
+            self.reads += 1;
+            buf.copy_from_slice(&self.memory[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), ()> {
+            self.writes += 1;
+            self.memory[offset..offset + data.len()].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_builtin_dictionary_pieces() {
+        let mut expected = [0u8; 1024];
+        unsafe { tamp_sys::tamp_initialize_dictionary(expected.as_mut_ptr(), expected.len()) };
+        let mut pieces = [0u8; 1024];
+        let mut builtin = BuiltinDictionary::new();
+        for piece in pieces.chunks_mut(100) {
+            builtin.fill(piece);
+        }
+        assert_eq!(pieces, expected);
+    }
+
+    #[test]
+    fn test_indirect_compressor() {
+        let data: Vec<u8> = (0..5000usize)
+            .map(|i| b"ch=3;adc=1021;adc=1019;"[i % 23] ^ (i / 1200) as u8)
+            .collect();
+        let config = Config::new().window_bits(10).unwrap();
+        let psram = Psram {
+            memory: std::vec![0; 1024],
+            reads: 0,
+            writes: 0,
+        };
+        let mut compressor = IndirectCompressor::<1024, _, 64>::new(config, psram).unwrap();
+        let mut stream = std::vec![0u8; data.len()];
+        let mut written = 0;
+        for chunk in data.chunks(50) {
+            let (consumed, n) = compressor.compress_chunk(chunk, &mut stream[written..]).unwrap();
+            assert_eq!(consumed, chunk.len());
+            written += n;
+        }
+        written += compressor.flush(&mut stream[written..], false).unwrap();
+        assert!(written < data.len() / 2);
+        assert!(compressor.blocks().reads < data.len());
+        assert_eq!(compressor.blocks().writes, 1024 / 64 + data.len() / 64);
+
+        let mut output = std::vec![0u8; data.len()];
+        let n = decompress::<1024>(&stream[..written], &mut output).unwrap();
+        assert_eq!(&output[..n], &data[..]);
+    }
+}
@@ -0,0 +1,507 @@
+//! Telemetry batches: several typed channels compressed as one self-describing unit.
+//!
+//! A batch stores its channels one after another (struct of arrays), each passed through
+//! its own filter, so timestamps get a delta pass while flags get bit-packed. The batch
+//! starts with a mini-header describing the channels, followed by one tamp stream:
+//!
+//! | Size | Field                                                       |
+//! |------|-------------------------------------------------------------|
+//! | 1    | Format version, currently 1                                 |
+//! | 1    | Channel count, 1 to `MAX_CHANNELS`                          |
+//! | 4    | Samples per channel, u32 LE                                 |
+//! | 2    | Per channel: `Kind` code, then filter code                  |
+//!
+//! Values are stored little-endian. Filter codes are 0 for `Raw`, 1 for `Delta`, 2 for
+//! `XorPrev` and `0x80 | bits` for `BitPack`, each with the value width as stride.
+//!
+//! ```ignore
+//! let len = compress_batch(&mut compressor, &[
+//!     Channel::new(Column::U32(&timestamps), ChannelFilter::Delta),
+//!     Channel::new(Column::I16(&values), ChannelFilter::Delta),
+//!     Channel::new(Column::U8(&flags), ChannelFilter::BitPack(2)),
+//! ], &mut upload)?;
+//! // receiver
+//! let batch = decompress_batch::<1024>(&upload[..len], &mut scratch)?;
+//! let timestamps = batch.channel::<u32>(0)?;
+//! ```
+
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+use crate::filter::{BitPack, Delta, Filter, XorPrev};
+#[cfg(feature = "compressor")]
+use crate::sink::SliceSink;
+
+/// Format version written by `compress_batch()`.
+pub const VERSION: u8 = 1;
+/// Most channels in one batch.
+pub const MAX_CHANNELS: usize = 16;
+/// Bytes of mini-header before the per-channel descriptors.
+const HEADER: usize = 6;
+/// Bytes staged per filter pass.
+const STAGE: usize = 64;
+
+/// Value type of a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Kind {
+    /// `u8` values.
+    U8 = 0,
+    /// `u16` values.
+    U16 = 1,
+    /// `i16` values.
+    I16 = 2,
+    /// `u32` values.
+    U32 = 3,
+    /// `i32` values.
+    I32 = 4,
+}
+
+impl Kind {
+    /// Bytes per value.
+    pub const fn width(self) -> usize {
+        match self {
+            Kind::U8 => 1,
+            Kind::U16 | Kind::I16 => 2,
+            Kind::U32 | Kind::I32 => 4,
+        }
+    }
+
+    #[cfg(feature = "decompressor")]
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => Kind::U8,
+            1 => Kind::U16,
+            2 => Kind::I16,
+            3 => Kind::U32,
+            4 => Kind::I32,
+            _ => return None,
+        })
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for i16 {}
+    impl Sealed for u32 {}
+    impl Sealed for i32 {}
+}
+
+/// A channel value type, implemented for `u8`, `u16`, `i16`, `u32` and `i32`.
+pub trait Value: sealed::Sealed + Copy {
+    /// Kind recorded in the batch header for this type.
+    const KIND: Kind;
+    /// Deserializes a little-endian value of `KIND.width()` bytes.
+    fn from_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_value {
+    ($($ty:ty => $kind:ident),*) => {$(
+        impl Value for $ty {
+            const KIND: Kind = Kind::$kind;
+            fn from_le(bytes: &[u8]) -> Self {
+                <$ty>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+impl_value!(u8 => U8, u16 => U16, i16 => I16, u32 => U32, i32 => I32);
+
+/// The values of one channel.
+#[derive(Clone, Copy, Debug)]
+pub enum Column<'a> {
+    /// `u8` values, e.g. flags.
+    U8(&'a [u8]),
+    /// `u16` values, e.g. raw ADC readings.
+    U16(&'a [u16]),
+    /// `i16` values, e.g. IMU axes.
+    I16(&'a [i16]),
+    /// `u32` values, e.g. timestamps.
+    U32(&'a [u32]),
+    /// `i32` values.
+    I32(&'a [i32]),
+}
+
+#[cfg(feature = "compressor")]
+impl Column<'_> {
+    fn kind(&self) -> Kind {
+        match self {
+            Column::U8(_) => Kind::U8,
+            Column::U16(_) => Kind::U16,
+            Column::I16(_) => Kind::I16,
+            Column::U32(_) => Kind::U32,
+            Column::I32(_) => Kind::I32,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::U8(v) => v.len(),
+            Column::U16(v) => v.len(),
+            Column::I16(v) => v.len(),
+            Column::U32(v) => v.len(),
+            Column::I32(v) => v.len(),
+        }
+    }
+
+    /// Writes value `i` little-endian to the start of `out`.
+    fn write_le(&self, i: usize, out: &mut [u8]) {
+        match self {
+            Column::U8(v) => out[0] = v[i],
+            Column::U16(v) => out[..2].copy_from_slice(&v[i].to_le_bytes()),
+            Column::I16(v) => out[..2].copy_from_slice(&v[i].to_le_bytes()),
+            Column::U32(v) => out[..4].copy_from_slice(&v[i].to_le_bytes()),
+            Column::I32(v) => out[..4].copy_from_slice(&v[i].to_le_bytes()),
+        }
+    }
+}
+
+/// Filter applied to a channel before compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelFilter {
+    /// Values are stored as they are.
+    Raw,
+    /// `filter::Delta` with the value width as stride, for counters and slow signals.
+    Delta,
+    /// `filter::XorPrev` with the value width as stride, for flag words and floats.
+    Xor,
+    /// `filter::BitPack` keeping the low `bits` bits, for `u8` channels only.
+    BitPack(u8),
+}
+
+impl ChannelFilter {
+    #[cfg(feature = "compressor")]
+    fn code(self) -> u8 {
+        match self {
+            ChannelFilter::Raw => 0,
+            ChannelFilter::Delta => 1,
+            ChannelFilter::Xor => 2,
+            ChannelFilter::BitPack(bits) => 0x80 | bits,
+        }
+    }
+
+    #[cfg(feature = "decompressor")]
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => ChannelFilter::Raw,
+            1 => ChannelFilter::Delta,
+            2 => ChannelFilter::Xor,
+            0x81..=0x88 => ChannelFilter::BitPack(code & 0x0f),
+            _ => return None,
+        })
+    }
+
+    /// Checks the filter suits `kind`.
+    fn check(self, kind: Kind) -> Result<(), Error> {
+        if let ChannelFilter::BitPack(bits) = self
+            && (kind != Kind::U8 || !(1..=8).contains(&bits))
+        {
+            return Err(Error::InvalidConfig("BitPack needs a u8 channel and 1-8 bits"));
+        }
+        Ok(())
+    }
+
+    /// Filtered length of `samples` values of `kind`.
+    #[cfg(feature = "decompressor")]
+    fn encoded_len(self, kind: Kind, samples: usize) -> usize {
+        match self {
+            ChannelFilter::BitPack(bits) => (samples * bits as usize).div_ceil(8),
+            _ => samples * kind.width(),
+        }
+    }
+
+    fn create(self, kind: Kind) -> Result<AnyFilter, Error> {
+        Ok(match self {
+            ChannelFilter::Raw => AnyFilter::Raw,
+            ChannelFilter::Delta => AnyFilter::Delta(Delta::new(kind.width())?),
+            ChannelFilter::Xor => AnyFilter::Xor(XorPrev::new(kind.width())?),
+            ChannelFilter::BitPack(bits) => AnyFilter::BitPack(BitPack::new(bits)?),
+        })
+    }
+}
+
+/// A filter picked at runtime from a `ChannelFilter`.
+enum AnyFilter {
+    Raw,
+    Delta(Delta),
+    Xor(XorPrev),
+    BitPack(BitPack),
+}
+
+impl Filter for AnyFilter {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        match self {
+            AnyFilter::Raw => {
+                let n = input.len().min(output.len());
+                output[..n].copy_from_slice(&input[..n]);
+                (n, n)
+            }
+            AnyFilter::Delta(f) => f.encode(input, output),
+            AnyFilter::Xor(f) => f.encode(input, output),
+            AnyFilter::BitPack(f) => f.encode(input, output),
+        }
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        match self {
+            AnyFilter::Raw => self.encode(input, output),
+            AnyFilter::Delta(f) => f.decode(input, output),
+            AnyFilter::Xor(f) => f.decode(input, output),
+            AnyFilter::BitPack(f) => f.decode(input, output),
+        }
+    }
+
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        match self {
+            AnyFilter::BitPack(f) => f.finish(output),
+            _ => 0,
+        }
+    }
+}
+
+/// One channel of a batch: its values and the filter to apply.
+#[cfg(feature = "compressor")]
+#[derive(Clone, Copy, Debug)]
+pub struct Channel<'a> {
+    column: Column<'a>,
+    filter: ChannelFilter,
+}
+
+#[cfg(feature = "compressor")]
+impl<'a> Channel<'a> {
+    /// Creates a channel.
+    pub fn new(column: Column<'a>, filter: ChannelFilter) -> Self {
+        Self { column, filter }
+    }
+}
+
+/// Compresses `channels` as one batch into `output`, mini-header included, and performs the
+/// final flush. `compressor` must be fresh, so that the batch carries the stream header.
+/// All channels must have the same length. Returns the batch length.
+#[cfg(feature = "compressor")]
+pub fn compress_batch<const N: usize>(
+    compressor: &mut Compressor<N>,
+    channels: &[Channel<'_>],
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    let samples = channels.first().map_or(0, |c| c.column.len());
+    if channels.is_empty() || channels.len() > MAX_CHANNELS {
+        return Err(Error::InvalidConfig("Batch needs 1 to 16 channels"));
+    }
+    if channels.iter().any(|c| c.column.len() != samples) || samples > u32::MAX as usize {
+        return Err(Error::InvalidConfig("Channels differ in length"));
+    }
+    let header_len = HEADER + 2 * channels.len();
+    let header = output.get_mut(..header_len).ok_or(Error::BufferTooSmall)?;
+    header[0] = VERSION;
+    header[1] = channels.len() as u8;
+    header[2..6].copy_from_slice(&(samples as u32).to_le_bytes());
+    for (descriptor, channel) in header[HEADER..].chunks_exact_mut(2).zip(channels) {
+        channel.filter.check(channel.column.kind())?;
+        descriptor[0] = channel.column.kind() as u8;
+        descriptor[1] = channel.filter.code();
+    }
+
+    let mut sink = SliceSink::new(&mut output[header_len..]);
+    let mut raw = [0u8; STAGE];
+    let mut filtered = [0u8; STAGE];
+    for channel in channels {
+        let kind = channel.column.kind();
+        let mut filter = channel.filter.create(kind)?;
+        let mut i = 0;
+        while i < samples {
+            let mut len = 0;
+            while i < samples && len + kind.width() <= STAGE {
+                channel.column.write_le(i, &mut raw[len..]);
+                len += kind.width();
+                i += 1;
+            }
+            let (_, n) = filter.encode(&raw[..len], &mut filtered);
+            compress_all(compressor, &filtered[..n], &mut sink)?;
+        }
+        let n = filter.finish(&mut filtered);
+        compress_all(compressor, &filtered[..n], &mut sink)?;
+    }
+    compressor.flush_into(&mut sink, false)?;
+    Ok(header_len + sink.len())
+}
+
+/// Compresses all of `bytes` into `sink`, failing with `OutputFull` if it runs out of room.
+#[cfg(feature = "compressor")]
+fn compress_all<const N: usize>(
+    compressor: &mut Compressor<N>,
+    bytes: &[u8],
+    sink: &mut SliceSink<'_>,
+) -> Result<(), Error> {
+    if compressor.compress_into(bytes, sink)? < bytes.len() {
+        return Err(Error::OutputFull);
+    }
+    Ok(())
+}
+
+/// A decompressed batch, borrowing the values from the output buffer.
+#[derive(Debug)]
+pub struct Batch<'o> {
+    samples: usize,
+    kinds: [Kind; MAX_CHANNELS],
+    channels: usize,
+    data: &'o [u8],
+}
+
+impl<'o> Batch<'o> {
+    /// Values per channel.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Number of channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Value type of `channel`.
+    pub fn kind(&self, channel: usize) -> Option<Kind> {
+        self.kinds[..self.channels].get(channel).copied()
+    }
+
+    /// Values of `channel`. Fails with `InvalidConfig` if it holds another type than `T`.
+    pub fn channel<T: Value + 'o>(
+        &self,
+        channel: usize,
+    ) -> Result<impl Iterator<Item = T> + 'o, Error> {
+        if self.kind(channel) != Some(T::KIND) {
+            return Err(Error::InvalidConfig("Channel missing or of another type"));
+        }
+        let offset: usize = self.kinds[..channel].iter().map(|k| k.width()).sum();
+        let data = &self.data[offset * self.samples..][..T::KIND.width() * self.samples];
+        Ok(data.chunks_exact(T::KIND.width()).map(T::from_le))
+    }
+}
+
+/// Decompresses a batch from `compress_batch()`, storing the values in `output`.
+/// `output` needs room for every value of every channel.
+#[cfg(feature = "decompressor")]
+pub fn decompress_batch<'o, const N: usize>(
+    input: &[u8],
+    output: &'o mut [u8],
+) -> Result<Batch<'o>, Error> {
+    let header = input.get(..HEADER).ok_or(Error::InputExhausted)?;
+    let count = header[1] as usize;
+    if header[0] != VERSION || !(1..=MAX_CHANNELS).contains(&count) {
+        return Err(Error::Serialization);
+    }
+    let samples = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    let descriptors = input
+        .get(HEADER..HEADER + 2 * count)
+        .ok_or(Error::InputExhausted)?;
+    let mut channels = [(Kind::U8, ChannelFilter::Raw); MAX_CHANNELS];
+    for (channel, descriptor) in channels.iter_mut().zip(descriptors.chunks_exact(2)) {
+        let kind = Kind::from_code(descriptor[0]).ok_or(Error::Serialization)?;
+        let filter = ChannelFilter::from_code(descriptor[1]).ok_or(Error::Serialization)?;
+        filter.check(kind).map_err(|_| Error::Serialization)?;
+        *channel = (kind, filter);
+    }
+    let channels = &channels[..count];
+    // `samples` is untrusted, so a size that overflows `usize` cannot fit any buffer either
+    let total = channels
+        .iter()
+        .try_fold(0usize, |total, (kind, _)| {
+            kind.width().checked_mul(samples)?.checked_add(total)
+        })
+        .ok_or(Error::BufferTooSmall)?;
+    if output.len() < total {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let (mut decompressor, stream_header) =
+        Decompressor::<N>::from_header(&input[HEADER + 2 * count..])?;
+    let mut stream = &input[HEADER + 2 * count + stream_header..];
+    let mut offset = 0;
+    for &(kind, filter) in channels {
+        let region = &mut output[offset..offset + kind.width() * samples];
+        let mut remaining = filter.encoded_len(kind, samples);
+        let mut decoder = filter.create(kind)?;
+        let mut written = 0;
+        let mut staged = [0u8; STAGE];
+        while remaining > 0 {
+            let n = remaining.min(STAGE);
+            decompressor.decompress_exact(&mut stream, &mut staged[..n])?;
+            remaining -= n;
+            let (_, w) = decoder.decode(&staged[..n], &mut region[written..]);
+            written += w;
+        }
+        if written != region.len() {
+            return Err(Error::LengthMismatch);
+        }
+        offset += region.len();
+    }
+    let mut kinds = [Kind::U8; MAX_CHANNELS];
+    for (kind, &(channel, _)) in kinds.iter_mut().zip(channels) {
+        *kind = channel;
+    }
+    Ok(Batch {
+        samples,
+        kinds,
+        channels: count,
+        data: &output[..total],
+    })
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Config;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_batch_huge_sample_count() {
+        // Sixteen u32 channels of u32::MAX samples overflow usize on 32-bit targets
+        let mut input = Vec::from([VERSION, MAX_CHANNELS as u8, 0xff, 0xff, 0xff, 0xff]);
+        for _ in 0..MAX_CHANNELS {
+            input.extend_from_slice(&[Kind::U32 as u8, ChannelFilter::Raw.code()]);
+        }
+        let mut output = [0u8; 64];
+        assert!(matches!(
+            decompress_batch::<1024>(&input, &mut output),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_batch_round_trip() {
+        let timestamps: Vec<u32> = (0..200).map(|i| 1_700_000_000 + i * 10).collect();
+        let values: Vec<i16> = (0..200).map(|i| (i as i16 % 40 - 20) * 3).collect();
+        let flags: Vec<u8> = (0..200).map(|i| (i / 50) as u8).collect();
+        let mut compressor = Compressor::<1024>::new(Config::new()).unwrap();
+        let mut upload = [0u8; 2048];
+        let channels = [
+            Channel::new(Column::U32(&timestamps), ChannelFilter::Delta),
+            Channel::new(Column::I16(&values), ChannelFilter::Delta),
+            Channel::new(Column::U8(&flags), ChannelFilter::BitPack(2)),
+        ];
+        let len = compress_batch(&mut compressor, &channels, &mut upload).unwrap();
+        assert!(len < 200 * 7 / 4);
+
+        let mut scratch = [0u8; 200 * 7];
+        let batch = decompress_batch::<1024>(&upload[..len], &mut scratch).unwrap();
+        assert_eq!((batch.samples(), batch.channels()), (200, 3));
+        assert_eq!(batch.channel::<u32>(0).unwrap().collect::<Vec<_>>(), timestamps);
+        assert_eq!(batch.channel::<i16>(1).unwrap().collect::<Vec<_>>(), values);
+        assert_eq!(batch.channel::<u8>(2).unwrap().collect::<Vec<_>>(), flags);
+        assert!(batch.channel::<u16>(1).is_err());
+
+        let mut compressor = Compressor::<1024>::new(Config::new()).unwrap();
+        let mismatched = [
+            Channel::new(Column::U32(&timestamps), ChannelFilter::Raw),
+            Channel::new(Column::U8(&flags[..10]), ChannelFilter::Raw),
+        ];
+        assert!(compress_batch(&mut compressor, &mismatched, &mut upload).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Streaming compressed data over BLE GATT notifications.
+//!
+//! `GattSender` packs the stream into notification-sized frames using the `frames` format
+//! and keeps the last `DEPTH` frames until the host acknowledges them. The host feeds
+//! notifications to a `frames::FrameReassembler` and writes its `next_sequence()` back to a
+//! control characteristic, both as a periodic ack and, after a reconnect, as the resume point.
+//! Frames the host never saw are then sent again, so the compressor does not need to be
+//! rewound.
+//!
+//! Resuming only replays retained frames; it does not use compressor checkpoints. `write`
+//! accepts no more data once `DEPTH` frames await an ack, so a resume point sent after a
+//! reconnect is always still held. The frames live in RAM, though: after a device reset the
+//! stream cannot be resumed and has to start over, unless it is produced through
+//! `session::Session`, whose checkpoints survive power loss.
+//!
+//! ```ignore
+//! let mut sender = GattSender::<DEFAULT_PAYLOAD, 8>::new();
+//! let consumed = sender.write(&compressed, |frame| gatt.notify(frame));
+//! // later, on a control characteristic write:
+//! sender.resume(request[0], |frame| gatt.notify(frame))?;
+//! ```
+
+use crate::Error;
+use crate::frames::{self, FRAME_HEADER, LAST};
+
+/// Notification payload with the default ATT MTU of 23.
+pub const DEFAULT_PAYLOAD: usize = 20;
+/// Notification payload with an ATT MTU of 185, as negotiated by most phones.
+pub const MTU_185_PAYLOAD: usize = 182;
+
+const SEQUENCE: u32 = frames::SEQUENCE as u32;
+
+/// Device side: frames of at most `MTU` bytes, with up to `DEPTH` unacknowledged frames kept
+/// for resending.
+pub struct GattSender<const MTU: usize, const DEPTH: usize> {
+    frames: [[u8; MTU]; DEPTH],
+    lens: [usize; DEPTH],
+    /// Counter of the oldest frame the host has not acknowledged.
+    acked: u32,
+    /// Counter of the frame being filled.
+    next: u32,
+    fill: usize,
+    finished: bool,
+}
+
+impl<const MTU: usize, const DEPTH: usize> GattSender<MTU, DEPTH> {
+    /// Creates an empty sender.
+    pub fn new() -> Self {
+        const {
+            assert!(MTU > FRAME_HEADER, "MTU must leave room for payload");
+            assert!(
+                DEPTH > 0 && DEPTH <= SEQUENCE as usize,
+                "DEPTH must be 1..=63"
+            );
+        };
+        Self {
+            frames: [[0; MTU]; DEPTH],
+            lens: [0; DEPTH],
+            acked: 0,
+            next: 0,
+            fill: FRAME_HEADER,
+            finished: false,
+        }
+    }
+
+    /// Appends `bytes`, passing each completed frame to `notify`.
+    ///
+    /// Returns the number of bytes accepted, which is short when `DEPTH` frames are awaiting
+    /// an ack. Call again after `ack()`.
+    pub fn write(&mut self, mut bytes: &[u8], mut notify: impl FnMut(&[u8])) -> usize {
+        let total = bytes.len();
+        while !bytes.is_empty() && !self.finished && self.has_slot() {
+            let frame = &mut self.frames[self.next as usize % DEPTH];
+            let n = bytes.len().min(MTU - self.fill);
+            frame[self.fill..self.fill + n].copy_from_slice(&bytes[..n]);
+            self.fill += n;
+            bytes = &bytes[n..];
+            if self.fill == MTU {
+                self.send(0, &mut notify);
+            }
+        }
+        total - bytes.len()
+    }
+
+    /// Sends the pending bytes as a short frame.
+    pub fn flush(&mut self, mut notify: impl FnMut(&[u8])) {
+        if self.fill > FRAME_HEADER {
+            self.send(0, &mut notify);
+        }
+    }
+
+    /// Sends the final frame.
+    /// Returns `OutputFull` if `DEPTH` frames are awaiting an ack.
+    pub fn finish(&mut self, mut notify: impl FnMut(&[u8])) -> Result<(), Error> {
+        if !self.finished {
+            if !self.has_slot() {
+                return Err(Error::OutputFull);
+            }
+            self.send(LAST, &mut notify);
+            self.finished = true;
+        }
+        Ok(())
+    }
+
+    /// Releases all frames before `next_sequence`, the host's next expected sequence number.
+    /// Returns `FrameSequence` if it does not match a frame sent and still held.
+    pub fn ack(&mut self, next_sequence: u8) -> Result<(), Error> {
+        self.acked = (self.acked..=self.next)
+            .find(|&counter| counter & SEQUENCE == next_sequence as u32 & SEQUENCE)
+            .ok_or(Error::FrameSequence)?;
+        Ok(())
+    }
+
+    /// Acknowledges up to `next_sequence` and sends every later frame again, e.g. after a
+    /// reconnect. Returns `FrameSequence` if that frame is no longer held.
+    pub fn resume(
+        &mut self,
+        next_sequence: u8,
+        mut notify: impl FnMut(&[u8]),
+    ) -> Result<(), Error> {
+        self.ack(next_sequence)?;
+        for counter in self.acked..self.next {
+            let slot = counter as usize % DEPTH;
+            notify(&self.frames[slot][..self.lens[slot]]);
+        }
+        Ok(())
+    }
+
+    /// Returns true once the final frame was sent and acknowledged.
+    pub fn is_complete(&self) -> bool {
+        self.finished && self.acked == self.next
+    }
+
+    fn has_slot(&self) -> bool {
+        self.next - self.acked < DEPTH as u32
+    }
+
+    fn send(&mut self, flags: u8, notify: &mut impl FnMut(&[u8])) {
+        let slot = self.next as usize % DEPTH;
+        self.frames[slot][0] = (self.next & SEQUENCE) as u8 | flags;
+        self.lens[slot] = self.fill;
+        notify(&self.frames[slot][..self.fill]);
+        self.next += 1;
+        self.fill = FRAME_HEADER;
+    }
+}
+
+impl<const MTU: usize, const DEPTH: usize> Default for GattSender<MTU, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::frames::FrameReassembler;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_gatt_resume() {
+        let data: Vec<u8> = (0..600u32).map(|i| (i * 13) as u8).collect();
+        let mut sender = GattSender::<DEFAULT_PAYLOAD, 4>::new();
+        let mut received = Vec::new();
+        let mut host = FrameReassembler::new(|p: &[u8]| received.extend_from_slice(p));
+        let mut link_up = true;
+        let mut remaining = &data[..];
+        let mut sent = 0;
+        while !sender.is_complete() {
+            let mut air: Vec<Vec<u8>> = Vec::new();
+            let consumed = sender.write(remaining, |frame| air.push(frame.into()));
+            remaining = &remaining[consumed..];
+            if remaining.is_empty() && sender.has_slot() {
+                sender.finish(|frame| air.push(frame.into())).unwrap();
+            }
+            sent += air.len();
+            // The link drops once, losing every frame in flight
+            if link_up && sent > 10 {
+                link_up = false;
+                air.clear();
+                sender
+                    .resume(host.next_sequence(), |frame| air.push(frame.into()))
+                    .unwrap();
+            }
+            for frame in &air {
+                assert!(frame.len() <= DEFAULT_PAYLOAD);
+                host.push(frame).unwrap();
+            }
+            sender.ack(host.next_sequence()).unwrap();
+        }
+        assert!(host.is_done());
+        assert_eq!(received, data);
+        assert!(!link_up);
+        assert!(sender.ack(100).is_err());
+    }
+}
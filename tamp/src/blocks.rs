@@ -0,0 +1,160 @@
+//! Random access into block-indexed archives.
+//!
+//! Archives made of independently compressed blocks start with a table, all integers
+//! little-endian:
+//!
+//! ```text
+//! [u32 block_size][u32 block_count][u64 total_len][u64 end; block_count][stream; block_count]
+//! ```
+//!
+//! `end` is the offset just past each block's stream, counted from the end of the table.
+//! Every stream is a complete tamp stream with its own header, so any block can be decoded
+//! without touching the others. `parallel::compress_blocks` writes this format.
+
+use core::ops::Range;
+use crate::{DynDecompressor, Error};
+
+/// Bytes in the fixed part of the block table, before the end offsets.
+pub(crate) const TABLE_HEADER: usize = 16;
+
+/// Parsed view of a block-indexed archive.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockTable<'a> {
+    block_size: u32,
+    total_len: u64,
+    ends: &'a [u8],
+    streams: &'a [u8],
+}
+
+impl<'a> BlockTable<'a> {
+    /// Parses the block table at the start of `archive`.
+    pub fn parse(archive: &'a [u8]) -> Result<Self, Error> {
+        let header = archive
+            .get(..TABLE_HEADER)
+            .ok_or(Error::InvalidConfig("Truncated block table"))?;
+        let block_size = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let count = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        let total_len = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        if block_size == 0 || total_len.div_ceil(block_size as u64) != count as u64 {
+            return Err(Error::InvalidConfig("Inconsistent block table"));
+        }
+        let table_end = count
+            .checked_mul(8)
+            .and_then(|n| n.checked_add(TABLE_HEADER))
+            .filter(|&n| n <= archive.len())
+            .ok_or(Error::InvalidConfig("Truncated block table"))?;
+        Ok(Self {
+            block_size,
+            total_len,
+            ends: &archive[TABLE_HEADER..table_end],
+            streams: &archive[table_end..],
+        })
+    }
+
+    /// Uncompressed size of every block but possibly the last.
+    pub fn block_size(&self) -> usize {
+        self.block_size as usize
+    }
+
+    /// Number of blocks.
+    pub fn len(&self) -> usize {
+        self.ends.len() / 8
+    }
+
+    /// Returns true if the archive holds no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Total uncompressed length of the archive.
+    pub fn total_len(&self) -> u64 {
+        self.total_len
+    }
+
+    /// Uncompressed byte range covered by block `index`.
+    pub fn range(&self, index: usize) -> Range<u64> {
+        let start = index as u64 * self.block_size as u64;
+        start..(start + self.block_size as u64).min(self.total_len)
+    }
+
+    /// Index of the block holding uncompressed offset `offset`.
+    pub fn block_at(&self, offset: u64) -> usize {
+        (offset / self.block_size as u64) as usize
+    }
+
+    /// Compressed stream of block `index`, or `None` if out of range or the table is corrupt.
+    pub fn block(&self, index: usize) -> Option<&'a [u8]> {
+        let end = |i: usize| -> Option<usize> {
+            let bytes = self.ends.get(8 * i..8 * i + 8)?;
+            usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap())).ok()
+        };
+        let start = if index == 0 { 0 } else { end(index - 1)? };
+        self.streams.get(start..end(index)?)
+    }
+
+    /// Decompresses block `index` into `output` using `window` as the decoder window.
+    ///
+    /// `window` must match the window size in the block's header and `output` must hold the
+    /// block's uncompressed length. Returns that length, or `LengthMismatch` if the block
+    /// decodes to fewer bytes.
+    pub fn decompress_block(
+        &self,
+        index: usize,
+        window: &mut [u8],
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let stream = self
+            .block(index)
+            .ok_or(Error::InvalidConfig("Block index out of range"))?;
+        let range = self.range(index);
+        let len = (range.end - range.start) as usize;
+        let output = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        let (mut decompressor, header) = DynDecompressor::from_header(stream, window)?;
+        let (_, written) = decompressor.decompress_chunk(&stream[header..], output)?;
+        if written != len {
+            return Err(Error::LengthMismatch);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(all(test, feature = "compressor"))]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Config};
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_block_table_random_access() {
+        // Two blocks of 300 and 100 bytes, laid out by hand
+        let input: [u8; 400] = core::array::from_fn(|i| (i % 13) as u8);
+        let mut archive = [0u8; 2048];
+        archive[..4].copy_from_slice(&300u32.to_le_bytes());
+        archive[4..8].copy_from_slice(&2u32.to_le_bytes());
+        archive[8..16].copy_from_slice(&400u64.to_le_bytes());
+        let mut end = 0;
+        for (i, block) in input.chunks(300).enumerate() {
+            let mut compressor =
+                Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+            let streams = &mut archive[32 + end..];
+            let (_, mut n) = compressor.compress_chunk(block, streams).unwrap();
+            n += compressor.flush(&mut streams[n..], false).unwrap();
+            end += n;
+            archive[16 + 8 * i..24 + 8 * i].copy_from_slice(&(end as u64).to_le_bytes());
+        }
+
+        let table = BlockTable::parse(&archive[..32 + end]).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.range(1), 300..400);
+        assert_eq!(table.block_at(350), 1);
+        let mut window = [0u8; 256];
+        let mut output = [0u8; 300];
+        let n = table.decompress_block(1, &mut window, &mut output).unwrap();
+        assert_eq!(&output[..n], &input[300..]);
+        let n = table.decompress_block(0, &mut window, &mut output).unwrap();
+        assert_eq!(&output[..n], &input[..300]);
+        assert!(table.block(2).is_none());
+
+        assert!(BlockTable::parse(&archive[..20]).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! Compile-time window selection from a RAM budget.
+
+/// Largest supported window size (256 B to 32 KB) such that the window plus `overhead`
+/// bytes fits in `budget`. Returns 0 if even a 256-byte window does not fit.
+pub const fn largest_window(budget: usize, overhead: usize) -> usize {
+    if overhead > budget {
+        return 0;
+    }
+    let mut bits = 15;
+    while bits >= 8 {
+        if 1 << bits <= budget - overhead {
+            return 1 << bits;
+        }
+        bits -= 1;
+    }
+    0
+}
+
+/// Resolves to the largest window size whose `Compressor` fits in a RAM budget in bytes.
+///
+/// Prefix the budget with `Decompressor,` to size a decompressor instead. Fails to compile
+/// if no supported window fits. Pair with `Config::new().window_bits(N.trailing_zeros() as u8)`.
+///
+/// ```ignore
+/// type Log = tamp::Compressor<{ tamp::largest_window_for!(3000) }>;
+/// type Ota = tamp::Decompressor<{ tamp::largest_window_for!(Decompressor, 9000) }>;
+/// ```
+#[macro_export]
+macro_rules! largest_window_for {
+    (Decompressor, $budget:expr) => {{
+        let window = $crate::budget::largest_window(
+            $budget,
+            $crate::Decompressor::<256>::MEMORY_BYTES - 256,
+        );
+        assert!(window != 0, "RAM budget too small for any tamp window");
+        window
+    }};
+    ($budget:expr) => {{
+        let window = $crate::budget::largest_window(
+            $budget,
+            $crate::Compressor::<256>::MEMORY_BYTES - 256,
+        );
+        assert!(window != 0, "RAM budget too small for any tamp window");
+        window
+    }};
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Config, Decompressor};
+
+    const N: usize = largest_window_for!(3000);
+    const _: () = assert!(Compressor::<N>::MEMORY_BYTES <= 3000);
+    const _: () = assert!(Compressor::<{ 2 * N }>::MEMORY_BYTES > 3000);
+    const _: () = assert!(largest_window_for!(Decompressor, 600) >= 256);
+
+    #[test]
+    fn test_largest_window() {
+        assert_eq!(largest_window(3000, 100), 2048);
+        assert_eq!(largest_window(2148, 100), 2048);
+        assert_eq!(largest_window(2147, 100), 1024);
+        assert_eq!(largest_window(300, 100), 0);
+        assert_eq!(largest_window(usize::MAX, 100), 32768);
+        assert_eq!(largest_window(50, 100), 0);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_largest_window_for() {
+        let config = Config::new().window_bits(N.trailing_zeros() as u8).unwrap();
+        assert!(Compressor::<N>::new(config).is_ok());
+        let _ = Decompressor::<{ largest_window_for!(Decompressor, 600) }>::MEMORY_BYTES;
+    }
+}
@@ -0,0 +1,154 @@
+//! `bytes::Buf` / `bytes::BufMut` entry points for services built on tokio and `bytes`.
+//!
+//! Input is consumed chunk by chunk, so a non-contiguous `Buf` (e.g. a `Chain` of frames)
+//! is compressed without first being copied into one slice, and output is written straight
+//! into the `BufMut` spare capacity.
+
+use bytes::{Buf, BufMut};
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "compressor")]
+use crate::compressor::MAX_FLUSH;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+
+#[cfg(feature = "compressor")]
+impl<const N: usize> Compressor<N> {
+    /// Compresses `input` into `output`, advancing both.
+    /// Stops once `input` is drained or `output` is full.
+    /// Returns (input_consumed, output_written).
+    pub fn compress_buf(
+        &mut self,
+        input: &mut impl Buf,
+        output: &mut impl BufMut,
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        while input.has_remaining() && output.has_remaining_mut() {
+            // SAFETY: the compressor only writes initialized bytes, reported as `n` below
+            let out = unsafe { output.chunk_mut().as_uninit_slice_mut() };
+            let (c, n) = self
+                .compress_chunk_uninit(input.chunk(), out)
+                .map(|(c, w)| (c, w.len()))?;
+            input.advance(c);
+            // SAFETY: the first `n` bytes of the chunk were written above
+            unsafe { output.advance_mut(n) };
+            consumed += c;
+            written += n;
+            if c == 0 && n == 0 {
+                break;
+            }
+        }
+        Ok((consumed, written))
+    }
+
+    /// Flushes remaining data into `output`, see `flush()`.
+    /// Fails with `OutputFull`, writing nothing, unless `output` has room for a full flush.
+    pub fn flush_buf(
+        &mut self,
+        output: &mut impl BufMut,
+        write_token: bool,
+    ) -> Result<usize, Error> {
+        if output.remaining_mut() < MAX_FLUSH {
+            return Err(Error::OutputFull);
+        }
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.flush(&mut buf, write_token)?;
+        output.put_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "decompressor")]
+impl<const N: usize> Decompressor<N> {
+    /// Decompresses `input` (header already consumed) into `output`, advancing both.
+    /// Stops once `input` is drained or `output` is full.
+    /// Returns (input_consumed, output_written).
+    pub fn decompress_buf(
+        &mut self,
+        input: &mut impl Buf,
+        output: &mut impl BufMut,
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        while output.has_remaining_mut() {
+            // SAFETY: the decompressor only writes initialized bytes, reported as `n` below
+            let out = unsafe { output.chunk_mut().as_uninit_slice_mut() };
+            let (c, n) = self
+                .decompress_chunk_uninit(input.chunk(), out)
+                .map(|(c, w)| (c, w.len()))?;
+            input.advance(c);
+            // SAFETY: the first `n` bytes of the chunk were written above
+            unsafe { output.advance_mut(n) };
+            consumed += c;
+            written += n;
+            if c == 0 && n == 0 {
+                break;
+            }
+        }
+        Ok((consumed, written))
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use alloc::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_buf_round_trip() {
+        let frames: [[u8; 300]; 3] = core::array::from_fn(|f| {
+            core::array::from_fn(|i| b"GET /metrics "[i % 13] ^ f as u8)
+        });
+        let mut input = (&frames[0][..]).chain(&frames[1][..]).chain(&frames[2][..]);
+
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let (consumed, written) = compressor
+            .compress_buf(&mut input, &mut compressed)
+            .unwrap();
+        assert_eq!(consumed, 900);
+        assert!(!input.has_remaining());
+        let mut small = [0u8; 8];
+        assert!(matches!(
+            compressor.flush_buf(&mut &mut small[..], false),
+            Err(Error::OutputFull)
+        ));
+        let flushed = compressor.flush_buf(&mut compressed, false).unwrap();
+        assert_eq!(compressed.len(), written + flushed);
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let (head, tail) = compressed[header..].split_at(7);
+        let mut input = head.chain(tail);
+        let mut output = [0u8; 1000];
+        let mut out = &mut output[..];
+        let (_, n) = decompressor.decompress_buf(&mut input, &mut out).unwrap();
+        assert_eq!(n, 900);
+        assert_eq!(&output[..n], frames.concat());
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_buf_incompressible() {
+        // A `Vec` hands out 64 bytes of spare capacity per chunk, so noise fills many chunks
+        let noise: Vec<u8> = (0..2000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let (consumed, written) = compressor
+            .compress_buf(&mut &noise[..], &mut compressed)
+            .unwrap();
+        assert_eq!(consumed, noise.len());
+        assert_eq!(compressed.len(), written);
+        compressor.flush_buf(&mut compressed, false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = Vec::with_capacity(noise.len());
+        decompressor
+            .decompress_buf(&mut &compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(output, noise);
+    }
+}
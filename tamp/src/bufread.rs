@@ -0,0 +1,273 @@
+//! `BufRead`-style decompression: borrow a decoded slice, then consume part of it.
+//!
+//! Parsers, e.g. line splitters over compressed logs, can work directly on the decoded bytes
+//! in the internal output buffer instead of copying them into a buffer of their own.
+//! `BufDecompressor` takes compressed input as a cursor like `decompress_exact()`;
+//! with `std`, `DecompressReader` pulls it from an `io::Read` and implements `io::BufRead`,
+//! or the `acid_io` traits with that feature on `no_std`.
+
+use crate::{Decompressor, Error};
+#[cfg(feature = "std")]
+use std::io;
+
+/// Decompressor with a `B` byte output buffer exposed through `fill_buf()` / `consume()`.
+pub struct BufDecompressor<const N: usize, const B: usize> {
+    decompressor: Decompressor<N>,
+    buf: [u8; B],
+    pos: usize,
+    len: usize,
+}
+
+impl<const N: usize, const B: usize> BufDecompressor<N, B> {
+    /// Wraps `decompressor`, whose header must already be consumed.
+    pub fn new(decompressor: Decompressor<N>) -> Self {
+        Self {
+            decompressor,
+            buf: [0; B],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the buffered decoded bytes, decoding more from `input` once all of them are
+    /// consumed and advancing the `input` cursor past the bytes used.
+    /// An empty slice means `input` ran out, or the stream ended.
+    pub fn fill_buf(&mut self, input: &mut &[u8]) -> Result<&[u8], Error> {
+        while self.pos == self.len {
+            let (consumed, written) = self.decompressor.decompress_chunk(input, &mut self.buf)?;
+            *input = &input[consumed..];
+            self.pos = 0;
+            self.len = written;
+            if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    /// Marks `amt` bytes of the slice last returned by `fill_buf()` as read.
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+
+    /// Returns true if decoded bytes are waiting to be consumed.
+    pub fn has_buffered(&self) -> bool {
+        self.pos < self.len
+    }
+
+    /// Returns the decompressor, dropping any unconsumed decoded bytes.
+    pub fn into_inner(self) -> Decompressor<N> {
+        self.decompressor
+    }
+}
+
+/// `io::BufRead` over a compressed `io::Read` source, staging up to `I` compressed bytes.
+/// With the `acid_io` feature and without `std`, it implements `acid_io::BufRead` instead;
+/// build it with `with_decompressor()` there.
+#[cfg(any(feature = "std", feature = "acid_io"))]
+pub struct DecompressReader<R, const N: usize, const B: usize, const I: usize = 256> {
+    inner: BufDecompressor<N, B>,
+    source: R,
+    input: [u8; I],
+    start: usize,
+    end: usize,
+}
+
+#[cfg(any(feature = "std", feature = "acid_io"))]
+impl<R, const N: usize, const B: usize, const I: usize> DecompressReader<R, N, B, I> {
+    /// Reads compressed input from `source` into `decompressor`, whose header must already
+    /// be consumed.
+    pub fn with_decompressor(decompressor: Decompressor<N>, source: R) -> Self {
+        Self {
+            inner: BufDecompressor::new(decompressor),
+            source,
+            input: [0; I],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the decompressor and the source, dropping buffered bytes.
+    pub fn into_inner(self) -> (Decompressor<N>, R) {
+        (self.inner.into_inner(), self.source)
+    }
+
+    /// `fill_buf()` for any `Read` flavor: `read` pulls from the source, codec errors are
+    /// mapped with `map_err`.
+    fn fill<E>(
+        &mut self,
+        mut read: impl FnMut(&mut R, &mut [u8]) -> Result<usize, E>,
+        map_err: impl Fn(Error) -> E,
+    ) -> Result<&[u8], E> {
+        while !self.inner.has_buffered() {
+            let mut input = &self.input[self.start..self.end];
+            let before = input.len();
+            self.inner.fill_buf(&mut input).map_err(&map_err)?;
+            self.start += before - input.len();
+            if self.inner.has_buffered() {
+                break;
+            }
+            self.input.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+            let n = read(&mut self.source, &mut self.input[self.end..])?;
+            if n == 0 {
+                break;
+            }
+            self.end += n;
+        }
+        let mut empty: &[u8] = &[];
+        self.inner.fill_buf(&mut empty).map_err(map_err)
+    }
+
+    /// Marks `amt` decoded bytes as read.
+    fn advance(&mut self, amt: usize) {
+        self.inner.consume(amt);
+    }
+}
+
+/// Maps a codec error to `InvalidData`: while decoding, any of them means a corrupt stream.
+/// The `tamp::Error` is kept as the source.
+#[cfg(feature = "std")]
+fn io_error(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read, const N: usize, const B: usize, const I: usize> DecompressReader<R, N, B, I> {
+    /// Reads the header from `source` and sets up a decompressor for it.
+    pub fn new(mut source: R) -> io::Result<Self> {
+        let mut input = [0u8; I];
+        // The header is a single byte, so one successful read is enough
+        let end = source.read(&mut input)?;
+        if end == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (decompressor, start) = Decompressor::from_header(&input[..end]).map_err(io_error)?;
+        let mut reader = Self::with_decompressor(decompressor, source);
+        reader.input = input;
+        reader.start = start;
+        reader.end = end;
+        Ok(reader)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read, const N: usize, const B: usize, const I: usize> io::BufRead
+    for DecompressReader<R, N, B, I>
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.fill(|source, buf| source.read(buf), io_error)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.advance(amt);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read, const N: usize, const B: usize, const I: usize> io::Read
+    for DecompressReader<R, N, B, I>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use io::BufRead;
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Maps a codec error to `InvalidData`.
+#[cfg(all(feature = "acid_io", not(feature = "std")))]
+fn acid_io_error(_: Error) -> acid_io::Error {
+    acid_io::ErrorKind::InvalidData.into()
+}
+
+#[cfg(all(feature = "acid_io", not(feature = "std")))]
+impl<R: acid_io::Read, const N: usize, const B: usize, const I: usize> acid_io::BufRead
+    for DecompressReader<R, N, B, I>
+{
+    fn fill_buf(&mut self) -> acid_io::Result<&[u8]> {
+        self.fill(|source, buf| source.read(buf), acid_io_error)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.advance(amt);
+    }
+}
+
+#[cfg(all(feature = "acid_io", not(feature = "std")))]
+impl<R: acid_io::Read, const N: usize, const B: usize, const I: usize> acid_io::Read
+    for DecompressReader<R, N, B, I>
+{
+    fn read(&mut self, buf: &mut [u8]) -> acid_io::Result<usize> {
+        let available = self.fill(|source, buf| source.read(buf), acid_io_error)?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+#[cfg(all(test, feature = "compressor"))]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Config};
+
+    /// Compresses `text` with a 256 byte window, returning the stream and its length.
+    fn compress(text: &[u8]) -> ([u8; 2048], usize) {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 2048];
+        let len = compressor.compress_all(text, &mut stream).unwrap();
+        (stream, len)
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_fill_buf_consume() {
+        let text: [u8; 1200] = core::array::from_fn(|i| b"boot ok\nlink up\n"[i % 16]);
+        let (stream, len) = compress(&text);
+
+        let (decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut reader = BufDecompressor::<256, 32>::new(decompressor);
+        let mut lines = 0;
+        let mut decoded = 0;
+        // Compressed input arrives in 10 byte pieces, lines are split out of the decoded buffer
+        for mut piece in stream[header..len].chunks(10) {
+            loop {
+                let buf = reader.fill_buf(&mut piece).unwrap();
+                if buf.is_empty() {
+                    break;
+                }
+                let n = match buf.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        lines += 1;
+                        end + 1
+                    }
+                    None => buf.len(),
+                };
+                assert_eq!(buf[..n], text[decoded..decoded + n]);
+                decoded += n;
+                reader.consume(n);
+            }
+        }
+        assert_eq!(decoded, text.len());
+        assert_eq!(lines, 150);
+    }
+
+    #[cfg(feature = "std")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_decompress_reader_lines() {
+        use std::io::BufRead;
+        let text: [u8; 1200] = core::array::from_fn(|i| b"boot ok\nlink up\n"[i % 16]);
+        let (stream, len) = compress(&text);
+
+        let reader = DecompressReader::<_, 256, 32, 16>::new(&stream[..len]).unwrap();
+        let lines: std::vec::Vec<_> = reader.lines().map(Result::unwrap).collect();
+        assert_eq!(lines.len(), 150);
+        assert!(lines.chunks(2).all(|pair| pair == ["boot ok", "link up"]));
+    }
+}
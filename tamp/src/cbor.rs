@@ -0,0 +1,77 @@
+//! CBOR messages compressed with tamp, via `minicbor`.
+//!
+//! Mirrors the `postcard` helpers for devices that already speak CBOR, e.g. LwM2M or CoAP
+//! payloads: the value is encoded into caller scratch space and compressed as one stream.
+
+use minicbor::encode::write::Cursor;
+use minicbor::{Decode, Encode};
+use crate::{Compressor, Decompressor, Error};
+
+/// Encodes `value` as CBOR into `scratch`, then compresses it into `output`.
+///
+/// Writes a complete stream (header and final flush), so `compressor` should be freshly
+/// created. Returns bytes written to `output`. `BufferTooSmall` means `scratch` could not
+/// hold the encoded value, `OutputFull` that `output` could not hold the compressed one.
+pub fn compress_cbor<T: Encode<()> + ?Sized, const N: usize>(
+    compressor: &mut Compressor<N>,
+    value: &T,
+    scratch: &mut [u8],
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    let mut cursor = Cursor::new(scratch);
+    minicbor::encode(value, &mut cursor).map_err(|e| {
+        if e.is_write() {
+            Error::BufferTooSmall
+        } else {
+            Error::Serialization
+        }
+    })?;
+    let len = cursor.position();
+    compressor.compress_all(&cursor.into_inner()[..len], output)
+}
+
+/// Decompresses a complete stream into `scratch`, then decodes a CBOR `T` from it.
+///
+/// The window size is read from the header and must match `N`. The returned value may
+/// borrow from `scratch` (e.g. `&str` fields), avoiding a copy. `OutputFull` means `scratch`
+/// could not hold the decompressed payload.
+pub fn decompress_cbor<'a, T: Decode<'a, ()>, const N: usize>(
+    input: &[u8],
+    scratch: &'a mut [u8],
+) -> Result<T, Error> {
+    let (mut decompressor, header_len) = Decompressor::<N>::from_header(input)?;
+    let len = decompressor.decompress_all(&input[header_len..], scratch)?;
+    minicbor::decode(&scratch[..len]).map_err(|_| Error::Serialization)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_cbor_round_trip() {
+        let value = (7u16, "/3303/0/5700", [21.5f32, 21.5, 21.6, 21.5]);
+        let mut scratch = [0u8; 128];
+        let mut output = [0u8; 128];
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let len = compress_cbor(&mut compressor, &value, &mut scratch, &mut output).unwrap();
+
+        let mut scratch = [0u8; 128];
+        let decoded: (u16, &str, [f32; 4]) =
+            decompress_cbor::<_, 256>(&output[..len], &mut scratch).unwrap();
+        assert_eq!(decoded, value);
+
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        assert!(matches!(
+            compress_cbor(&mut compressor, &value, &mut [0u8; 8], &mut output),
+            Err(Error::BufferTooSmall)
+        ));
+        let mut small = [0u8; 8];
+        assert!(matches!(
+            decompress_cbor::<(u16, &str, [f32; 4]), 256>(&output[..len], &mut small),
+            Err(Error::OutputFull)
+        ));
+    }
+}
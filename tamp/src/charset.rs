@@ -0,0 +1,166 @@
+//! Table-driven charset mapping for 5 and 6-bit literals.
+//!
+//! Bytes of a small charset (digits, upper case, NMEA punctuation, ...) are mapped to codes
+//! `0..charset.len()`, so they fit `literal_bits(5)` or `literal_bits(6)`. Any other byte is
+//! sent as the escape code followed by its two nibbles. `Charset` is a `Filter`: encode in
+//! front of the compressor, decode after the decompressor.
+
+use crate::filter::Filter;
+use crate::{Config, Error};
+
+/// Marks bytes outside the charset in the forward table.
+const UNMAPPED: u8 = 0xFF;
+
+/// Maps a charset to a small literal alphabet with an escape code for other bytes.
+#[derive(Clone)]
+pub struct Charset {
+    forward: [u8; 256],
+    reverse: [u8; 64],
+    bits: u8,
+    pending: [u8; 3],
+    start: usize,
+    end: usize,
+    escape: Escape,
+}
+
+/// Decoder position within an escape sequence.
+#[derive(Clone, Copy)]
+enum Escape {
+    None,
+    High,
+    Low(u8),
+}
+
+impl Charset {
+    /// Creates a mapping for literals of `bits` (5 or 6) bits.
+    /// `charset` holds at most `2^bits - 1` distinct bytes; the last code is the escape.
+    pub fn new(charset: &[u8], bits: u8) -> Result<Self, Error> {
+        if !(5..=6).contains(&bits) {
+            return Err(Error::InvalidConfig("Charset literals must be 5 or 6 bits"));
+        }
+        if charset.len() >= 1 << bits {
+            return Err(Error::InvalidConfig("Charset too large for literal size"));
+        }
+        let mut forward = [UNMAPPED; 256];
+        let mut reverse = [b'?'; 64];
+        for (code, &byte) in charset.iter().enumerate() {
+            if forward[byte as usize] != UNMAPPED {
+                return Err(Error::InvalidConfig("Duplicate byte in charset"));
+            }
+            forward[byte as usize] = code as u8;
+            reverse[code] = byte;
+        }
+        Ok(Self {
+            forward,
+            reverse,
+            bits,
+            pending: [0; 3],
+            start: 0,
+            end: 0,
+            escape: Escape::None,
+        })
+    }
+
+    /// Returns `config` with the literal size this mapping needs.
+    pub fn configure(&self, mut config: Config) -> Config {
+        config.literal_bits = self.bits;
+        config
+    }
+
+    fn escape_code(&self) -> u8 {
+        (1 << self.bits) - 1
+    }
+}
+
+impl Filter for Charset {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        while written < output.len() {
+            if self.start < self.end {
+                output[written] = self.pending[self.start];
+                self.start += 1;
+                written += 1;
+                continue;
+            }
+            let Some(&byte) = input.get(consumed) else {
+                break;
+            };
+            consumed += 1;
+            let code = self.forward[byte as usize];
+            if code != UNMAPPED {
+                self.pending[0] = code;
+                self.end = 1;
+            } else {
+                self.pending = [self.escape_code(), byte >> 4, byte & 0x0F];
+                self.end = 3;
+            }
+            self.start = 0;
+        }
+        (consumed, written)
+    }
+
+    /// Undefined codes decode as `?`.
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        while written < output.len() {
+            let Some(&code) = input.get(consumed) else {
+                break;
+            };
+            consumed += 1;
+            self.escape = match self.escape {
+                Escape::None if code == self.escape_code() => Escape::High,
+                Escape::None => {
+                    output[written] = self.reverse[code as usize & 63];
+                    written += 1;
+                    Escape::None
+                }
+                Escape::High => Escape::Low(code & 0x0F),
+                Escape::Low(high) => {
+                    output[written] = high << 4 | (code & 0x0F);
+                    written += 1;
+                    Escape::None
+                }
+            };
+        }
+        (consumed, written)
+    }
+
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        self.encode(&[], output).1
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_charset_round_trip() {
+        let nmea = b"$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n";
+        let charset = Charset::new(b"0123456789,.*$ABCEGMNPRW\r\n", 5).unwrap();
+        let config = charset.configure(Config::new().window_bits(8).unwrap());
+
+        let mut mapped = [0u8; 128];
+        let (consumed, len) = charset.clone().encode(b"~", &mut mapped);
+        assert_eq!((consumed, len), (1, 3));
+        let (_, len) = charset.clone().encode(nmea, &mut mapped);
+
+        let mut compressor = Compressor::<256>::new(config).unwrap();
+        let mut compressed = [0u8; 256];
+        let (_, mut clen) = compressor
+            .compress_chunk(&mapped[..len], &mut compressed)
+            .unwrap();
+        clen += compressor.flush(&mut compressed[clen..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut codes = [0u8; 128];
+        let (_, n) = decompressor
+            .decompress_chunk(&compressed[header..clen], &mut codes)
+            .unwrap();
+        let mut text = [0u8; 128];
+        let (_, n) = charset.clone().decode(&codes[..n], &mut text);
+        assert_eq!(&text[..n], &nmea[..]);
+    }
+}
@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::{Compressor, Config, Decompressor, Error};
+
+/// Wrapper that stores `T` as a tamp-compressed postcard payload inside any serde format.
+///
+/// On serialize `T` is encoded with postcard, compressed with a `N` byte window and written
+/// as a byte string; deserialize reverses this. The tamp header at the start of the bytes
+/// carries the window configuration, so a mismatched `N` is rejected rather than misdecoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compressed<T, const N: usize = 1024>(pub T);
+
+impl<T, const N: usize> Compressed<T, N> {
+    /// Wraps a value.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Serialize, const N: usize> Serialize for Compressed<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::Error as _;
+        let payload = postcard::to_allocvec(&self.0).map_err(S::Error::custom)?;
+        let compressed = compress_vec::<N>(&payload)
+            .map_err(|e| S::Error::custom(format_args!("tamp: {:?}", e)))?;
+        serializer.serialize_bytes(&compressed)
+    }
+}
+
+impl<'de, T: DeserializeOwned, const N: usize> Deserialize<'de> for Compressed<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use de::Error as _;
+        let compressed = deserializer.deserialize_bytes(BytesVisitor(PhantomData))?;
+        let payload = decompress_vec::<N>(&compressed)
+            .map_err(|e| D::Error::custom(format_args!("tamp: {:?}", e)))?;
+        postcard::from_bytes(&payload)
+            .map(Self)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Accepts byte strings, and sequences of bytes for formats without a bytes type (JSON).
+struct BytesVisitor<'de>(PhantomData<&'de ()>);
+
+impl<'de> Visitor<'de> for BytesVisitor<'de> {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("tamp compressed bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Compresses `input` into a new vector, including header and final flush.
+fn compress_vec<const N: usize>(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let config = Config::new().window_bits(N.trailing_zeros() as u8)?;
+    let mut compressor = Compressor::<N>::new(config)?;
+    let mut output = Vec::with_capacity(input.len() / 2);
+    // 128 input bytes never expand beyond the buffer, even with 8-bit literals
+    let mut buffer = [0u8; 256];
+    for chunk in input.chunks(128) {
+        let mut chunk = chunk;
+        while !chunk.is_empty() {
+            let (consumed, written) = compressor.compress_chunk(chunk, &mut buffer)?;
+            output.extend_from_slice(&buffer[..written]);
+            chunk = &chunk[consumed..];
+        }
+    }
+    let written = compressor.flush(&mut buffer, false)?;
+    output.extend_from_slice(&buffer[..written]);
+    Ok(output)
+}
+
+/// Decompresses a complete stream (header included) into a new vector.
+fn decompress_vec<const N: usize>(input: &[u8]) -> Result<Vec<u8>, Error> {
+    let (mut decompressor, header_len) = Decompressor::<N>::from_header(input)?;
+    let mut input = &input[header_len..];
+    let mut output = Vec::with_capacity(input.len() * 2);
+    let mut buffer = [0u8; 256];
+    loop {
+        let (consumed, written) = decompressor.decompress_chunk(input, &mut buffer)?;
+        output.extend_from_slice(&buffer[..written]);
+        input = &input[consumed..];
+        if consumed == 0 && written == 0 {
+            return Ok(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compressed_round_trip() {
+        let readings: Vec<u32> = (0..512).map(|i| 1000 + i % 8).collect();
+        let document = (7u8, Compressed::<_, 1024>(readings.clone()));
+
+        let encoded = postcard::to_allocvec(&document).unwrap();
+        assert!(encoded.len() < postcard::to_allocvec(&readings).unwrap().len());
+
+        let (tag, decoded): (u8, Compressed<Vec<u32>, 1024>) =
+            postcard::from_bytes(&encoded).unwrap();
+        assert_eq!(tag, 7);
+        assert_eq!(decoded.into_inner(), readings);
+
+        // Window size comes from the header and must match N
+        let mismatched: Result<(u8, Compressed<Vec<u32>, 256>), _> =
+            postcard::from_bytes(&encoded);
+        assert!(mismatched.is_err());
+    }
+}
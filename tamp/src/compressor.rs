@@ -1,181 +1,302 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::fmt;
 use core::marker::PhantomData;
-use heapless::Vec;
+use core::mem::MaybeUninit;
 use tamp_sys::{
-    TampCompressor, TampConf, tamp_compressor_compress_cb, tamp_compressor_flush,
+    TAMP_OUTPUT_FULL, TampCompressor, tamp_compressor_compress_cb, tamp_compressor_flush,
     tamp_compressor_full, tamp_compressor_init, tamp_compressor_poll, tamp_compressor_sink,
-    tamp_initialize_dictionary,
+    tamp_res,
 };
-use crate::Error;
+use crate::sink::Sink;
+use crate::window::{self, WindowStorage};
+use crate::{Config, Error};
 
-/// Configuration for tamp compression/decompression.
-///
-/// Default configuration uses 10-bit window (1KB), 8-bit literals, lazy matching enabled.
-#[derive(Clone)]
-pub struct Config {
-    /// Window size in bits (8-15). Window size = 2^window_bits bytes. Default: 10 (1KB).
-    pub window_bits: u8,
-    /// Literal size in bits (5-8). Default: 8.
-    pub literal_bits: u8,
-    /// Enable lazy matching for better compression at cost of ~50% more CPU. Default: true.
-    pub lazy_matching: bool,
-    /// Use custom dictionary initialization. Default: false.
-    pub use_custom_dictionary: bool,
-}
-
-impl Default for Config {
-    /// Creates default configuration: 10-bit window (1KB), 8-bit literals, lazy matching enabled.
-    fn default() -> Self {
-        Self {
-            window_bits: 10, // 1KB window
-            literal_bits: 8,
-            lazy_matching: false,
-            use_custom_dictionary: false,
-        }
-    }
-}
+/// Upper bound on the bytes a single `poll()` writes: the 32-bit bit buffer drained twice.
+pub(crate) const MAX_POLL: usize = 8;
+/// Upper bound on the bytes `flush()` writes: 16 buffered literals plus the bit buffer.
+pub(crate) const MAX_FLUSH: usize = 32;
+/// Size of the C input buffer that `sink()` fills.
+pub(crate) const INPUT_BUFFER: usize = 16;
+/// Stack buffer `compress_into()` compresses through before pushing to the sink.
+const STAGE: usize = 64;
 
-impl Config {
-    /// Creates a new configuration with default settings.
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    /// Sets window size in bits (8-15). Window size = 2^bits bytes.
-    /// Larger windows provide better compression but use more memory.
-    pub fn window_bits(mut self, bits: u8) -> Result<Self, Error> {
-        if !(8..=15).contains(&bits) {
-            return Err(Error::InvalidConfig("Window bits must be 8-15"));
-        }
-        self.window_bits = bits;
-        Ok(self)
-    }
-
-    /// Sets literal size in bits (5-8). More bits = larger alphabet but less compression.
-    pub fn literal_bits(mut self, bits: u8) -> Result<Self, Error> {
-        if !(5..=8).contains(&bits) {
-            return Err(Error::InvalidConfig("Literal bits must be 5-8"));
+/// Seeds a compressor window from `dictionary`, or with tamp's built-in dictionary.
+#[cfg(feature = "dictionary")]
+pub(crate) fn init_window(
+    window: &mut [u8],
+    config: &Config,
+    dictionary: Option<&[u8]>,
+) -> Result<(), Error> {
+    let n = window.len();
+    if let Some(dict) = dictionary {
+        if config.use_custom_dictionary {
+            let copy_len = dict.len().min(n);
+            window[..copy_len].copy_from_slice(&dict[..copy_len]);
+        } else {
+            // Use tamp's built-in dictionary initialization
+            unsafe {
+                tamp_sys::tamp_initialize_dictionary(window.as_mut_ptr(), n);
+            }
+            if !dict.is_empty() {
+                let copy_len = dict.len().min(n);
+                window[..copy_len].copy_from_slice(&dict[..copy_len]);
+            }
         }
-        self.literal_bits = bits;
-        Ok(self)
-    }
-
-    /// Enables lazy matching. Improves compression ~0.5-2% at cost of ~50% more CPU.
-    pub fn lazy_matching(mut self, enabled: bool) -> Self {
-        self.lazy_matching = enabled;
-        self
-    }
-
-    /// Enables custom dictionary initialization. Dictionary must be provided during construction.
-    pub fn custom_dictionary(mut self, enabled: bool) -> Self {
-        self.use_custom_dictionary = enabled;
-        self
+    } else if config.use_custom_dictionary {
+        return Err(Error::InvalidConfig(
+            "Custom dictionary enabled but none provided",
+        ));
     }
+    Ok(())
+}
 
-    pub(crate) fn to_c_config(&self) -> TampConf {
-        let mut conf = TampConf {
-            _bitfield_align_1: [],
-            _bitfield_1: Default::default(),
-        };
-        conf.set_window(self.window_bits as u16);
-        conf.set_literal(self.literal_bits as u16);
-        conf.set_use_custom_dictionary(self.use_custom_dictionary as u16);
-        // Note: lazy_matching not available in current bindings
-        conf
+/// Without the `dictionary` feature the C library seeds the built-in dictionary itself, and
+/// custom dictionaries are rejected.
+#[cfg(not(feature = "dictionary"))]
+pub(crate) fn init_window(
+    _window: &mut [u8],
+    config: &Config,
+    dictionary: Option<&[u8]>,
+) -> Result<(), Error> {
+    if config.use_custom_dictionary || dictionary.is_some() {
+        return Err(Error::InvalidConfig("Dictionaries need the `dictionary` feature"));
     }
+    Ok(())
+}
 
-    /// Returns window size in bytes (2^window_bits).
-    pub fn window_size(&self) -> usize {
-        1usize << self.window_bits
-    }
+/// Progress through a slice of input buffers, see `Compressor::compress_chunks`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChunksConsumed {
+    /// Number of leading buffers consumed entirely.
+    pub slices: usize,
+    /// Bytes consumed from the buffer after them.
+    pub bytes: usize,
 }
 
-/// Streaming compressor with heapless window buffer.
+/// Streaming compressor with an inline window buffer.
 ///
 /// `N` is the window buffer size in bytes and must equal 2^window_bits.
 /// Use type aliases like `Compressor1K` for convenience.
 ///
-/// Memory usage: ~N + 64 bytes (window + struct overhead).
-pub struct Compressor<const N: usize> {
+/// Memory usage: ~N + 64 bytes (window + struct overhead); see `MEMORY_BYTES` for the exact figure.
+/// `W` holds the window; besides the default inline array it can be any `WindowStorage`,
+/// e.g. a `&'static mut` window placed in another memory, see `with_storage()`.
+pub struct Compressor<const N: usize, W: WindowStorage<N> = [u8; N]> {
     inner: TampCompressor,
-    window: Vec<u8, N>,
+    window: W,
+    /// Input bytes consumed since creation or `reset()`.
+    consumed: u64,
+    /// Set by the final flush.
+    finished: bool,
     _marker: PhantomData<*mut ()>, // !Send + !Sync for raw C state
 }
 
 impl<const N: usize> Compressor<N> {
+    /// Exact RAM footprint of a `Compressor<N>` in bytes: the window, the C state and the
+    /// `Debug` counters.
+    ///
+    /// Usable in const context to check a RAM budget at compile time, e.g.
+    /// `const _: () = assert!(Compressor::<1024>::MEMORY_BYTES <= 1200);`
+    pub const MEMORY_BYTES: usize = core::mem::size_of::<Self>();
+
+    /// Creates a compressor with the default configuration and the window size implied by `N`,
+    /// e.g. `Compressor1K::new_default()`. Fails to compile unless `N` is a power of two
+    /// from 256 to 32768.
+    pub fn new_default() -> Self {
+        const {
+            assert!(N.is_power_of_two() && N >= 256 && N <= 32768, "Invalid window size N")
+        };
+        let config = Config::new()
+            .window_bits(N.trailing_zeros() as u8)
+            .expect("N checked at compile time");
+        Self::new(config).expect("default configuration is valid")
+    }
+
     /// Creates a new compressor with the given configuration.
     /// Buffer size N must equal 2^config.window_bits.
     pub fn new(config: Config) -> Result<Self, Error> {
-        Self::with_dictionary(config, None)
+        Self::with_storage(config, [0u8; N])
     }
 
     /// Creates a compressor with optional dictionary initialization.
     /// Dictionary improves compression when data has predictable patterns.
+    #[cfg(feature = "dictionary")]
     pub fn with_dictionary(config: Config, dictionary: Option<&[u8]>) -> Result<Self, Error> {
-        let expected_size = config.window_size();
-        if N != expected_size {
-            return Err(Error::InvalidConfig(
-                "Buffer size N must equal 2^window_bits",
-            ));
-        }
+        Self::with_storage_dictionary(config, [0u8; N], dictionary)
+    }
 
-        let mut window = Vec::new();
-        window.resize(N, 0).map_err(|_| Error::BufferTooSmall)?;
+    /// Creates a compressor on the heap, building the window in place. Unlike
+    /// `Box::new(Compressor::new(config)?)`, it never passes through the stack, so large
+    /// windows fit small stacks.
+    #[cfg(feature = "alloc")]
+    pub fn new_boxed(config: Config) -> Result<Box<Self>, Error> {
+        // SAFETY: every field is valid when zeroed: null C pointers, empty buffers, zero
+        // counters. `reset()` then initializes the C state against the boxed window.
+        let mut compressor = unsafe { Box::<Self>::new_zeroed().assume_init() };
+        compressor.reset(config)?;
+        Ok(compressor)
+    }
 
-        // Initialize dictionary if provided
-        if let Some(dict) = dictionary {
-            if config.use_custom_dictionary {
-                let copy_len = dict.len().min(N);
-                window[..copy_len].copy_from_slice(&dict[..copy_len]);
-            } else {
-                // Use tamp's built-in dictionary initialization
-                unsafe {
-                    tamp_initialize_dictionary(window.as_mut_ptr(), N);
-                }
-                if !dict.is_empty() {
-                    let copy_len = dict.len().min(N);
-                    window[..copy_len].copy_from_slice(&dict[..copy_len]);
-                }
-            }
-        } else if config.use_custom_dictionary {
-            return Err(Error::InvalidConfig(
-                "Custom dictionary enabled but none provided",
-            ));
+    /// Duplicates the compressor state, window and buffered input included, for speculative
+    /// compression: trial-compress a payload on the fork, check its size, then either
+    /// `discard()` the fork or keep it in place of `self`. The original stream is never touched.
+    pub fn fork(&self) -> Self {
+        Self {
+            // The window pointer is refreshed by `inner()` before every C call
+            inner: self.inner,
+            window: self.window,
+            consumed: self.consumed,
+            finished: self.finished,
+            _marker: PhantomData,
         }
+    }
 
-        let mut compressor = Self {
+    /// Rebuilds a compressor at a flush boundary from `window_state()`, continuing the
+    /// stream without writing a second header.
+    pub(crate) fn restore(config: Config, window: &[u8; N], pos: usize) -> Result<Self, Error> {
+        // The window is overwritten below, so no dictionary is needed to initialize
+        let mut compressor = Self::new(Config {
+            use_custom_dictionary: false,
+            ..config
+        })?;
+        compressor.window = *window;
+        compressor.inner.set_window_pos(pos as u32);
+        // Drop the header `new()` queued in the bit buffer
+        compressor.inner.bit_buffer = 0;
+        compressor.inner.set_bit_buffer_pos(0);
+        Ok(compressor)
+    }
+}
+
+impl<const N: usize> Compressor<N, &'static mut [u8; N]> {
+    /// Creates a compressor working in `window`, e.g. a buffer with
+    /// `#[link_section = ".ccmram"]` or from a `StaticCell`. The compressor itself then only
+    /// holds the C state and a pointer, so it can live on a small stack or be moved freely
+    /// while the window stays in the memory chosen for it. The window is cleared first.
+    pub fn new_static(config: Config, window: &'static mut [u8; N]) -> Result<Self, Error> {
+        Self::with_storage(config, window)
+    }
+}
+
+impl<const N: usize, W: WindowStorage<N>> Compressor<N, W> {
+    /// Creates a compressor keeping its window in `storage`, which is cleared first.
+    pub fn with_storage(config: Config, storage: W) -> Result<Self, Error> {
+        Self::with_storage_dictionary(config, storage, None)
+    }
+
+    /// `with_storage()` with optional dictionary initialization, as in `with_dictionary()`.
+    pub(crate) fn with_storage_dictionary(
+        config: Config,
+        storage: W,
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let mut compressor = Self::from_window(storage);
+        compressor.init(config, dictionary)?;
+        Ok(compressor)
+    }
+
+    /// Wraps `window` with zeroed, not yet initialized C state.
+    fn from_window(window: W) -> Self {
+        Self {
             inner: unsafe { core::mem::zeroed() },
             window,
+            consumed: 0,
+            finished: false,
             _marker: PhantomData,
-        };
+        }
+    }
 
+    /// Seeds the window and initializes the C state for a new stream.
+    fn init(&mut self, config: Config, dictionary: Option<&[u8]>) -> Result<(), Error> {
+        if N != config.window_size() {
+            return Err(Error::InvalidConfig(
+                "Buffer size N must equal 2^window_bits",
+            ));
+        }
+        let window = self.window.window_mut();
+        window.fill(0);
+        init_window(window, &config, dictionary)?;
+        self.inner = unsafe { core::mem::zeroed() };
+        self.consumed = 0;
+        self.finished = false;
         let c_config = config.to_c_config();
-        let result = unsafe {
-            tamp_compressor_init(
-                &mut compressor.inner,
-                &c_config,
-                compressor.window.as_mut_ptr(),
-            )
-        };
+        let result =
+            unsafe { tamp_compressor_init(&mut self.inner, &c_config, window.as_mut_ptr()) };
+        Error::from_tamp_res(result)
+    }
 
-        Error::from_tamp_res(result)?;
-        Ok(compressor)
+    /// Reinitializes the compressor in place for a new stream, discarding any buffered input.
+    /// Avoids building a second window on the stack as `new()` does.
+    pub fn reset(&mut self, config: Config) -> Result<(), Error> {
+        self.init(config, None)
+    }
+
+    /// Drops the compressor without the `drop-check` complaint about unflushed data, for
+    /// streams abandoned on purpose.
+    pub fn discard(mut self) {
+        self.finished = true;
+    }
+
+    /// Returns true if input was compressed but not flushed out of the compressor yet.
+    #[cfg(all(feature = "drop-check", debug_assertions))]
+    fn has_unflushed(&self) -> bool {
+        self.consumed > 0
+            && !self.finished
+            && (self.pending_input_bytes() > 0 || self.pending_output_bits() > 0)
     }
 
     /// Compresses input data into output buffer.
-    /// Returns (input_consumed, output_written). May not consume all input if output is full.
-    /// Call repeatedly until all input is consumed.
+    /// Returns (input_consumed, output_written). May not consume all input if output is full;
+    /// a full `output` is not an error and everything written to it counts, so call
+    /// repeatedly with fresh room until all input is consumed.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
     pub fn compress_chunk(
         &mut self,
         input: &[u8],
         output: &mut [u8],
     ) -> Result<(usize, usize), Error> {
+        // SAFETY: the C side only writes initialized bytes to `output`
+        let output = unsafe { &mut *(output as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        let (consumed, written) = self.compress_chunk_uninit(input, output)?;
+        Ok((consumed, written.len()))
+    }
+
+    /// Like `compress_chunk`, but writes into uninitialized memory so large output buffers
+    /// need not be zeroed first. Returns (input_consumed, initialized output prefix).
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn compress_chunk_uninit<'o>(
+        &mut self,
+        input: &[u8],
+        output: &'o mut [MaybeUninit<u8>],
+    ) -> Result<(usize, &'o mut [u8]), Error> {
+        let (input_consumed, output_written, result) = self.compress_raw(input, output);
+        trace_event!("compress_chunk: {} bytes in, {} bytes out", input_consumed, output_written);
+        if result != TAMP_OUTPUT_FULL as tamp_res {
+            Error::from_tamp_res(result)?;
+        }
+        // SAFETY: the first `output_written` bytes were written by the compressor
+        let written = unsafe {
+            core::slice::from_raw_parts_mut(output.as_mut_ptr().cast(), output_written)
+        };
+        Ok((input_consumed, written))
+    }
+
+    /// Sinks and polls in a single C call until `input` is used up or `output` is full.
+    /// Returns (input_consumed, output_written, result); bytes are written even when the
+    /// result is `TAMP_OUTPUT_FULL`.
+    fn compress_raw(
+        &mut self,
+        input: &[u8],
+        output: &mut [MaybeUninit<u8>],
+    ) -> (usize, usize, tamp_res) {
         let mut input_consumed = 0;
         let mut output_written = 0;
 
         let result = unsafe {
             tamp_compressor_compress_cb(
-                &mut self.inner,
-                output.as_mut_ptr(),
+                self.inner(),
+                output.as_mut_ptr().cast(),
                 output.len(),
                 &mut output_written,
                 input.as_ptr(),
@@ -185,28 +306,148 @@ impl<const N: usize> Compressor<N> {
                 core::ptr::null_mut(), // No user data
             )
         };
+        self.consumed += input_consumed as u64;
+        (input_consumed, output_written, result)
+    }
 
-        Error::from_tamp_res(result)?;
-        Ok((input_consumed, output_written))
+    /// Compresses several discontiguous input buffers as one stream, e.g. DMA ping-pong halves.
+    /// Returns (consumed, output_written). Stops early once `output` has fewer than 8 bytes
+    /// free; resume with the unconsumed remainder described by `consumed`.
+    pub fn compress_chunks(
+        &mut self,
+        inputs: &[&[u8]],
+        output: &mut [u8],
+    ) -> Result<(ChunksConsumed, usize), Error> {
+        let mut consumed = ChunksConsumed::default();
+        let mut written = 0;
+        while let Some(input) = inputs.get(consumed.slices) {
+            let rest = &input[consumed.bytes..];
+            if rest.is_empty() {
+                consumed.slices += 1;
+                consumed.bytes = 0;
+                continue;
+            }
+            if output.len() - written < MAX_POLL {
+                break;
+            }
+            let (c, w) = self.compress_chunk(rest, &mut output[written..])?;
+            consumed.bytes += c;
+            written += w;
+            if c == 0 && w == 0 {
+                break;
+            }
+        }
+        Ok((consumed, written))
+    }
+
+    /// Compresses input into a set of output buffers, e.g. per-packet transmit descriptors.
+    /// Buffers are filled completely, front to back. Returns (input_consumed, output_written);
+    /// stops early once fewer than 8 bytes of output space remain.
+    pub fn compress_chunk_vectored(
+        &mut self,
+        input: &[u8],
+        outputs: &mut [&mut [u8]],
+    ) -> Result<(usize, usize), Error> {
+        let space: usize = outputs.iter().map(|output| output.len()).sum();
+        let (mut consumed, mut written) = (0, 0);
+        let mut buf = [0u8; MAX_POLL];
+        while consumed < input.len() {
+            if self.is_full() {
+                if space - written < MAX_POLL {
+                    break;
+                }
+                let n = self.poll(&mut buf)?;
+                scatter(outputs, written, &buf[..n]);
+                written += n;
+            }
+            consumed += self.sink(&input[consumed..]);
+        }
+        Ok((consumed, written))
+    }
+
+    /// Compresses bytes pulled lazily from `input` until it ends or `output` is nearly full.
+    /// Returns (bytes_pulled, output_written). A byte is only pulled once it can be sunk,
+    /// so pass `iter.by_ref()` to resume with the same iterator later.
+    pub fn compress_iter(
+        &mut self,
+        mut input: impl Iterator<Item = u8>,
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.is_full() {
+                if output.len() - written < MAX_POLL {
+                    break;
+                }
+                written += self.poll(&mut output[written..])?;
+                continue;
+            }
+            // Pull only what the input buffer has room for, then sink it in one call
+            let mut staged = [0u8; INPUT_BUFFER];
+            let free = INPUT_BUFFER - self.pending_input_bytes();
+            let n = staged[..free]
+                .iter_mut()
+                .zip(input.by_ref())
+                .map(|(slot, byte)| *slot = byte)
+                .count();
+            if n == 0 {
+                break;
+            }
+            self.sink(&staged[..n]);
+            consumed += n;
+        }
+        Ok((consumed, written))
+    }
+
+    /// Compresses `input` into `sink`, stopping once it has fewer than 8 bytes of room.
+    /// Returns input bytes consumed.
+    pub fn compress_into(&mut self, input: &[u8], sink: &mut impl Sink) -> Result<usize, Error> {
+        // One C call per stage of output rather than a sink() and poll() per 16 input bytes
+        let mut buf = [MaybeUninit::<u8>::uninit(); STAGE];
+        let mut consumed = 0;
+        while consumed < input.len() && sink.remaining() >= MAX_POLL {
+            let room = sink.remaining().min(STAGE);
+            let (c, written) = self.compress_chunk_uninit(&input[consumed..], &mut buf[..room])?;
+            sink.push(written).map_err(|_| Error::OutputFull)?;
+            consumed += c;
+            if c == 0 && written.is_empty() {
+                break;
+            }
+        }
+        Ok(consumed)
+    }
+
+    /// Flushes into `sink`, see `flush()`. Fails with `OutputFull`, leaving the compressor
+    /// untouched, unless the sink has room for 32 bytes.
+    pub fn flush_into(&mut self, sink: &mut impl Sink, write_token: bool) -> Result<(), Error> {
+        if sink.remaining() < MAX_FLUSH {
+            return Err(Error::OutputFull);
+        }
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.flush(&mut buf, write_token)?;
+        sink.push(&buf[..n]).map_err(|_| Error::OutputFull)
     }
 
     /// Low-level: sinks up to 16 bytes into internal buffer.
     /// Use with `poll()` for fine-grained control. Most users should use `compress_chunk()`.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
     pub fn sink(&mut self, input: &[u8]) -> usize {
         let mut consumed = 0;
         unsafe {
-            tamp_compressor_sink(&mut self.inner, input.as_ptr(), input.len(), &mut consumed);
+            tamp_compressor_sink(self.inner(), input.as_ptr(), input.len(), &mut consumed);
         }
+        self.consumed += consumed as u64;
         consumed
     }
 
     /// Low-level: polls internal buffer for compressed data.
     /// Use with `sink()` for fine-grained control. Most users should use `compress_chunk()`.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
     pub fn poll(&mut self, output: &mut [u8]) -> Result<usize, Error> {
         let mut output_written = 0;
         let result = unsafe {
             tamp_compressor_poll(
-                &mut self.inner,
+                self.inner(),
                 output.as_mut_ptr(),
                 output.len(),
                 &mut output_written,
@@ -223,15 +464,84 @@ impl<const N: usize> Compressor<N> {
         unsafe { tamp_compressor_full(&self.inner as *const _ as *mut _) }
     }
 
+    /// Sinks all of `input`, polling into `buf` whenever the internal buffer fills and handing
+    /// each polled piece to `out`. `buf` needs room for 8 bytes. Returns bytes polled.
+    pub(crate) fn sink_all(
+        &mut self,
+        input: &[u8],
+        buf: &mut [u8],
+        out: &mut impl FnMut(&[u8]),
+    ) -> Result<usize, Error> {
+        self.try_sink_all(input, buf, &mut |piece| {
+            out(piece);
+            Ok::<_, Error>(())
+        })
+    }
+
+    /// `sink_all()` for an `out` that can fail, e.g. by writing to flash. Stops at the first
+    /// error of either the compressor or `out`.
+    pub(crate) fn try_sink_all<E: From<Error>>(
+        &mut self,
+        mut input: &[u8],
+        buf: &mut [u8],
+        out: &mut impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<usize, E> {
+        let mut polled = 0;
+        while !input.is_empty() {
+            if self.is_full() {
+                let n = self.poll(buf)?;
+                out(&buf[..n])?;
+                polled += n;
+            }
+            input = &input[self.sink(input)..];
+        }
+        Ok(polled)
+    }
+
+    /// Loads `data` into the match window as if it had just been compressed, without emitting
+    /// any output, so following input can reference it. Unlike a custom dictionary this works
+    /// after construction and can be repeated, e.g. with the last kilobyte of yesterday's log.
+    /// Only the last `N` bytes of `data` are copied, but the window position advances by all
+    /// of it. The decompressor must load the same bytes at the same point of the stream.
+    /// Fails with `InputPending` unless all sunk input was compressed; call it after
+    /// construction or a `flush()`.
+    pub fn prime(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.pending_input_bytes() > 0 {
+            return Err(Error::InputPending);
+        }
+        let pos = self.inner.window_pos() as usize;
+        let pos = window::prime(self.window.window_mut(), pos, data);
+        self.inner.set_window_pos(pos as u32);
+        Ok(())
+    }
+
+    /// Window contents and position, to persist the stream at a flush boundary.
+    pub(crate) fn window_state(&self) -> (&[u8; N], usize) {
+        (self.window.window(), self.inner.window_pos() as usize)
+    }
+
+    /// Input bytes sunk but not yet compressed; `poll()` or `flush()` processes them.
+    pub fn pending_input_bytes(&self) -> usize {
+        self.inner.input_size() as usize
+    }
+
+    /// Compressed bits waiting in the bit buffer for a whole byte before they are written.
+    /// Together with `pending_input_bytes()` this tells whether a `flush()` would emit
+    /// anything; both are 0 right after one.
+    pub fn pending_output_bits(&self) -> usize {
+        self.inner.bit_buffer_pos() as usize
+    }
+
     /// Flushes remaining data from internal buffers.
     /// `write_token`: true to continue using compressor, false for final flush.
     /// Must be called at end of compression to ensure all data is output.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
     pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
         let mut output_written = 0;
 
         let result = unsafe {
             tamp_compressor_flush(
-                &mut self.inner,
+                self.inner(),
                 output.as_mut_ptr(),
                 output.len(),
                 &mut output_written,
@@ -239,8 +549,462 @@ impl<const N: usize> Compressor<N> {
             )
         };
 
+        trace_event!("flush: {} bytes out, final: {}", output_written, !write_token);
         Error::from_tamp_res(result)?;
+        self.finished |= !write_token;
         Ok(output_written)
     }
+
+    /// Compresses all of `input` and performs the final flush.
+    /// Returns bytes written to `output`, or `OutputFull` if it is too small.
+    #[cfg_attr(not(any(feature = "dictionary", feature = "minicbor")), allow(dead_code))]
+    pub(crate) fn compress_all(
+        &mut self,
+        mut input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut written = 0;
+        while !input.is_empty() {
+            let (consumed, n) = self.compress_chunk(input, &mut output[written..])?;
+            if consumed == 0 && n == 0 {
+                return Err(Error::OutputFull);
+            }
+            input = &input[consumed..];
+            written += n;
+        }
+        Ok(written + self.flush(&mut output[written..], false)?)
+    }
+
+    /// Returns the C state with its window pointer refreshed.
+    /// An inline window moves with `self`, so the pointer set at init dangles once it does.
+    fn inner(&mut self) -> &mut TampCompressor {
+        self.inner.window = self.window.window_mut().as_mut_ptr();
+        &mut self.inner
+    }
+}
+
+impl<const N: usize, W: WindowStorage<N>> fmt::Debug for Compressor<N, W> {
+    /// Prints the configuration and progress, not the window contents.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compressor")
+            .field("window_size", &N)
+            .field("literal_bits", &self.inner.conf_literal())
+            .field("consumed", &self.consumed)
+            .field("pending", &self.inner.input_size())
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize, W: WindowStorage<N>> defmt::Format for Compressor<N, W> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Compressor {{ window_size: {=usize}, literal_bits: {=u32}, consumed: {=u64}, \
+             pending: {=u32}, finished: {=bool} }}",
+            N,
+            self.inner.conf_literal(),
+            self.consumed,
+            self.inner.input_size(),
+            self.finished,
+        )
+    }
+}
+
+/// Flags a compressor dropped with buffered data, the usual cause of truncated streams.
+/// Reported with a defmt warning when `defmt` is enabled, by a debug assertion otherwise.
+#[cfg(all(feature = "drop-check", debug_assertions))]
+impl<const N: usize, W: WindowStorage<N>> Drop for Compressor<N, W> {
+    fn drop(&mut self) {
+        // A second panic while unwinding would abort and hide the first one
+        #[cfg(feature = "std")]
+        if std::thread::panicking() {
+            return;
+        }
+        #[cfg(feature = "defmt")]
+        if self.has_unflushed() {
+            defmt::warn!("tamp: {} dropped without a final flush", self);
+        }
+        #[cfg(not(feature = "defmt"))]
+        debug_assert!(
+            !self.has_unflushed(),
+            "{self:?} dropped without a final flush; call flush() or discard()"
+        );
+    }
+}
+
+impl<const N: usize> TryFrom<Config> for Compressor<N> {
+    type Error = Error;
+
+    /// Same as `new()`, for configs coming from parsing or deserialization.
+    fn try_from(config: Config) -> Result<Self, Error> {
+        Self::new(config)
+    }
+}
+
+impl<const N: usize> Default for Compressor<N> {
+    /// Same as `new_default()`.
+    fn default() -> Self {
+        Self::new_default()
+    }
+}
+
+/// Result of `estimate_ratio`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ratio {
+    /// Sample length in bytes.
+    pub uncompressed: usize,
+    /// Compressed length in bytes, header included.
+    pub compressed: usize,
+}
+
+impl Ratio {
+    /// Uncompressed over compressed length, e.g. 2.0 when compression halves the sample.
+    pub fn factor(&self) -> f32 {
+        self.uncompressed as f32 / self.compressed.max(1) as f32
+    }
+
+    /// Returns true if compression saves at least `percent` percent of the sample.
+    /// Integer-only, for targets without an FPU.
+    pub fn saves(&self, percent: u8) -> bool {
+        self.compressed * 100 <= self.uncompressed * (100 - percent.min(100) as usize)
+    }
+}
+
+/// Compresses `sample` with a `Compressor<N>` and reports the ratio, discarding the output.
+///
+/// Only the compressor's state is needed, no output buffer, so devices can cheaply decide
+/// whether compressing a payload is worth the CPU time.
+pub fn estimate_ratio<const N: usize>(sample: &[u8], config: Config) -> Result<Ratio, Error> {
+    let mut compressor = Compressor::<N>::new(config)?;
+    let mut buf = [0u8; MAX_FLUSH];
+    let mut compressed = compressor.sink_all(sample, &mut buf[..MAX_POLL], &mut |_| {})?;
+    compressed += compressor.flush(&mut buf, false)?;
+    Ok(Ratio {
+        uncompressed: sample.len(),
+        compressed,
+    })
 }
 
+/// Copies `bytes` into `outputs` at `offset` bytes past the start of the first buffer.
+fn scatter(outputs: &mut [&mut [u8]], mut offset: usize, mut bytes: &[u8]) {
+    for output in outputs.iter_mut() {
+        if offset >= output.len() {
+            offset -= output.len();
+            continue;
+        }
+        let n = bytes.len().min(output.len() - offset);
+        output[offset..offset + n].copy_from_slice(&bytes[..n]);
+        bytes = &bytes[n..];
+        offset = 0;
+        if bytes.is_empty() {
+            break;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::Decompressor;
+
+    const _: () = assert!(Compressor::<1024>::MEMORY_BYTES > 1024);
+    const _: () = assert!(Decompressor::<256>::MEMORY_BYTES > 256);
+
+    #[test]
+    fn test_memory_bytes() {
+        assert_eq!(Compressor::<256>::MEMORY_BYTES, core::mem::size_of::<Compressor<256>>());
+        assert_eq!(
+            Compressor::<2048>::MEMORY_BYTES - Compressor::<1024>::MEMORY_BYTES,
+            1024
+        );
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_chunk_output_full() {
+        let noise: [u8; 600] =
+            core::array::from_fn(|i| ((i as u32).wrapping_mul(2654435761) >> 13) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 1024];
+        let (mut consumed, mut len) = (0, 0);
+        while consumed < noise.len() {
+            let (c, n) = compressor
+                .compress_chunk(&noise[consumed..], &mut stream[len..len + 10])
+                .unwrap();
+            consumed += c;
+            len += n;
+        }
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 600];
+        decompressor
+            .decompress_exact(&mut &stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(output, noise);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_chunks_resumes() {
+        let a = b"ping-pong ping-pong ".as_slice();
+        let b = b"ping-pong halves".as_slice();
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = [0u8; 64];
+        let mut len = 0;
+        let (mut first, mut second) = (a, b);
+        loop {
+            let end = (len + 9).min(compressed.len());
+            let (consumed, written) = compressor
+                .compress_chunks(&[first, second], &mut compressed[len..end])
+                .unwrap();
+            len += written;
+            if consumed.slices == 2 {
+                break;
+            }
+            if consumed.slices == 1 {
+                (first, second) = (&second[consumed.bytes..], &[]);
+            } else {
+                first = &first[consumed.bytes..];
+            }
+        }
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor
+            .decompress_chunk(&compressed[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], b"ping-pong ping-pong ping-pong halves");
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_iter() {
+        let mut samples = (0..200u16).flat_map(|i| (i % 20).to_le_bytes());
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = [0u8; 400];
+        let (mut pulled, mut len) = (0, 0);
+        loop {
+            let end = (len + 16).min(compressed.len());
+            let (n, w) = compressor
+                .compress_iter(samples.by_ref(), &mut compressed[len..end])
+                .unwrap();
+            (pulled, len) = (pulled + n, len + w);
+            if n == 0 && w == 0 {
+                break;
+            }
+        }
+        assert_eq!(pulled, 400);
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 400];
+        let (_, n) = decompressor
+            .decompress_chunk(&compressed[header..len], &mut output)
+            .unwrap();
+        let expected = (0..200u16).flat_map(|i| (i % 20).to_le_bytes());
+        assert!(output[..n].iter().copied().eq(expected));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_new_boxed() {
+        let config = Config::new().window_bits(15).unwrap();
+        let mut compressor = Compressor::<32768>::new_boxed(config.clone()).unwrap();
+        let mut decompressor = Decompressor::<32768>::new_boxed(config).unwrap();
+        let input = b"boxed boxed boxed boxed boxed";
+        let mut stream = [0u8; 64];
+        let (_, mut len) = compressor.compress_chunk(input, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor.decompress_chunk(&stream[1..len], &mut output).unwrap();
+        assert_eq!(&output[..n], input);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_new_static() {
+        extern crate std;
+        use std::boxed::Box;
+
+        let window: &'static mut [u8; 256] = Box::leak(Box::new([0xAA; 256]));
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = Compressor::new_static(config.clone(), window).unwrap();
+        assert!(core::mem::size_of_val(&compressor) < 128);
+        let input = b"static window static window";
+        let mut stream = [0u8; 64];
+        let (_, mut len) = compressor.compress_chunk(input, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let mut inline = Compressor::<256>::new(config).unwrap();
+        let mut expected = [0u8; 64];
+        assert_eq!(inline.compress_all(input, &mut expected).unwrap(), len);
+        assert_eq!(stream[..len], expected[..len]);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_into_sink() {
+        use crate::SliceSink;
+
+        let input = b"sink sink sink sink sink sink sink sink";
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 64];
+        let mut sink = SliceSink::new(&mut stream);
+        assert_eq!(compressor.compress_into(input, &mut sink).unwrap(), input.len());
+        compressor.flush_into(&mut sink, false).unwrap();
+        let compressed = sink.written();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(compressed).unwrap();
+        let mut buf = [0u8; 64];
+        let mut output = SliceSink::new(&mut buf);
+        let consumed = decompressor
+            .decompress_into(&compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(consumed, compressed.len() - header);
+        assert_eq!(output.written(), input);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_vectored_output() {
+        let input = b"telemetry frame 1; telemetry frame 2; telemetry frame 3";
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut packets = [[0u8; 5]; 16];
+        let mut outputs: [&mut [u8]; 16] = packets.each_mut().map(|p| p.as_mut_slice());
+        let (consumed, written) = compressor
+            .compress_chunk_vectored(input, &mut outputs)
+            .unwrap();
+        assert_eq!(consumed, input.len());
+        let mut compressed = packets.concat();
+        let len = written + compressor.flush(&mut compressed[written..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut lines = [[0u8; 20]; 3];
+        let mut outputs: [&mut [u8]; 3] = lines.each_mut().map(|l| l.as_mut_slice());
+        let (_, n) = decompressor
+            .decompress_chunk_vectored(&compressed[header..len], &mut outputs)
+            .unwrap();
+        assert_eq!(n, input.len());
+        assert_eq!(&lines.concat()[..n], input);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_estimate_ratio() {
+        let text: [u8; 2000] = core::array::from_fn(|i| b"temperature=21.5;"[i % 17]);
+        let config = Config::new().window_bits(8).unwrap();
+        let ratio = estimate_ratio::<256>(&text, config.clone()).unwrap();
+        let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+        let mut output = [0u8; 2048];
+        let len = compressor.compress_all(&text, &mut output).unwrap();
+        assert_eq!(ratio.compressed, len);
+        assert!(ratio.factor() > 4.0);
+        assert!(ratio.saves(75));
+
+        let noise: [u8; 2000] = core::array::from_fn(|i| (i * 7919 % 251) as u8 ^ (i >> 3) as u8);
+        assert!(!estimate_ratio::<256>(&noise, config).unwrap().saves(10));
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_fork() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 256];
+        let (_, mut len) = compressor.compress_chunk(b"status=ok;", &mut stream).unwrap();
+
+        // Trial-compress a payload too random to fit a 16 byte packet, then drop the fork
+        let candidate: [u8; 64] = core::array::from_fn(|i| (i * 7919 % 251) as u8);
+        let mut trial = compressor.fork();
+        let mut packet = [0u8; 64];
+        let (_, mut n) = trial.compress_chunk(&candidate, &mut packet).unwrap();
+        n += trial.flush(&mut packet[n..], true).unwrap();
+        assert!(n > 16);
+        trial.discard();
+
+        let (_, n) = compressor.compress_chunk(b"status=ok;", &mut stream[len..]).unwrap();
+        len += n;
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 32];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], b"status=ok;status=ok;");
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_pending() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut output = [0u8; 64];
+        assert_eq!(compressor.sink(b"abc"), 3);
+        assert_eq!(compressor.pending_input_bytes(), 3);
+        compressor.flush(&mut output, true).unwrap();
+        assert_eq!(compressor.pending_input_bytes(), 0);
+        assert_eq!(compressor.pending_output_bits(), 0);
+
+        // A poll compresses whole tokens, usually leaving a partial byte behind
+        assert_eq!(compressor.sink(b"pending output?!"), 16);
+        compressor.poll(&mut output).unwrap();
+        assert!(compressor.pending_input_bytes() < 16);
+        assert!(compressor.pending_output_bits() < 8);
+        compressor.discard();
+    }
+
+    #[cfg(all(feature = "drop-check", debug_assertions, not(feature = "defmt")))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    #[should_panic(expected = "dropped without a final flush")]
+    fn test_drop_check() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        compressor.sink(b"lost tail");
+        drop(compressor);
+    }
+
+    #[cfg(feature = "dictionary")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_prime() {
+        let yesterday: [u8; 256] = core::array::from_fn(|i| b"t=21.5,rh=40,p=1013;"[i % 20]);
+        let today = b"t=21.5,rh=41,p=1013;t=21.5,rh=40,p=1012;";
+        let config = Config::new().window_bits(8).unwrap();
+        let compress = |prime: bool, stream: &mut [u8]| {
+            let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+            if prime {
+                compressor.prime(&yesterday).unwrap();
+            }
+            let (_, len) = compressor.compress_chunk(today, stream).unwrap();
+            len + compressor.flush(&mut stream[len..], false).unwrap()
+        };
+        let mut plain = [0u8; 64];
+        let mut primed = [0u8; 64];
+        let plain_len = compress(false, &mut plain);
+        let len = compress(true, &mut primed);
+        assert!(len < plain_len);
+
+        // Priming a fresh compressor with a whole window equals a custom dictionary
+        let (_, header) = Config::from_header(&primed).unwrap();
+        let dictionary_config = config.clone().custom_dictionary(true);
+        let mut decompressor =
+            Decompressor::<256>::with_dictionary(dictionary_config, Some(&yesterday)).unwrap();
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor
+            .decompress_chunk(&primed[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], today);
+
+        let mut compressor = Compressor::<256>::new(config).unwrap();
+        compressor.sink(b"x");
+        assert!(matches!(
+            compressor.prime(&yesterday),
+            Err(Error::InputPending)
+        ));
+        compressor.discard();
+    }
+}
@@ -0,0 +1,213 @@
+use tamp_sys::TampConf;
+use crate::Error;
+
+/// Configuration for tamp compression/decompression.
+///
+/// Default configuration uses 10-bit window (1KB), 8-bit literals, lazy matching disabled.
+#[derive(Clone)]
+pub struct Config {
+    /// Window size in bits (8-15). Window size = 2^window_bits bytes. Default: 10 (1KB).
+    pub window_bits: u8,
+    /// Literal size in bits (5-8). Default: 8.
+    pub literal_bits: u8,
+    /// Enable lazy matching for better compression at cost of ~50% more CPU. Default: false.
+    /// Ignored where the C library is built without it, which is the default on `thumbv`
+    /// targets (see `tamp_sys::LAZY_MATCHING`), and by the pure-Rust compressor.
+    pub lazy_matching: bool,
+    /// Use custom dictionary initialization. Default: false.
+    pub use_custom_dictionary: bool,
+    /// Window search of the pure-Rust compressor; the C compressor ignores it.
+    /// Default: `Search::Exhaustive`.
+    pub search: Search,
+}
+
+/// How the pure-Rust compressor (`rust-backend` feature) searches the window for matches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Search {
+    /// Compare against every window position: best ratio, slowest.
+    #[default]
+    Exhaustive,
+    /// Follow hash chains of earlier positions with the same two leading bytes. Several
+    /// times faster for slightly worse ratio, at the cost of `2 * N + 2048` bytes of RAM.
+    HashChain,
+    /// Compare only against the most recent position with the same two leading bytes.
+    /// The least work per byte, at some cost in ratio that depends on the data.
+    /// See `Config::fast()`.
+    Fast,
+}
+
+impl Default for Config {
+    /// Creates default configuration: 10-bit window (1KB), 8-bit literals, lazy matching disabled.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// Creates a new configuration with default settings.
+    ///
+    /// This and the builder methods are `const fn`, so a configuration can live in a
+    /// `static` or `const` shared by compressor and decompressor declarations.
+    pub const fn new() -> Self {
+        Self {
+            window_bits: 10, // 1KB window
+            literal_bits: 8,
+            lazy_matching: false,
+            use_custom_dictionary: false,
+            search: Search::Exhaustive,
+        }
+    }
+
+    /// Creates a default configuration with the given window and literal sizes, panicking if
+    /// either is out of range. Evaluated in a `const` or `static`, the panic is a compile
+    /// error, so this is the way to get a validated configuration without `Result`:
+    ///
+    /// ```
+    /// # use tamp::Config;
+    /// static CONFIG: Config = Config::with_bits(12, 7).lazy_matching(true);
+    /// ```
+    pub const fn with_bits(window_bits: u8, literal_bits: u8) -> Self {
+        match Self::new().window_bits(window_bits) {
+            Ok(config) => match config.literal_bits(literal_bits) {
+                Ok(config) => config,
+                Err(_) => panic!("Literal bits must be 5-8"),
+            },
+            Err(_) => panic!("Window bits must be 8-15"),
+        }
+    }
+
+    /// Configuration for the least search effort of `RustCompressor`: lazy matching off and
+    /// `Search::Fast`, whose `SingleProbe` finder needs only 512 bytes beside the window.
+    /// The C compressor has no search setting, so this is only available with `rust-backend`.
+    ///
+    /// `cargo bench -p tamp --features rust-backend -- match_finder` prints ratio and speed
+    /// of each search on the Canterbury corpus.
+    #[cfg(feature = "rust-backend")]
+    pub const fn fast() -> Self {
+        Self::new().lazy_matching(false).search(Search::Fast)
+    }
+
+    /// Sets window size in bits (8-15). Window size = 2^bits bytes.
+    /// Larger windows provide better compression but use more memory.
+    pub const fn window_bits(mut self, bits: u8) -> Result<Self, Error> {
+        if !matches!(bits, 8..=15) {
+            return Err(Error::InvalidConfig("Window bits must be 8-15"));
+        }
+        self.window_bits = bits;
+        Ok(self)
+    }
+
+    /// Sets literal size in bits (5-8). More bits = larger alphabet but less compression.
+    pub const fn literal_bits(mut self, bits: u8) -> Result<Self, Error> {
+        if !matches!(bits, 5..=8) {
+            return Err(Error::InvalidConfig("Literal bits must be 5-8"));
+        }
+        self.literal_bits = bits;
+        Ok(self)
+    }
+
+    /// Enables lazy matching. Improves compression ~0.5-2% at cost of ~50% more CPU.
+    pub const fn lazy_matching(mut self, enabled: bool) -> Self {
+        self.lazy_matching = enabled;
+        self
+    }
+
+    /// Sets the window search of the pure-Rust compressor.
+    pub const fn search(mut self, search: Search) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Enables custom dictionary initialization. Dictionary must be provided during construction.
+    pub const fn custom_dictionary(mut self, enabled: bool) -> Self {
+        self.use_custom_dictionary = enabled;
+        self
+    }
+
+    pub(crate) fn to_c_config(&self) -> TampConf {
+        let mut conf = TampConf {
+            _bitfield_align_1: [],
+            _bitfield_1: Default::default(),
+        };
+        conf.set_window(self.window_bits as u16);
+        conf.set_literal(self.literal_bits as u16);
+        conf.set_use_custom_dictionary(self.use_custom_dictionary as u16);
+        tamp_sys::tamp_conf_set_lazy_matching(&mut conf, self.lazy_matching);
+        conf
+    }
+
+    /// Returns window size in bytes (2^window_bits).
+    pub const fn window_size(&self) -> usize {
+        1usize << self.window_bits
+    }
+
+    /// Shortest match the stream format can encode: 3 for large windows with small literals,
+    /// where a 2 byte match would not be shorter than the literals, 2 otherwise.
+    pub const fn min_match_len(&self) -> usize {
+        2 + (self.window_bits > 10 + ((self.literal_bits - 5) << 1)) as usize
+    }
+
+    /// Longest match the stream format can encode.
+    pub const fn max_match_len(&self) -> usize {
+        self.min_match_len() + 13
+    }
+
+    /// Upper bound on the compressed size of `len` input bytes, header included.
+    /// Matches are only emitted when shorter than literals, so the worst case is every byte
+    /// sent as a flag bit plus a literal, padded to a byte by the final flush.
+    pub const fn max_compressed_size(&self, len: usize) -> usize {
+        1 + (len * (1 + self.literal_bits as usize)).div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARED: Config = Config::with_bits(12, 7).custom_dictionary(true);
+    const _: () = assert!(
+        SHARED.window_size() == 4096 && SHARED.min_match_len() == 2 && SHARED.use_custom_dictionary
+    );
+
+    #[test]
+    fn test_const_config() {
+        #[cfg(feature = "rust-backend")]
+        {
+            static FAST: Config = Config::fast();
+            assert_eq!(FAST.search, Search::Fast);
+        }
+        assert_eq!(SHARED.literal_bits, 7);
+        assert!(Config::new().window_bits(16).is_err());
+    }
+
+    #[cfg(all(feature = "compressor", feature = "decompressor"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_lazy_matching_reaches_c() {
+        use crate::{Compressor, Decompressor};
+        // Greedy takes "abc", lazy emits 'a' and takes the longer "bcdefghij" after it
+        let input = b"bcdefghij abc abcdefghij";
+        let compress = |lazy| {
+            let config = Config::new().window_bits(8).unwrap().lazy_matching(lazy);
+            let mut stream = [0u8; 64];
+            let len = Compressor::<256>::new(config)
+                .unwrap()
+                .compress_all(input, &mut stream)
+                .unwrap();
+            let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+            let mut output = [0u8; 24];
+            decompressor
+                .decompress_exact(&mut &stream[header..len], &mut output)
+                .unwrap();
+            assert_eq!(&output, input);
+            (stream, len)
+        };
+        assert_eq!(compress(true) != compress(false), tamp_sys::LAZY_MATCHING);
+    }
+
+    #[test]
+    #[should_panic(expected = "Literal bits must be 5-8")]
+    fn test_with_bits_out_of_range() {
+        let _ = Config::with_bits(10, 9);
+    }
+}
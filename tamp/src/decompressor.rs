@@ -1,73 +1,539 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::fmt;
 use core::marker::PhantomData;
-use heapless::Vec;
+use core::mem::MaybeUninit;
 use tamp_sys::{
     TAMP_INPUT_EXHAUSTED, TAMP_OK, TAMP_OUTPUT_FULL, TampConf, TampDecompressor,
     tamp_decompressor_decompress_cb, tamp_decompressor_init, tamp_decompressor_read_header, tamp_res,
 };
+use crate::sink::Sink;
+use crate::window::{self, WindowStorage};
 use crate::{Error, Config};
 
-/// Streaming decompressor with heapless window buffer.
+/// Compressed bytes `Decompressor::sink` buffers.
+const SINK_BUFFER: usize = 16;
+
+/// Streaming decompressor with an inline window buffer.
 ///
 /// `N` is the window buffer size in bytes and must equal 2^window_bits.
 /// Use type aliases like `Decompressor1K` for convenience.
 ///
-/// Memory usage: ~N + 32 bytes (window + struct overhead).
-pub struct Decompressor<const N: usize> {
+/// Memory usage: ~N + 48 bytes (window + struct overhead); see `MEMORY_BYTES` for the exact figure.
+/// `W` holds the window; besides the default inline array it can be any `WindowStorage`,
+/// e.g. a `&'static mut` window placed in another memory, see `with_storage()`.
+pub struct Decompressor<const N: usize, W: WindowStorage<N> = [u8; N]> {
     inner: TampDecompressor,
-    window: Vec<u8, N>,
+    window: W,
+    input: [u8; SINK_BUFFER],
+    input_len: u8,
+    /// Output bytes produced since creation or `reset()`.
+    produced: u64,
     _marker: PhantomData<*mut ()>,
 }
 
 impl<const N: usize> Decompressor<N> {
+    /// Exact RAM footprint of a `Decompressor<N>` in bytes: the window, the C state, the
+    /// `sink()` buffer and the `Debug` counter.
+    ///
+    /// Usable in const context to check a RAM budget at compile time, e.g.
+    /// `const _: () = assert!(Decompressor::<1024>::MEMORY_BYTES <= 1200);`
+    pub const MEMORY_BYTES: usize = core::mem::size_of::<Self>();
+
+    /// Creates a decompressor with the default configuration and the window size implied by
+    /// `N`, e.g. `Decompressor1K::new_default()`, for streams from a default compressor with
+    /// the header already consumed. Fails to compile unless `N` is a power of two from 256
+    /// to 32768.
+    pub fn new_default() -> Self {
+        const {
+            assert!(N.is_power_of_two() && N >= 256 && N <= 32768, "Invalid window size N")
+        };
+        let config = Config::new()
+            .window_bits(N.trailing_zeros() as u8)
+            .expect("N checked at compile time");
+        Self::new(config).expect("default configuration is valid")
+    }
+
     /// Creates a new decompressor with the given configuration.
     /// Buffer size N must equal 2^config.window_bits.
     pub fn new(config: Config) -> Result<Self, Error> {
-        Self::with_dictionary(config, None)
+        Self::with_storage(config, [0u8; N])
     }
 
     /// Creates a decompressor with optional dictionary initialization.
     /// Dictionary must match the one used during compression.
+    #[cfg(feature = "dictionary")]
     pub fn with_dictionary(config: Config, dictionary: Option<&[u8]>) -> Result<Self, Error> {
+        Self::with_storage_dictionary(config, [0u8; N], dictionary)
+    }
+
+    /// Creates decompressor by reading configuration from compressed stream header.
+    /// Returns (decompressor, bytes_consumed_from_input).
+    /// Buffer size N must match the window size found in header.
+    pub fn from_header(input: &[u8]) -> Result<(Self, usize), Error> {
+        let (config, input_consumed) = Config::from_header(input)?;
+
         let expected_size = config.window_size();
         if N != expected_size {
+            return Err(Error::InvalidConfig("Buffer size N doesn't match header"));
+        }
+
+        let decompressor = Self::new(config)?;
+        Ok((decompressor, input_consumed))
+    }
+
+    /// Creates a decompressor on the heap, building the window in place. Unlike
+    /// `Box::new(Decompressor::new(config)?)`, it never passes through the stack, so large
+    /// windows fit small stacks.
+    #[cfg(feature = "alloc")]
+    pub fn new_boxed(config: Config) -> Result<Box<Self>, Error> {
+        // SAFETY: every field is valid when zeroed: null C pointers, empty buffers, zero
+        // counters. `reset()` then initializes the C state against the boxed window.
+        let mut decompressor = unsafe { Box::<Self>::new_zeroed().assume_init() };
+        decompressor.reset(config)?;
+        Ok(decompressor)
+    }
+}
+
+impl<const N: usize, W: WindowStorage<N>> Decompressor<N, W> {
+    /// Creates a decompressor keeping its window in `storage`, which is cleared first.
+    pub fn with_storage(config: Config, storage: W) -> Result<Self, Error> {
+        Self::with_storage_dictionary(config, storage, None)
+    }
+
+    /// `with_storage()` with optional dictionary initialization, as in `with_dictionary()`.
+    pub(crate) fn with_storage_dictionary(
+        config: Config,
+        storage: W,
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let mut decompressor = Self::from_window(storage);
+        decompressor.init(config, dictionary)?;
+        Ok(decompressor)
+    }
+
+    /// Wraps `window` with zeroed, not yet initialized C state.
+    fn from_window(window: W) -> Self {
+        Self {
+            inner: unsafe { core::mem::zeroed() },
+            window,
+            input: [0; SINK_BUFFER],
+            input_len: 0,
+            produced: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Seeds the window and initializes the C state for a new stream.
+    fn init(&mut self, config: Config, dictionary: Option<&[u8]>) -> Result<(), Error> {
+        if N != config.window_size() {
             return Err(Error::InvalidConfig(
                 "Buffer size N must equal 2^window_bits",
             ));
         }
-
-        let mut window = Vec::new();
-        window.resize(N, 0).map_err(|_| Error::BufferTooSmall)?;
-
-        // Initialize dictionary if provided
+        let window = self.window.window_mut();
+        window.fill(0);
+        #[cfg(feature = "dictionary")]
         if let Some(dict) = dictionary
             && config.use_custom_dictionary
         {
             let copy_len = dict.len().min(N);
             window[..copy_len].copy_from_slice(&dict[..copy_len]);
         }
+        #[cfg(not(feature = "dictionary"))]
+        if config.use_custom_dictionary || dictionary.is_some() {
+            return Err(Error::InvalidConfig("Dictionaries need the `dictionary` feature"));
+        }
+        self.input_len = 0;
+        self.produced = 0;
+        self.inner = unsafe { core::mem::zeroed() };
+        let c_config = config.to_c_config();
+        let result =
+            unsafe { tamp_decompressor_init(&mut self.inner, &c_config, window.as_mut_ptr()) };
+        Error::from_tamp_res(result)
+    }
 
-        let mut decompressor = Self {
-            inner: unsafe { core::mem::zeroed() },
-            window,
-            _marker: PhantomData,
+    /// Reinitializes the decompressor in place for a new stream, discarding buffered input.
+    /// Avoids building a second window on the stack as `new()` does.
+    pub fn reset(&mut self, config: Config) -> Result<(), Error> {
+        self.init(config, None)
+    }
+
+    /// Decompresses input data into output buffer.
+    /// Returns (input_consumed, output_written). May not consume all input or fill all output.
+    /// Call repeatedly until input is exhausted or output is filled.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn decompress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        // SAFETY: the C side only writes initialized bytes to `output`
+        let output = unsafe { &mut *(output as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        let (consumed, written) = self.decompress_chunk_uninit(input, output)?;
+        Ok((consumed, written.len()))
+    }
+
+    /// Like `decompress_chunk`, but writes into uninitialized memory so large output buffers
+    /// need not be zeroed first. Returns (input_consumed, initialized output prefix).
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn decompress_chunk_uninit<'o>(
+        &mut self,
+        input: &[u8],
+        output: &'o mut [MaybeUninit<u8>],
+    ) -> Result<(usize, &'o mut [u8]), Error> {
+        let mut input_consumed = 0;
+        let mut output_written = 0;
+
+        let result = unsafe {
+            tamp_decompressor_decompress_cb(
+                self.inner(),
+                output.as_mut_ptr().cast(),
+                output.len(),
+                &mut output_written,
+                input.as_ptr(),
+                input.len(),
+                &mut input_consumed,
+                None,                  // No callback
+                core::ptr::null_mut(), // No user data
+            )
         };
 
-        let c_config = config.to_c_config();
+        // For decompressor, INPUT_EXHAUSTED and OUTPUT_FULL are normal conditions
+        match result {
+            x if x == TAMP_OK as tamp_res
+                || x == TAMP_OUTPUT_FULL as tamp_res
+                || x == TAMP_INPUT_EXHAUSTED as tamp_res => {}
+            _ => Error::from_tamp_res(result)?,
+        }
+        self.produced += output_written as u64;
+        trace_event!("decompress_chunk: {} bytes in, {} bytes out", input_consumed, output_written);
+        // SAFETY: the first `output_written` bytes were written by the decompressor
+        let written = unsafe {
+            core::slice::from_raw_parts_mut(output.as_mut_ptr().cast(), output_written)
+        };
+        Ok((input_consumed, written))
+    }
+
+    /// Fills all of `output`, advancing the `input` cursor past the consumed bytes.
+    ///
+    /// Returns `InputExhausted` if the stream ends or `input` runs out first; the bytes
+    /// decoded so far are left in `output` and the cursor is still advanced. Errors from
+    /// corrupt data are passed through as they are.
+    pub fn decompress_exact(&mut self, input: &mut &[u8], output: &mut [u8]) -> Result<(), Error> {
+        let mut filled = 0;
+        while filled < output.len() {
+            let (consumed, written) = self.decompress_chunk(input, &mut output[filled..])?;
+            *input = &input[consumed..];
+            filled += written;
+            if consumed == 0 && written == 0 {
+                return Err(Error::InputExhausted);
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances the decompressed stream by up to `n` bytes without returning them.
+    /// Returns (input_consumed, bytes_skipped); fewer than `n` bytes are skipped only when
+    /// `input` runs out. The window is still updated, through a small stack buffer.
+    pub fn skip(&mut self, mut input: &[u8], n: usize) -> Result<(usize, usize), Error> {
+        let mut scratch = [MaybeUninit::<u8>::uninit(); 64];
+        let (mut consumed, mut skipped) = (0, 0);
+        while skipped < n {
+            let room = (n - skipped).min(scratch.len());
+            let (c, w) = self.decompress_chunk_uninit(input, &mut scratch[..room])?;
+            let w = w.len();
+            input = &input[c..];
+            consumed += c;
+            skipped += w;
+            if c == 0 && w == 0 {
+                break;
+            }
+        }
+        Ok((consumed, skipped))
+    }
+
+    /// Decodes all of `input` without storing the output, returning the decompressed length
+    /// it yields. For streamed uploads call it per chunk and sum, e.g. to validate and size
+    /// a payload before allocating storage for it.
+    pub fn measure(&mut self, input: &[u8]) -> Result<u64, Error> {
+        let (_, len) = self.skip(input, usize::MAX)?;
+        Ok(len as u64)
+    }
+
+    /// Loads plaintext the receiver already has into the window, as if it had just been
+    /// decompressed, so decoding can resume from a sync point such as a `flush()` boundary.
+    /// Mirrors `Compressor::prime`: only the last `N` bytes of `data` are copied, but the
+    /// window position advances by all of it. On a fresh decompressor pass the plaintext
+    /// before the sync point, or at least its last `N + total % N` bytes for `total` bytes.
+    /// Fails with `InputPending` while compressed input is buffered or a token is partially
+    /// decoded.
+    pub fn prime_window(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.input_len > 0 || self.inner.bit_buffer_pos() > 0 {
+            return Err(Error::InputPending);
+        }
+        let pos = self.inner.window_pos() as usize;
+        let pos = window::prime(self.window.window_mut(), pos, data);
+        self.inner.set_window_pos(pos as u32);
+        Ok(())
+    }
+
+    /// Low-level: sinks up to 16 bytes of compressed input into an internal buffer.
+    /// Use with `poll()` for fine-grained control, e.g. one byte per receive interrupt.
+    /// Most users should use `decompress_chunk()`; don't mix the two while input is buffered.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn sink(&mut self, input: &[u8]) -> usize {
+        let len = self.input_len as usize;
+        let free = self.input.get_mut(len..).unwrap_or_default();
+        let n = free.len().min(input.len());
+        for (dst, src) in free.iter_mut().zip(input) {
+            *dst = *src;
+        }
+        self.input_len = (len + n) as u8;
+        n
+    }
+
+    /// Low-level: decodes buffered input into `output`, returning the bytes written.
+    /// Returns 0 once more input is needed. Use with `sink()`.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    pub fn poll(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        let input = self.input;
+        let buffered = input.get(..self.input_len as usize).unwrap_or_default();
+        let (consumed, written) = self.decompress_chunk(buffered, output)?;
+        // Move what is left to the front
+        let rest = buffered.get(consumed..).unwrap_or_default();
+        for (dst, src) in self.input.iter_mut().zip(rest) {
+            *dst = *src;
+        }
+        self.input_len = rest.len() as u8;
+        Ok(written)
+    }
+
+    /// Returns true if the internal input buffer is full (16 bytes).
+    /// When full, call `poll()` to decode buffered data.
+    pub fn is_full(&self) -> bool {
+        self.input_len as usize == SINK_BUFFER
+    }
+
+    /// Decompresses `input` into `sink` until the input is exhausted or the sink is full.
+    /// Returns input bytes consumed.
+    pub fn decompress_into(&mut self, input: &[u8], sink: &mut impl Sink) -> Result<usize, Error> {
+        // Staged through uninitialized memory, so no stack buffer is zeroed per call
+        let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+        let mut consumed = 0;
+        loop {
+            let room = sink.remaining().min(buf.len());
+            let (c, written) = self.decompress_chunk_uninit(&input[consumed..], &mut buf[..room])?;
+            consumed += c;
+            sink.push(written).map_err(|_| Error::OutputFull)?;
+            if c == 0 && written.is_empty() {
+                return Ok(consumed);
+            }
+        }
+    }
+
+    /// Decompresses input into a set of output buffers, filled completely, front to back.
+    /// Returns (input_consumed, output_written).
+    pub fn decompress_chunk_vectored(
+        &mut self,
+        input: &[u8],
+        outputs: &mut [&mut [u8]],
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        for output in outputs.iter_mut() {
+            let mut filled = 0;
+            while filled < output.len() {
+                let (c, w) = self.decompress_chunk(&input[consumed..], &mut output[filled..])?;
+                consumed += c;
+                filled += w;
+                if c == 0 && w == 0 {
+                    return Ok((consumed, written + filled));
+                }
+            }
+            written += filled;
+        }
+        Ok((consumed, written))
+    }
+
+    /// Decompresses all of `input` (header already consumed) into `output`.
+    /// Returns bytes written, or `OutputFull` if input remains once `output` is full.
+    #[cfg(any(feature = "postcard", feature = "minicbor"))]
+    pub(crate) fn decompress_all(
+        &mut self,
+        mut input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut written = 0;
+        loop {
+            let (consumed, n) = self.decompress_chunk(input, &mut output[written..])?;
+            input = &input[consumed..];
+            written += n;
+            if consumed == 0 && n == 0 {
+                break;
+            }
+        }
+        if !input.is_empty() {
+            return Err(Error::OutputFull);
+        }
+        Ok(written)
+    }
+
+    /// Returns the C state with its window pointer refreshed.
+    /// An inline window moves with `self`, so the pointer set at init dangles once it does.
+    fn inner(&mut self) -> &mut TampDecompressor {
+        self.inner.window = self.window.window_mut().as_mut_ptr();
+        &mut self.inner
+    }
+}
+
+impl<const N: usize, W: WindowStorage<N>> fmt::Debug for Decompressor<N, W> {
+    /// Prints the configuration and progress, not the window contents.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decompressor")
+            .field("window_size", &N)
+            .field("literal_bits", &self.inner.conf_literal())
+            .field("produced", &self.produced)
+            .field("buffered", &self.input_len)
+            .field("pending_bits", &self.inner.bit_buffer_pos())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize, W: WindowStorage<N>> defmt::Format for Decompressor<N, W> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Decompressor {{ window_size: {=usize}, literal_bits: {=u32}, produced: {=u64}, \
+             buffered: {=u8}, pending_bits: {=u32} }}",
+            N,
+            self.inner.conf_literal(),
+            self.produced,
+            self.input_len,
+            self.inner.bit_buffer_pos(),
+        )
+    }
+}
+
+impl<const N: usize> TryFrom<Config> for Decompressor<N> {
+    type Error = Error;
+
+    /// Same as `new()`, for configs coming from parsing or deserialization.
+    fn try_from(config: Config) -> Result<Self, Error> {
+        Self::new(config)
+    }
+}
+
+impl<const N: usize> Default for Decompressor<N> {
+    /// Same as `new_default()`.
+    fn default() -> Self {
+        Self::new_default()
+    }
+}
+
+/// Output bytes requested per call in `decompress_zero_copy`.
+const ZERO_COPY_STEP: usize = 16;
+
+/// Decompresses a complete stream using `output` itself as the history window.
+///
+/// No internal window is allocated: the decoder's window is pointed at successive
+/// window-sized blocks of `output`, each seeded with a copy of the previous block as history,
+/// and the decoded bytes are the ones it writes there. This halves RAM use for one-shot asset
+/// decompression at the cost of one block copy per window of output.
+/// `output` must be at least the decompressed length rounded up to the window size.
+/// Custom dictionaries are not supported. Returns the decompressed length; `output` contents
+/// past it are unspecified, and all of it is unspecified on error.
+pub fn decompress_zero_copy(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+    let (config, header) = Config::from_header(input)?;
+    if config.use_custom_dictionary {
+        return Err(Error::InvalidConfig(
+            "Zero-copy decompression does not support custom dictionaries",
+        ));
+    }
+    let n = config.window_size();
+    if output.len() < n {
+        return Err(Error::BufferTooSmall);
+    }
+
+    // All accesses go through `out` while the C state uses the output as its window
+    let out = output.as_mut_ptr();
+    let len = output.len();
+    let mut inner: TampDecompressor = unsafe { core::mem::zeroed() };
+    let c_config = config.to_c_config();
+    Error::from_tamp_res(unsafe { tamp_decompressor_init(&mut inner, &c_config, out) })?;
+
+    let mut input = &input[header..];
+    let mut block = 0;
+    let mut total = 0;
+    let mut full = false;
+    // The C output only counts bytes: the window writes are the real output. A call writes at
+    // most one pending match plus ZERO_COPY_STEP bytes to the window, all after the match
+    // completes, so overlapping matches copy correctly.
+    let mut scratch = [0u8; ZERO_COPY_STEP];
+    let mut backup = [0u8; ZERO_COPY_STEP + 32];
+    loop {
+        let pos = inner.window_pos() as usize;
+        let near_end = pos + backup.len() > n;
+        if near_end {
+            unsafe {
+                core::ptr::copy_nonoverlapping(out.add(block), backup.as_mut_ptr(), backup.len())
+            };
+        }
+        let (mut consumed, mut written) = (0, 0);
         let result = unsafe {
-            tamp_decompressor_init(
-                &mut decompressor.inner,
-                &c_config,
-                decompressor.window.as_mut_ptr(),
+            tamp_decompressor_decompress_cb(
+                &mut inner,
+                scratch.as_mut_ptr(),
+                scratch.len(),
+                &mut written,
+                input.as_ptr(),
+                input.len(),
+                &mut consumed,
+                None,
+                core::ptr::null_mut(),
             )
         };
+        match result {
+            x if x == TAMP_OK as tamp_res
+                || x == TAMP_OUTPUT_FULL as tamp_res
+                || x == TAMP_INPUT_EXHAUSTED as tamp_res => {}
+            _ => Error::from_tamp_res(result)?,
+        }
+        input = &input[consumed..];
+        total += written;
+        if full && written > 0 {
+            return Err(Error::BufferTooSmall);
+        }
 
-        Error::from_tamp_res(result)?;
-        Ok(decompressor)
+        let wrapped = inner.window_pos() as usize;
+        if near_end && wrapped < pos {
+            // The window wrapped onto the start of this block: move the new bytes into the
+            // next block, seeded with this one as history, and restore what they overwrote
+            let next = block + n;
+            if next + n > len {
+                if wrapped > 0 {
+                    return Err(Error::BufferTooSmall);
+                }
+                full = true;
+            } else {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(out.add(block), out.add(next), n);
+                    core::ptr::copy_nonoverlapping(backup.as_ptr(), out.add(block), wrapped);
+                    inner.window = out.add(next);
+                }
+                block = next;
+            }
+        }
+        if consumed == 0 && written == 0 {
+            return Ok(total);
+        }
     }
+}
 
-    /// Creates decompressor by reading configuration from compressed stream header.
-    /// Returns (decompressor, bytes_consumed_from_input).
-    /// Buffer size N must match the window size found in header.
+impl Config {
+    /// Reads the configuration from a compressed stream header without creating a decompressor.
+    /// Returns (config, bytes_consumed_from_input).
+    /// Useful to pick the window size N before constructing a `Decompressor<N>`.
     pub fn from_header(input: &[u8]) -> Result<(Self, usize), Error> {
         let mut conf = unsafe { core::mem::zeroed::<TampConf>() };
         let mut input_consumed = 0;
@@ -88,51 +554,227 @@ impl<const N: usize> Decompressor<N> {
             literal_bits: conf.literal() as u8,
             use_custom_dictionary: conf.use_custom_dictionary() != 0,
             lazy_matching: false, // Not used for decompression
+            ..Config::new()
         };
+        trace_event!(
+            "header: {} bytes, window_bits: {}, literal_bits: {}, custom dictionary: {}",
+            input_consumed,
+            config.window_bits,
+            config.literal_bits,
+            config.use_custom_dictionary
+        );
+        Ok((config, input_consumed))
+    }
+}
 
-        let expected_size = config.window_size();
-        if N != expected_size {
-            return Err(Error::InvalidConfig("Buffer size N doesn't match header"));
-        }
+#[cfg(all(test, feature = "compressor"))]
+mod tests {
+    use super::*;
+    use crate::Compressor;
 
-        let decompressor = Self::new(config)?;
-        Ok((decompressor, input_consumed))
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_decompress_zero_copy() {
+        let asset: [u8; 3000] =
+            core::array::from_fn(|i| b"glyph-data:"[i % 11] ^ (i / 700) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = [0u8; 4096];
+        let (_, mut len) = compressor.compress_chunk(&asset, &mut compressed).unwrap();
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let mut output = [0u8; 3072];
+        let n = decompress_zero_copy(&compressed[..len], &mut output).unwrap();
+        assert_eq!(&output[..n], &asset[..]);
+
+        let mut small = [0u8; 2900];
+        assert!(matches!(
+            decompress_zero_copy(&compressed[..len], &mut small),
+            Err(Error::BufferTooSmall)
+        ));
     }
 
-    /// Decompresses input data into output buffer.
-    /// Returns (input_consumed, output_written). May not consume all input or fill all output.
-    /// Call repeatedly until input is exhausted or output is filled.
-    pub fn decompress_chunk(
-        &mut self,
-        input: &[u8],
-        output: &mut [u8],
-    ) -> Result<(usize, usize), Error> {
-        let mut input_consumed = 0;
-        let mut output_written = 0;
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_uninit_round_trip() {
+        let data: [u8; 600] = core::array::from_fn(|i| (i % 41) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = [MaybeUninit::uninit(); 1024];
+        let (consumed, written) = compressor
+            .compress_chunk_uninit(&data, &mut compressed)
+            .unwrap();
+        assert_eq!(consumed, data.len());
+        let mut len = written.len();
+        let mut stream = [0u8; 1024];
+        stream[..len].copy_from_slice(written);
+        len += compressor.flush(&mut stream[len..], false).unwrap();
 
-        let result = unsafe {
-            tamp_decompressor_decompress_cb(
-                &mut self.inner,
-                output.as_mut_ptr(),
-                output.len(),
-                &mut output_written,
-                input.as_ptr(),
-                input.len(),
-                &mut input_consumed,
-                None,                  // No callback
-                core::ptr::null_mut(), // No user data
-            )
-        };
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [MaybeUninit::uninit(); 600];
+        let (_, decoded) = decompressor
+            .decompress_chunk_uninit(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(decoded, &data[..]);
+    }
 
-        // For decompressor, INPUT_EXHAUSTED and OUTPUT_FULL are normal conditions
-        match result {
-            x if x == TAMP_OK as tamp_res
-                || x == TAMP_OUTPUT_FULL as tamp_res
-                || x == TAMP_INPUT_EXHAUSTED as tamp_res =>
-            {
-                Ok((input_consumed, output_written))
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_sink_poll() {
+        let data: [u8; 900] = core::array::from_fn(|i| b"isr "[i % 4] ^ (i / 200) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 1024];
+        let (_, mut len) = compressor.compress_chunk(&data, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 900];
+        let mut written = 0;
+        // One received byte per "interrupt", draining at most 8 output bytes per poll
+        for &byte in &stream[header..len] {
+            while decompressor.sink(&[byte]) == 0 {
+                assert!(decompressor.is_full());
+                let end = (written + 8).min(output.len());
+                written += decompressor.poll(&mut output[written..end]).unwrap();
+            }
+        }
+        loop {
+            let end = (written + 8).min(output.len());
+            let n = decompressor.poll(&mut output[written..end]).unwrap();
+            if n == 0 {
+                break;
             }
-            _ => Error::from_tamp_res(result).map(|_| (input_consumed, output_written)),
+            written += n;
         }
+        assert_eq!(&output[..written], &data[..]);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_skip_and_measure() {
+        let data: [u8; 1000] = core::array::from_fn(|i| (i % 7 + i / 100) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 1024];
+        let (_, mut len) = compressor.compress_chunk(&data, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut input = &stream[header..len];
+        let (consumed, skipped) = decompressor.skip(input, 777).unwrap();
+        assert_eq!(skipped, 777);
+        input = &input[consumed..];
+        let mut output = [0u8; 223];
+        let (_, n) = decompressor.decompress_chunk(input, &mut output).unwrap();
+        assert_eq!(&output[..n], &data[777..]);
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let (_, skipped) = decompressor.skip(&stream[header..len], 5000).unwrap();
+        assert_eq!(skipped, data.len());
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let measured: u64 = stream[header..len]
+            .chunks(10)
+            .map(|chunk| decompressor.measure(chunk).unwrap())
+            .sum();
+        assert_eq!(measured, data.len() as u64);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_decompress_exact() {
+        let data: [u8; 1000] = core::array::from_fn(|i| (i % 9 * i / 50) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 1024];
+        let (_, mut len) = compressor.compress_chunk(&data, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut input = &stream[header..len];
+        let mut record = [0u8; 400];
+        decompressor.decompress_exact(&mut input, &mut record).unwrap();
+        assert_eq!(record, data[..400]);
+        decompressor.decompress_exact(&mut input, &mut record).unwrap();
+        assert_eq!(record, data[400..800]);
+        assert!(matches!(
+            decompressor.decompress_exact(&mut input, &mut record),
+            Err(Error::InputExhausted)
+        ));
+        assert_eq!(record[..200], data[800..]);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_default_constructors() {
+        let data: [u8; 700] = core::array::from_fn(|i| (i % 23) as u8);
+        let mut compressor = crate::Compressor1K::default();
+        let mut stream = [0u8; 800];
+        let len = compressor.compress_all(&data, &mut stream).unwrap();
+
+        let (config, header) = Config::from_header(&stream).unwrap();
+        assert_eq!(config.window_size(), 1024);
+        let mut decompressor = crate::Decompressor1K::new_default();
+        let mut output = [0u8; 700];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &data[..]);
+
+        let config = Config::new().window_bits(9).unwrap();
+        assert!(Decompressor::<512>::try_from(config.clone()).is_ok());
+        let result: Result<crate::Compressor1K, _> = config.try_into();
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_debug() {
+        extern crate std;
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+        assert_eq!(compressor.sink(b"abc"), 3);
+        assert_eq!(
+            std::format!("{compressor:?}"),
+            "Compressor { window_size: 256, literal_bits: 8, consumed: 3, pending: 3, \
+             finished: false, .. }"
+        );
+        let mut stream = [0u8; 32];
+        let len = compressor.flush(&mut stream, false).unwrap();
+        assert!(std::format!("{compressor:?}").contains("finished: true"));
+
+        let mut decompressor = Decompressor::<256>::new(config).unwrap();
+        let mut output = [0u8; 3];
+        decompressor.decompress_chunk(&stream[1..len], &mut output).unwrap();
+        assert!(std::format!("{decompressor:?}").starts_with(
+            "Decompressor { window_size: 256, literal_bits: 8, produced: 3, buffered: 0,"
+        ));
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_prime_window_resume() {
+        let data: [u8; 700] =
+            core::array::from_fn(|i| b"seq=0042,val=17;"[i % 16] ^ (i / 90) as u8);
+        let (before, after) = data.split_at(600);
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+        let mut stream = [0u8; 1024];
+        let (_, mut sync) = compressor.compress_chunk(before, &mut stream).unwrap();
+        sync += compressor.flush(&mut stream[sync..], true).unwrap();
+        let (_, mut len) = compressor.compress_chunk(after, &mut stream[sync..]).unwrap();
+        len += sync;
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        // The transfer broke at the sync point: resume with a fresh decompressor
+        let mut decompressor = Decompressor::<256>::new(config).unwrap();
+        decompressor.prime_window(&before[600 - (256 + 600 % 256)..]).unwrap();
+        let mut output = [0u8; 400];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[sync..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], after);
+
+        decompressor.sink(&stream[sync..sync + 4]);
+        assert!(matches!(
+            decompressor.prime_window(before),
+            Err(Error::InputPending)
+        ));
     }
 }
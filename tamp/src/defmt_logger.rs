@@ -0,0 +1,155 @@
+use crate::staging::Staging;
+use crate::{Compressor, Config, Error};
+
+/// Compressing stage for a defmt global logger.
+///
+/// Frames are encoded with `defmt::Encoder` and the encoded stream is compressed before it
+/// reaches `transport`, so the decompressed output can be fed straight to `defmt-print`.
+/// Call each method from the matching `defmt::Logger` hook; output is only guaranteed to
+/// reach the transport after `flush()`. The hooks cannot return errors, so failures are
+/// counted in `errors()`.
+///
+/// ```ignore
+/// #[defmt::global_logger]
+/// struct Logger;
+///
+/// unsafe impl defmt::Logger for Logger {
+///     fn acquire() { with_log(|log| log.start_frame(radio_send)) }
+///     unsafe fn write(bytes: &[u8]) { with_log(|log| log.write(bytes, radio_send)) }
+///     unsafe fn release() { with_log(|log| log.end_frame(radio_send)) }
+///     unsafe fn flush() { with_log(|log| log.flush(radio_send)) }
+/// }
+/// ```
+pub struct DefmtCompressor<const N: usize> {
+    compressor: Compressor<N>,
+    encoder: defmt::Encoder,
+    staging: Staging,
+    errors: u32,
+}
+
+impl<const N: usize> DefmtCompressor<N> {
+    /// Creates a logging stage. Literals must be 8 bits since defmt frames are binary.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        if config.literal_bits != 8 {
+            return Err(Error::InvalidConfig("defmt frames require 8-bit literals"));
+        }
+        Ok(Self {
+            compressor: Compressor::new(config)?,
+            encoder: defmt::Encoder::new(),
+            staging: Staging::new(),
+            errors: 0,
+        })
+    }
+
+    /// Number of calls whose output was lost because compression failed.
+    pub fn errors(&self) -> u32 {
+        self.errors
+    }
+
+    /// Starts a log frame. Call from `Logger::acquire`.
+    pub fn start_frame(&mut self, mut transport: impl FnMut(&[u8])) {
+        let Self {
+            compressor,
+            encoder,
+            staging,
+            errors,
+        } = self;
+        encoder.start_frame(|bytes| {
+            Self::compress(staging, compressor, errors, bytes, &mut transport)
+        });
+    }
+
+    /// Writes part of a log frame. Call from `Logger::write`.
+    pub fn write(&mut self, data: &[u8], mut transport: impl FnMut(&[u8])) {
+        let Self {
+            compressor,
+            encoder,
+            staging,
+            errors,
+        } = self;
+        encoder.write(data, |bytes| {
+            Self::compress(staging, compressor, errors, bytes, &mut transport)
+        });
+    }
+
+    /// Ends a log frame. Call from `Logger::release`.
+    pub fn end_frame(&mut self, mut transport: impl FnMut(&[u8])) {
+        let Self {
+            compressor,
+            encoder,
+            staging,
+            errors,
+        } = self;
+        encoder
+            .end_frame(|bytes| Self::compress(staging, compressor, errors, bytes, &mut transport));
+    }
+
+    /// Pushes all buffered frames to the transport. Call from `Logger::flush`.
+    pub fn flush(&mut self, mut transport: impl FnMut(&[u8])) {
+        let result = self
+            .staging
+            .flush(&mut self.compressor, true, &mut transport);
+        Self::count(&mut self.errors, result);
+    }
+
+    fn compress(
+        staging: &mut Staging,
+        compressor: &mut Compressor<N>,
+        errors: &mut u32,
+        bytes: &[u8],
+        transport: &mut impl FnMut(&[u8]),
+    ) {
+        Self::count(errors, staging.write(compressor, bytes, transport));
+    }
+
+    fn count(errors: &mut u32, result: Result<(), Error>) {
+        if result.is_err() {
+            *errors = errors.saturating_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Decompressor;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_defmt_compressor() {
+        let config = Config::new().window_bits(8).unwrap();
+        let mut log = DefmtCompressor::<256>::new(config).unwrap();
+        let mut encoder = defmt::Encoder::new();
+        let (mut stream, mut expected) = (Vec::new(), Vec::new());
+        for i in 0..40u8 {
+            let frame = [i % 4, 0x55, 0, i];
+            log.start_frame(|b| stream.extend_from_slice(b));
+            log.write(&frame, |b| stream.extend_from_slice(b));
+            log.end_frame(|b| stream.extend_from_slice(b));
+            encoder.start_frame(|b| expected.extend_from_slice(b));
+            encoder.write(&frame, |b| expected.extend_from_slice(b));
+            encoder.end_frame(|b| expected.extend_from_slice(b));
+        }
+        log.flush(|b| stream.extend_from_slice(b));
+        assert_eq!(log.errors(), 0);
+        assert!(stream.len() < expected.len());
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = std::vec![0u8; expected.len()];
+        decompressor
+            .decompress_exact(&mut &stream[header..], &mut output)
+            .unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_defmt_compressor_rejects_narrow_literals() {
+        let config = Config::new().literal_bits(7).unwrap();
+        assert!(matches!(
+            DefmtCompressor::<1024>::new(config),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
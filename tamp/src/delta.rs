@@ -0,0 +1,262 @@
+//! Firmware delta updates using the installed image as dictionary.
+//!
+//! The new image is split into `N`-byte blocks, each compressed as an independent stream
+//! with `N` bytes of the old image pre-loaded as custom dictionary, so unchanged code
+//! compresses to a handful of matches. The compressor looks up where each block's content
+//! sits in the old image through a hash index built once over it, preferring the copy
+//! closest to where the previous block left off, so code shifted by an insertion still
+//! finds its old copy. The patch is a sequence of
+//! `[u16 LE length][u32 LE old offset][tamp stream]` blocks.
+//!
+//! A block may take its dictionary from anywhere in the old image, so the decoder needs the
+//! old image intact until the patch is applied, e.g. by writing the new one to the other
+//! slot of an A/B layout.
+
+use crate::{Config, Error};
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+
+/// Bytes in front of every block: the stream length and the old image offset.
+const PREFIX: usize = 6;
+
+/// Bytes of a new block looked up in the old image to locate it.
+#[cfg(feature = "compressor")]
+const ANCHOR: usize = 16;
+/// Distance between the old image positions entered in the index.
+#[cfg(feature = "compressor")]
+const STRIDE: usize = 8;
+/// Buckets of the anchor index, a power of two.
+#[cfg(feature = "compressor")]
+const BUCKETS: usize = 4096;
+
+/// Fills `dictionary` with the old image from `offset`, zero-padded past its end.
+#[cfg(feature = "compressor")]
+fn old_window(old: &[u8], offset: usize, dictionary: &mut [u8]) {
+    let start = offset.min(old.len());
+    let window = &old[start..old.len().min(start + dictionary.len())];
+    dictionary.fill(0);
+    dictionary[..window.len()].copy_from_slice(window);
+}
+
+/// Hash index of the old image: every `STRIDE`-th position by the hash of the `ANCHOR`
+/// bytes there, so locating a block costs a few lookups instead of a scan of the image.
+/// A bucket keeps the first position hashed to it; later ones are not found.
+#[cfg(feature = "compressor")]
+struct AnchorIndex<'a> {
+    old: &'a [u8],
+    /// Old image position + 1 per bucket, 0 if empty.
+    buckets: [u32; BUCKETS],
+}
+
+#[cfg(feature = "compressor")]
+impl<'a> AnchorIndex<'a> {
+    fn new(old: &'a [u8]) -> Self {
+        let mut index = Self {
+            old,
+            buckets: [0; BUCKETS],
+        };
+        let positions = old.len().checked_sub(ANCHOR).map_or(0, |last| last + 1);
+        for q in (0..positions.min(u32::MAX as usize)).step_by(STRIDE) {
+            let bucket = &mut index.buckets[hash(&old[q..q + ANCHOR])];
+            if *bucket == 0 {
+                *bucket = q as u32 + 1;
+            }
+        }
+        index
+    }
+
+    /// Indexed position of `anchor` in the old image.
+    fn find(&self, anchor: &[u8]) -> Option<usize> {
+        let q = (self.buckets[hash(anchor)] as usize).checked_sub(1)?;
+        (self.old[q..q + ANCHOR] == *anchor).then_some(q)
+    }
+
+    /// Offset of the old image window to pre-load for `block`: where one of its anchors
+    /// occurs in the old image, aligned to the block start and closest to `guess`, or
+    /// `guess` if none does. Each anchor is tried at `STRIDE` shifts, one of which lines
+    /// up with an indexed position.
+    fn locate(&self, block: &[u8], guess: usize) -> usize {
+        [0, block.len() / 2]
+            .into_iter()
+            .flat_map(|p| p..p + STRIDE)
+            .filter_map(|p| self.find(block.get(p..p + ANCHOR)?)?.checked_sub(p))
+            .min_by_key(|&offset| offset.abs_diff(guess))
+            .unwrap_or(guess)
+    }
+}
+
+/// Bucket of the `ANCHOR` bytes at the start of `anchor`.
+#[cfg(feature = "compressor")]
+fn hash(anchor: &[u8]) -> usize {
+    let bytes: [u8; 8] = anchor[..8].try_into().unwrap();
+    let lo = u64::from_le_bytes(bytes);
+    let bytes: [u8; 8] = anchor[8..ANCHOR].try_into().unwrap();
+    let hi = u64::from_le_bytes(bytes);
+    let mixed = (lo ^ hi.rotate_left(29)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (mixed >> (64 - BUCKETS.trailing_zeros())) as usize
+}
+
+/// Compresses `new` against the installed image `old`, passing patch bytes to `sink`.
+///
+/// `config` must match `N`; its custom dictionary flag is forced on. `scratch` holds one
+/// compressed block and should be at least `N + N / 8 + 32` bytes. The index of `old` takes
+/// another 16 KiB of stack. Returns the patch length.
+#[cfg(feature = "compressor")]
+pub fn compress_delta<const N: usize>(
+    mut config: Config,
+    old: &[u8],
+    new: &[u8],
+    scratch: &mut [u8],
+    mut sink: impl FnMut(&[u8]),
+) -> Result<usize, Error> {
+    config.use_custom_dictionary = true;
+    let index = AnchorIndex::new(old);
+    let mut dictionary = [0u8; N];
+    let mut total = 0;
+    let mut guess = 0;
+    for block in new.chunks(N) {
+        let offset = index.locate(block, guess);
+        old_window(old, offset, &mut dictionary);
+        let mut compressor = Compressor::<N>::with_dictionary(config.clone(), Some(&dictionary))?;
+        let len = compressor.compress_all(block, scratch)?;
+        sink(&(len as u16).to_le_bytes());
+        sink(&(offset as u32).to_le_bytes());
+        sink(&scratch[..len]);
+        total += PREFIX + len;
+        guess = offset + N;
+    }
+    Ok(total)
+}
+
+/// Applies a patch produced by `compress_delta`, passing the new image to `output`.
+///
+/// `read_old(offset, buf)` must fill `buf` with the installed image at `offset`, zero-padded
+/// past its end (typically a flash read). Returns the new image length.
+#[cfg(feature = "decompressor")]
+pub fn decompress_delta<const N: usize>(
+    mut patch: &[u8],
+    mut read_old: impl FnMut(usize, &mut [u8]),
+    mut output: impl FnMut(&[u8]),
+) -> Result<usize, Error> {
+    let mut dictionary = [0u8; N];
+    let mut buf = [0u8; 64];
+    let mut total = 0;
+    while !patch.is_empty() {
+        let (len, offset) = match patch {
+            [l0, l1, o0, o1, o2, o3, ..] => (
+                u16::from_le_bytes([*l0, *l1]) as usize,
+                u32::from_le_bytes([*o0, *o1, *o2, *o3]) as usize,
+            ),
+            _ => return Err(Error::InputExhausted),
+        };
+        let mut block = patch
+            .get(PREFIX..PREFIX + len)
+            .ok_or(Error::InputExhausted)?;
+        patch = &patch[PREFIX + len..];
+
+        let (config, header) = Config::from_header(block)?;
+        block = &block[header..];
+        read_old(offset, &mut dictionary);
+        let mut decompressor = Decompressor::<N>::with_dictionary(config, Some(&dictionary))?;
+        loop {
+            let (consumed, written) = decompressor.decompress_chunk(block, &mut buf)?;
+            block = &block[consumed..];
+            output(&buf[..written]);
+            total += written;
+            if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    /// `len` bytes without repeats a window could exploit, from xorshift32.
+    fn noise(seed: u32, len: usize) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_delta_round_trip() {
+        let old: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut new = old.clone();
+        new[100..110].fill(0xAA);
+        new[2500] ^= 0x55;
+        new.extend_from_slice(b"appended");
+
+        let config = Config::new().window_bits(8).unwrap();
+        let mut scratch = [0u8; 320];
+        let mut patch = Vec::new();
+        let len = compress_delta::<256>(config, &old, &new, &mut scratch, |b| {
+            patch.extend_from_slice(b)
+        })
+        .unwrap();
+        assert_eq!(len, patch.len());
+        assert!(patch.len() < new.len() / 4);
+
+        let mut image = Vec::new();
+        decompress_delta::<256>(
+            &patch,
+            |offset, buf| old_window(&old, offset, buf),
+            |b| image.extend_from_slice(b),
+        )
+        .unwrap();
+        assert_eq!(image, new);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_delta_shifted() {
+        let old = noise(1, 4000);
+        let mut new = old.clone();
+        new.splice(700..700, noise(2, 600));
+
+        let config = Config::new().window_bits(8).unwrap();
+        let mut scratch = [0u8; 320];
+        let mut patch = Vec::new();
+        compress_delta::<256>(config, &old, &new, &mut scratch, |b| patch.extend_from_slice(b))
+            .unwrap();
+        assert!(patch.len() < 600 + old.len() / 4);
+
+        let mut image = Vec::new();
+        decompress_delta::<256>(
+            &patch,
+            |offset, buf| old_window(&old, offset, buf),
+            |b| image.extend_from_slice(b),
+        )
+        .unwrap();
+        assert_eq!(image, new);
+    }
+
+    #[test]
+    fn test_locate() {
+        let old = noise(1, 2000);
+        let index = AnchorIndex::new(&old);
+        assert_eq!(index.locate(&old[1000..1256], 0), 1000);
+        assert_eq!(index.locate(&old[1000..1256], 1300), 1000);
+        // Not on the indexed stride
+        assert_eq!(index.locate(&old[1003..1259], 0), 1003);
+        let mut changed = old[1000..1256].to_vec();
+        changed[..20].fill(0);
+        assert_eq!(index.locate(&changed, 0), 1000);
+        assert_eq!(index.locate(&[0; 256], 512), 512);
+        assert_eq!(AnchorIndex::new(&old[..10]).locate(&old[..256], 7), 7);
+    }
+}
@@ -0,0 +1,496 @@
+//! Versioned storage format for trained dictionaries.
+//!
+//! A stream compressed with a custom dictionary decodes into garbage with any other one, and
+//! nothing in the stream says so. Storing the dictionary with a header lets both ends check
+//! it before use, and its checksum doubles as an identifier to compare over the link:
+//!
+//! | Offset | Size | Field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0      | 4    | Magic `TDIC`                           |
+//! | 4      | 1    | Format version, currently 1            |
+//! | 5      | 1    | Window bits (8-15)                     |
+//! | 6      | 2    | Data length, u16 LE                    |
+//! | 8      | 4    | CRC-32 (IEEE) of the data, u32 LE      |
+//! | 12     | len  | Data, at most 2^window_bits bytes      |
+//!
+//! With `alloc`, `train()` builds the data from sample messages, e.g. on a gateway that
+//! then pushes it to the fleet.
+//!
+//! ```ignore
+//! let dict = Dictionary::load(flash.read_slice(DICT_ADDR))?;
+//! dict.verify(peer_checksum)?;
+//! let compressor = Compressor::<1024>::with_dictionary(dict.config(), Some(dict.data()))?;
+//! ```
+//!
+//! Fleets rotating dictionaries keep several in a `DictionaryRegistry` under small numeric
+//! IDs. Each stream then starts with the one byte ID of its dictionary, followed by the tamp
+//! header, and the receiver picks the dictionary from it:
+//!
+//! ```ignore
+//! // sender
+//! let compressor = registry.compressor::<1024>(CURRENT_ID, config)?;
+//! uplink.send(&[CURRENT_ID])?;
+//! // receiver
+//! let (mut decompressor, consumed) = registry.decompressor::<1024>(&packet)?;
+//! ```
+//!
+//! To catch a wrong dictionary before decoding, the sender can pass its output through a
+//! `FingerprintWriter`, which inserts the dictionary checksum as a u32 LE right after the
+//! one byte tamp header of streams with the custom dictionary flag set. `Dictionary::open()`
+//! checks it on the receiving side:
+//!
+//! ```ignore
+//! let mut writer = FingerprintWriter::new(&dict, |bytes| uplink.send(bytes));
+//! // receiver
+//! let (mut decompressor, consumed) = dict.open::<1024>(&packet)?;
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(all(feature = "compressor", feature = "dictionary"))]
+use crate::Compressor;
+#[cfg(all(feature = "decompressor", feature = "dictionary"))]
+use crate::Decompressor;
+use crate::tokens::Tokens;
+use crate::{Config, Error};
+
+/// Magic bytes at the start of a stored dictionary.
+pub const MAGIC: [u8; 4] = *b"TDIC";
+/// Format version written by `store()`; `load()` rejects any other.
+pub const VERSION: u8 = 1;
+/// Bytes of header before the dictionary data.
+pub const HEADER_LEN: usize = 12;
+/// Bytes of dictionary fingerprint a `FingerprintWriter` inserts after the stream header.
+pub const FINGERPRINT_LEN: usize = 4;
+/// Length of the sample pieces `train()` picks from.
+#[cfg(feature = "alloc")]
+const SEGMENT: usize = 32;
+/// Length of the substrings `train()` counts.
+#[cfg(feature = "alloc")]
+const GRAM: usize = 4;
+
+/// A dictionary together with the window size it was trained for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dictionary<'a> {
+    window_bits: u8,
+    data: &'a [u8],
+}
+
+impl<'a> Dictionary<'a> {
+    /// Wraps `data` for a 2^`window_bits` byte window. `data` may be shorter than the window.
+    pub fn new(window_bits: u8, data: &'a [u8]) -> Result<Self, Error> {
+        let config = Config::new().window_bits(window_bits)?;
+        if data.len() > config.window_size() {
+            return Err(Error::InvalidConfig("Dictionary larger than the window"));
+        }
+        Ok(Self { window_bits, data })
+    }
+
+    /// Parses a stored dictionary, checking magic, version, length and checksum.
+    /// Bytes after the data are ignored, so `stored` may be a whole flash sector.
+    pub fn load(stored: &'a [u8]) -> Result<Self, Error> {
+        let header = stored.get(..HEADER_LEN).ok_or(Error::InvalidDictionary)?;
+        if header[..4] != MAGIC || header[4] != VERSION {
+            return Err(Error::InvalidDictionary);
+        }
+        let len = u16::from_le_bytes([header[6], header[7]]) as usize;
+        let checksum = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let data = stored
+            .get(HEADER_LEN..HEADER_LEN + len)
+            .ok_or(Error::InvalidDictionary)?;
+        let dictionary = Self::new(header[5], data).map_err(|_| Error::InvalidDictionary)?;
+        dictionary.verify(checksum)?;
+        Ok(dictionary)
+    }
+
+    /// Writes header and data into `output`, returning the bytes written.
+    pub fn store(&self, output: &mut [u8]) -> Result<usize, Error> {
+        let len = self.stored_len();
+        let output = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        output[..4].copy_from_slice(&MAGIC);
+        output[4] = VERSION;
+        output[5] = self.window_bits;
+        output[6..8].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
+        output[8..12].copy_from_slice(&self.checksum().to_le_bytes());
+        output[HEADER_LEN..].copy_from_slice(self.data);
+        Ok(len)
+    }
+
+    /// Bytes `store()` writes.
+    pub fn stored_len(&self) -> usize {
+        HEADER_LEN + self.data.len()
+    }
+
+    /// CRC-32 of the data, to compare with the dictionary the other end uses.
+    pub fn checksum(&self) -> u32 {
+        crc32(self.data)
+    }
+
+    /// Fails with `InvalidDictionary` unless the data has the `expected` checksum.
+    pub fn verify(&self, expected: u32) -> Result<(), Error> {
+        if self.checksum() != expected {
+            return Err(Error::InvalidDictionary);
+        }
+        Ok(())
+    }
+
+    /// Window size in bits the dictionary was trained for.
+    pub fn window_bits(&self) -> u8 {
+        self.window_bits
+    }
+
+    /// The dictionary data, to pass to `with_dictionary()`.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Default configuration with this window size and the custom dictionary enabled.
+    pub fn config(&self) -> Config {
+        Config {
+            window_bits: self.window_bits,
+            use_custom_dictionary: true,
+            ..Config::new()
+        }
+    }
+
+    /// Reads the header at the start of `input` and creates a decompressor for the stream.
+    /// If the header enables the custom dictionary, the fingerprint written by a
+    /// `FingerprintWriter` must follow it and match this dictionary, else this fails with
+    /// `InvalidDictionary`. Returns (decompressor, bytes_consumed_from_input).
+    #[cfg(all(feature = "decompressor", feature = "dictionary"))]
+    pub fn open<const N: usize>(&self, input: &[u8]) -> Result<(Decompressor<N>, usize), Error> {
+        let (config, header) = Config::from_header(input)?;
+        if !config.use_custom_dictionary {
+            return Ok((Decompressor::new(config)?, header));
+        }
+        let fingerprint = input
+            .get(header..header + FINGERPRINT_LEN)
+            .ok_or(Error::InputExhausted)?;
+        self.verify(u32::from_le_bytes(fingerprint.try_into().unwrap()))?;
+        if config.window_bits != self.window_bits {
+            return Err(Error::InvalidDictionary);
+        }
+        let decompressor = Decompressor::with_dictionary(config, Some(self.data))?;
+        Ok((decompressor, header + FINGERPRINT_LEN))
+    }
+}
+
+/// Passes compressed output on to `sink`, inserting the dictionary fingerprint after the
+/// stream header if the header enables the custom dictionary.
+pub struct FingerprintWriter<F: FnMut(&[u8])> {
+    fingerprint: Option<[u8; FINGERPRINT_LEN]>,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8])> FingerprintWriter<F> {
+    /// Creates a writer for a stream compressed with `dictionary`.
+    pub fn new(dictionary: &Dictionary<'_>, sink: F) -> Self {
+        Self {
+            fingerprint: Some(dictionary.checksum().to_le_bytes()),
+            sink,
+        }
+    }
+
+    /// Passes on compressed bytes; the first one written is taken as the stream header.
+    pub fn write(&mut self, bytes: &[u8]) {
+        let Some((&header, rest)) = bytes.split_first() else {
+            return;
+        };
+        let Some(fingerprint) = self.fingerprint.take() else {
+            (self.sink)(bytes);
+            return;
+        };
+        (self.sink)(&[header]);
+        if Tokens::new(&[header]).is_ok_and(|tokens| tokens.config().use_custom_dictionary) {
+            (self.sink)(&fingerprint);
+        }
+        (self.sink)(rest);
+    }
+
+    /// Returns the sink.
+    pub fn into_inner(self) -> F {
+        self.sink
+    }
+}
+
+/// Up to `K` dictionaries, each under a one byte ID.
+pub struct DictionaryRegistry<'a, const K: usize> {
+    entries: [Option<(u8, Dictionary<'a>)>; K],
+}
+
+impl<'a, const K: usize> DictionaryRegistry<'a, K> {
+    /// Creates an empty registry.
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; K],
+        }
+    }
+
+    /// Adds `dictionary` under `id`, replacing any dictionary already registered under it.
+    /// Fails with `BufferTooSmall` if `K` dictionaries are registered already.
+    pub fn register(&mut self, id: u8, dictionary: Dictionary<'a>) -> Result<(), Error> {
+        let slot = match self.entries.iter().position(|e| e.is_some_and(|(key, _)| key == id)) {
+            Some(slot) => slot,
+            None => self.entries.iter().position(Option::is_none).ok_or(Error::BufferTooSmall)?,
+        };
+        self.entries[slot] = Some((id, dictionary));
+        Ok(())
+    }
+
+    /// Looks up the dictionary registered under `id`.
+    pub fn get(&self, id: u8) -> Result<&Dictionary<'a>, Error> {
+        self.entries
+            .iter()
+            .flatten()
+            .find(|(key, _)| *key == id)
+            .map(|(_, dictionary)| dictionary)
+            .ok_or(Error::UnknownDictionary(id))
+    }
+
+    /// Creates a compressor using the dictionary under `id`. The window size and custom
+    /// dictionary flag of `config` are overridden to match it. Send `id` as the first byte
+    /// of the stream, before the compressed output.
+    #[cfg(all(feature = "compressor", feature = "dictionary"))]
+    pub fn compressor<const N: usize>(
+        &self,
+        id: u8,
+        config: Config,
+    ) -> Result<Compressor<N>, Error> {
+        let dictionary = self.get(id)?;
+        let config = Config {
+            window_bits: dictionary.window_bits,
+            use_custom_dictionary: true,
+            ..config
+        };
+        Compressor::with_dictionary(config, Some(dictionary.data))
+    }
+
+    /// Reads the dictionary ID and header at the start of `input` and creates a decompressor
+    /// with the matching dictionary. Returns (decompressor, bytes_consumed_from_input).
+    /// Fails with `UnknownDictionary` for an unregistered ID and `InvalidDictionary` if the
+    /// header does not match the registered dictionary.
+    #[cfg(all(feature = "decompressor", feature = "dictionary"))]
+    pub fn decompressor<const N: usize>(
+        &self,
+        input: &[u8],
+    ) -> Result<(Decompressor<N>, usize), Error> {
+        let (&id, rest) = input.split_first().ok_or(Error::InputExhausted)?;
+        let dictionary = self.get(id)?;
+        let (config, header) = Config::from_header(rest)?;
+        if !config.use_custom_dictionary || config.window_bits != dictionary.window_bits {
+            return Err(Error::InvalidDictionary);
+        }
+        let decompressor = Decompressor::with_dictionary(config, Some(dictionary.data))?;
+        Ok((decompressor, 1 + header))
+    }
+}
+
+impl<const K: usize> Default for DictionaryRegistry<'_, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds dictionary data for a 2^`window_bits` byte window from typical messages.
+///
+/// Greedily picks the pieces of `samples` containing the most 4-byte strings that recur
+/// across samples, until the window is full or nothing else recurs. The most useful pieces
+/// go last, as the compressor overwrites the window from the start. Wrap the result with
+/// `Dictionary::new()` to store it. Fails with `InvalidConfig` if the samples share nothing.
+#[cfg(feature = "alloc")]
+pub fn train(samples: &[&[u8]], window_bits: u8) -> Result<Vec<u8>, Error> {
+    let size = Config::new().window_bits(window_bits)?.window_size();
+    // Number of samples each string appears in; strings in a single sample are useless
+    let mut counts = BTreeMap::<[u8; GRAM], u32>::new();
+    let mut seen = BTreeSet::new();
+    for sample in samples {
+        seen.clear();
+        for gram in sample.windows(GRAM) {
+            if seen.insert(gram) {
+                *counts.entry(gram.try_into().unwrap()).or_default() += 1;
+            }
+        }
+    }
+    counts.retain(|_, count| *count > 1);
+
+    // Overlapping by half, so recurring strings are not all cut at piece boundaries
+    let mut segments: Vec<&[u8]> = samples
+        .iter()
+        .flat_map(|sample| {
+            (0..sample.len())
+                .step_by(SEGMENT / 2)
+                .map(|start| &sample[start..sample.len().min(start + SEGMENT)])
+        })
+        .collect();
+    let mut picked = Vec::new();
+    let mut len = 0;
+    while len < size {
+        let best = segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let score: u32 = segment.windows(GRAM).filter_map(|g| counts.get(g)).sum();
+                (score, i)
+            })
+            .max();
+        let Some((_, index)) = best.filter(|&(score, _)| score > 0) else {
+            break;
+        };
+        let segment = segments.swap_remove(index);
+        for gram in segment.windows(GRAM) {
+            counts.remove(gram);
+        }
+        let segment = &segment[..segment.len().min(size - len)];
+        len += segment.len();
+        picked.push(segment);
+    }
+    if picked.is_empty() {
+        return Err(Error::InvalidConfig("Samples share no content"));
+    }
+    Ok(picked.iter().rev().flat_map(|segment| segment.iter().copied()).collect())
+}
+
+/// Bitwise CRC-32 (IEEE 802.3). Dictionaries are loaded rarely, so no table is kept.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+/// Feeds `data` into a running CRC-32 register, for data checked in several pieces.
+/// Start with `!0` and invert the final register.
+pub(crate) fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_load() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let data: [u8; 200] = core::array::from_fn(|i| b"temp=,hum=,"[i % 11]);
+        let dictionary = Dictionary::new(8, &data).unwrap();
+        let mut flash = [0xFFu8; 256];
+        let len = dictionary.store(&mut flash).unwrap();
+        assert_eq!(len, HEADER_LEN + 200);
+        let loaded = Dictionary::load(&flash).unwrap();
+        assert_eq!(loaded, dictionary);
+        assert!(loaded.verify(dictionary.checksum()).is_ok());
+        assert!(loaded.config().use_custom_dictionary);
+
+        let mut corrupt = flash;
+        corrupt[HEADER_LEN + 17] ^= 1;
+        assert!(matches!(Dictionary::load(&corrupt), Err(Error::InvalidDictionary)));
+        let mut future = flash;
+        future[4] = VERSION + 1;
+        assert!(matches!(Dictionary::load(&future), Err(Error::InvalidDictionary)));
+        assert!(matches!(Dictionary::load(&flash[..len - 1]), Err(Error::InvalidDictionary)));
+        assert!(matches!(dictionary.store(&mut [0; 100]), Err(Error::BufferTooSmall)));
+        assert!(Dictionary::new(8, &[0; 257]).is_err());
+    }
+
+    #[cfg(all(feature = "alloc", feature = "compressor", feature = "dictionary"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_train() {
+        extern crate std;
+        use std::format;
+
+        let records: Vec<Vec<u8>> = (0..40)
+            .map(|i| format!("{{\"id\":\"node-{i}\",\"temp\":{}.5,\"state\":\"ok\"}}", i % 9))
+            .map(|record| record.into_bytes())
+            .collect();
+        let samples: Vec<&[u8]> = records.iter().map(Vec::as_slice).collect();
+        let data = train(&samples, 8).unwrap();
+        assert!(!data.is_empty() && data.len() <= 256);
+        assert!(train(&[&b"abcdef"[..], b"uvwxyz"], 8).is_err());
+
+        let message = b"{\"id\":\"node-77\",\"temp\":3.5,\"state\":\"ok\"}";
+        let compressed_len = |compressor: &mut Compressor<256>| {
+            let mut output = [0u8; 128];
+            let (_, n) = compressor.compress_chunk(message, &mut output).unwrap();
+            n + compressor.flush(&mut output[n..], false).unwrap()
+        };
+        let dictionary = Dictionary::new(8, &data).unwrap();
+        let mut trained =
+            Compressor::<256>::with_dictionary(dictionary.config(), Some(&data)).unwrap();
+        let mut plain = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        assert!(compressed_len(&mut trained) < compressed_len(&mut plain));
+    }
+
+    #[cfg(all(feature = "compressor", feature = "decompressor", feature = "dictionary"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_registry() {
+        let old: [u8; 256] = core::array::from_fn(|i| b"t=21.5;"[i % 7]);
+        let new: [u8; 256] = core::array::from_fn(|i| b"temp=21.5,rh=40;"[i % 16]);
+        let mut registry = DictionaryRegistry::<2>::new();
+        registry.register(1, Dictionary::new(8, &old).unwrap()).unwrap();
+        registry.register(2, Dictionary::new(8, &new).unwrap()).unwrap();
+        assert!(registry.register(3, Dictionary::new(8, &new).unwrap()).is_err());
+
+        let mut compressor = registry.compressor::<256>(2, Config::new()).unwrap();
+        let mut stream = [0u8; 128];
+        stream[0] = 2;
+        let (_, mut len) = compressor
+            .compress_chunk(b"temp=19.0,rh=55;", &mut stream[1..])
+            .unwrap();
+        len += 1;
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, consumed) = registry.decompressor::<256>(&stream[..len]).unwrap();
+        let mut output = [0u8; 32];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[consumed..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], b"temp=19.0,rh=55;");
+
+        stream[0] = 7;
+        assert!(matches!(
+            registry.decompressor::<256>(&stream[..len]),
+            Err(Error::UnknownDictionary(7))
+        ));
+    }
+
+    #[cfg(all(feature = "compressor", feature = "decompressor", feature = "dictionary"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_fingerprint() {
+        extern crate std;
+        use std::vec::Vec;
+
+        let installed: [u8; 256] = core::array::from_fn(|i| b"lat=52.1,lon=4.3;"[i % 17]);
+        let outdated: [u8; 256] = core::array::from_fn(|i| b"lat=52.1;lon=4.3,"[i % 17]);
+        let dictionary = Dictionary::new(8, &installed).unwrap();
+        let mut compressor =
+            Compressor::<256>::with_dictionary(dictionary.config(), Some(&installed)).unwrap();
+        let mut stream = Vec::new();
+        let mut writer = FingerprintWriter::new(&dictionary, |bytes: &[u8]| {
+            stream.extend_from_slice(bytes)
+        });
+        let mut output = [0u8; 64];
+        let (_, n) = compressor.compress_chunk(b"lat=51.9,lon=4.4;", &mut output).unwrap();
+        writer.write(&output[..n]);
+        let n = compressor.flush(&mut output, false).unwrap();
+        writer.write(&output[..n]);
+
+        let (mut decompressor, consumed) = dictionary.open::<256>(&stream).unwrap();
+        assert_eq!(consumed, 1 + FINGERPRINT_LEN);
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[consumed..], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], b"lat=51.9,lon=4.4;");
+
+        let wrong = Dictionary::new(8, &outdated).unwrap();
+        assert!(matches!(wrong.open::<256>(&stream), Err(Error::InvalidDictionary)));
+    }
+}
@@ -0,0 +1,139 @@
+//! Double-buffered compression for DMA acquisition.
+//!
+//! DMA fills one half of a `DoubleBuffer` while the CPU compresses the other. The DMA
+//! complete interrupt calls `swap()`, handing the freshly filled half to the CPU and the
+//! compressed one back to the DMA. `compress()` may be called with output buffers as small
+//! as 8 bytes; the offset into the half survives partial consumption, so no input is lost
+//! or compressed twice. If the CPU falls behind, `swap()` reports `Error::Overrun` instead of
+//! letting the DMA overwrite uncompressed data.
+//!
+//! ```ignore
+//! // DMA complete interrupt, inside a critical section shared with the main loop
+//! buffers.swap()?;
+//! dma.start(buffers.dma_buffer());
+//! // main loop
+//! let n = buffers.compress(&mut compressor, &mut packet)?;
+//! ```
+
+use crate::compressor::MAX_POLL;
+use crate::{Compressor, Error};
+
+/// Two `HALF` byte buffers, one owned by the DMA and one by the compressor.
+///
+/// Word aligned so either half can be a DMA target. `HALF` must be a non-zero multiple of 4.
+#[repr(C, align(4))]
+pub struct DoubleBuffer<const HALF: usize> {
+    halves: [[u8; HALF]; 2],
+    /// Index of the half the DMA is filling.
+    dma: usize,
+    /// Bytes of the CPU half already compressed.
+    offset: usize,
+    /// The CPU half holds data not yet fully compressed.
+    ready: bool,
+}
+
+impl<const HALF: usize> DoubleBuffer<HALF> {
+    /// Creates a buffer pair with the DMA on the first half and nothing to compress.
+    pub const fn new() -> Self {
+        const {
+            assert!(
+                HALF > 0 && HALF.is_multiple_of(4),
+                "HALF must be a non-zero multiple of 4"
+            )
+        };
+        Self {
+            halves: [[0; HALF]; 2],
+            dma: 0,
+            offset: 0,
+            ready: false,
+        }
+    }
+
+    /// The half the DMA should fill next.
+    pub fn dma_buffer(&mut self) -> &mut [u8; HALF] {
+        &mut self.halves[self.dma]
+    }
+
+    /// Hands the half the DMA just filled to the compressor and the other half to the DMA.
+    /// Fails with `Overrun`, changing nothing, if the compressor half is not done yet.
+    pub fn swap(&mut self) -> Result<(), Error> {
+        if self.ready {
+            return Err(Error::Overrun);
+        }
+        self.dma ^= 1;
+        self.offset = 0;
+        self.ready = true;
+        Ok(())
+    }
+
+    /// Returns true if a filled half is waiting to be compressed.
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Compresses the rest of the filled half into `output`, returning the bytes written.
+    /// Stops once `output` has fewer than 8 bytes free; call again with fresh output while
+    /// `is_ready()`. Returns 0 when nothing is waiting.
+    pub fn compress<const N: usize>(
+        &mut self,
+        compressor: &mut Compressor<N>,
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        if !self.ready {
+            return Ok(0);
+        }
+        let half = &self.halves[self.dma ^ 1];
+        let mut written = 0;
+        while self.offset < HALF {
+            if compressor.is_full() {
+                if output.len() - written < MAX_POLL {
+                    break;
+                }
+                written += compressor.poll(&mut output[written..])?;
+            }
+            self.offset += compressor.sink(&half[self.offset..]);
+        }
+        self.ready = self.offset < HALF;
+        Ok(written)
+    }
+}
+
+impl<const HALF: usize> Default for DoubleBuffer<HALF> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::{Config, Decompressor};
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_double_buffer() {
+        let signal: [u8; 1024] = core::array::from_fn(|i| (i % 64 * 3 + i / 256) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut buffers = DoubleBuffer::<128>::new();
+        let mut stream = [0u8; 1024];
+        let mut len = 0;
+        for block in signal.chunks(128) {
+            buffers.dma_buffer().copy_from_slice(block);
+            buffers.swap().unwrap();
+            assert!(matches!(buffers.swap(), Err(Error::Overrun)));
+            // Small packets force partial consumption of the half
+            while buffers.is_ready() {
+                let end = (len + 24).min(stream.len());
+                len += buffers.compress(&mut compressor, &mut stream[len..end]).unwrap();
+            }
+        }
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 1024];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &signal[..]);
+    }
+}
@@ -0,0 +1,383 @@
+//! Compressor and decompressor whose window size is only known at runtime.
+//!
+//! `Compressor<N>` fixes the window size in the type. Libraries that accept whatever window
+//! the application hands them, and bindings that take the size from a caller or a stream
+//! header, can use these instead. Both dispatch to a `Compressor<N>` / `Decompressor<N>`
+//! keeping its window in a borrowed slice, whose length is checked against the configuration
+//! when the value is created, or with `alloc` on the heap.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::window::WindowStorage;
+use crate::{Config, Error, Sink};
+
+/// The window of a `Dyn*` codec.
+enum DynWindow<'w, const N: usize> {
+    Borrowed(&'w mut [u8; N]),
+    #[cfg(feature = "alloc")]
+    Boxed(Box<[u8; N]>),
+}
+
+impl<const N: usize> WindowStorage<N> for DynWindow<'_, N> {
+    fn window(&self) -> &[u8; N] {
+        match self {
+            Self::Borrowed(window) => window,
+            #[cfg(feature = "alloc")]
+            Self::Boxed(window) => window,
+        }
+    }
+
+    fn window_mut(&mut self) -> &mut [u8; N] {
+        match self {
+            Self::Borrowed(window) => window,
+            #[cfg(feature = "alloc")]
+            Self::Boxed(window) => window,
+        }
+    }
+}
+
+/// Where a `Dyn*` codec gets its window from, before the size is known.
+enum WindowSource<'w> {
+    Borrowed(&'w mut [u8]),
+    #[cfg(feature = "alloc")]
+    Boxed,
+}
+
+impl<'w> WindowSource<'w> {
+    /// The window for `N` bytes; a borrowed slice must have exactly that length.
+    fn take<const N: usize>(self) -> Result<DynWindow<'w, N>, Error> {
+        match self {
+            Self::Borrowed(window) => window
+                .try_into()
+                .map(DynWindow::Borrowed)
+                .map_err(|_| Error::InvalidConfig("Window length must equal 2^window_bits")),
+            // SAFETY: zeroed bytes are a valid window, and the codec clears it anyway.
+            #[cfg(feature = "alloc")]
+            Self::Boxed => Ok(DynWindow::Boxed(unsafe {
+                Box::<[u8; N]>::new_zeroed().assume_init()
+            })),
+        }
+    }
+}
+
+/// Declares the codec for every window size, the constructors picking one by `window_bits`
+/// and the RAM figures. `dispatch!` runs an expression on whichever codec a value holds.
+macro_rules! window_sizes {
+    ($($bits:literal => $variant:ident($n:literal)),* $(,)?) => {
+        #[cfg(feature = "compressor")]
+        enum AnyCompressor<'w> {
+            $($variant(Compressor<$n, DynWindow<'w, $n>>),)*
+        }
+
+        #[cfg(feature = "compressor")]
+        impl<'w> AnyCompressor<'w> {
+            fn new(
+                config: Config,
+                window: WindowSource<'w>,
+                dictionary: Option<&[u8]>,
+            ) -> Result<Self, Error> {
+                match config.window_bits {
+                    $($bits => Ok(Self::$variant(Compressor::with_storage_dictionary(
+                        config,
+                        window.take::<$n>()?,
+                        dictionary,
+                    )?)),)*
+                    _ => Err(Error::InvalidConfig("Window bits must be 8-15")),
+                }
+            }
+
+            fn memory_bytes(window_bits: u8) -> Result<usize, Error> {
+                match window_bits {
+                    $($bits => Ok(Compressor::<$n>::MEMORY_BYTES),)*
+                    _ => Err(Error::InvalidConfig("Window bits must be 8-15")),
+                }
+            }
+        }
+
+        #[cfg(feature = "decompressor")]
+        enum AnyDecompressor<'w> {
+            $($variant(Decompressor<$n, DynWindow<'w, $n>>),)*
+        }
+
+        #[cfg(feature = "decompressor")]
+        impl<'w> AnyDecompressor<'w> {
+            fn new(
+                config: Config,
+                window: WindowSource<'w>,
+                dictionary: Option<&[u8]>,
+            ) -> Result<Self, Error> {
+                match config.window_bits {
+                    $($bits => Ok(Self::$variant(Decompressor::with_storage_dictionary(
+                        config,
+                        window.take::<$n>()?,
+                        dictionary,
+                    )?)),)*
+                    _ => Err(Error::InvalidConfig("Window bits must be 8-15")),
+                }
+            }
+
+            fn memory_bytes(window_bits: u8) -> Result<usize, Error> {
+                match window_bits {
+                    $($bits => Ok(Decompressor::<$n>::MEMORY_BYTES),)*
+                    _ => Err(Error::InvalidConfig("Window bits must be 8-15")),
+                }
+            }
+        }
+
+        macro_rules! dispatch {
+            ($any:ident, $value:expr, $codec:ident => $body:expr) => {
+                match $value {
+                    $($any::$variant($codec) => $body,)*
+                }
+            };
+        }
+    };
+}
+
+window_sizes! {
+    8 => W256(256),
+    9 => W512(512),
+    10 => W1K(1024),
+    11 => W2K(2048),
+    12 => W4K(4096),
+    13 => W8K(8192),
+    14 => W16K(16384),
+    15 => W32K(32768),
+}
+
+/// Streaming compressor whose window size is only known at runtime.
+///
+/// Behaves like `Compressor<N>` with `N == 2^config.window_bits`.
+#[cfg(feature = "compressor")]
+pub struct DynCompressor<'w> {
+    inner: AnyCompressor<'w>,
+}
+
+#[cfg(feature = "compressor")]
+impl<'w> DynCompressor<'w> {
+    /// Creates a compressor using `window` as its buffer.
+    /// `window.len()` must equal 2^config.window_bits.
+    pub fn new(config: Config, window: &'w mut [u8]) -> Result<Self, Error> {
+        let inner = AnyCompressor::new(config, WindowSource::Borrowed(window), None)?;
+        Ok(Self { inner })
+    }
+
+    /// Creates a compressor with optional dictionary initialization, see
+    /// `Compressor::with_dictionary`.
+    #[cfg(feature = "dictionary")]
+    pub fn with_dictionary(
+        config: Config,
+        window: &'w mut [u8],
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let inner = AnyCompressor::new(config, WindowSource::Borrowed(window), dictionary)?;
+        Ok(Self { inner })
+    }
+
+    /// RAM held by a `Compressor<N>` for a window of `window_bits`, see
+    /// `Compressor::MEMORY_BYTES`.
+    pub fn memory_bytes(window_bits: u8) -> Result<usize, Error> {
+        AnyCompressor::memory_bytes(window_bits)
+    }
+
+    /// Compresses input data into output buffer, see `Compressor::compress_chunk`.
+    /// Returns (input_consumed, output_written).
+    pub fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.compress_chunk(input, output))
+    }
+
+    /// Compresses `input` into `sink`, see `Compressor::compress_into`.
+    /// Returns input bytes consumed.
+    pub fn compress_into(&mut self, input: &[u8], sink: &mut impl Sink) -> Result<usize, Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.compress_into(input, sink))
+    }
+
+    /// Low-level: adds input to the internal buffer, see `Compressor::sink`.
+    pub fn sink(&mut self, input: &[u8]) -> usize {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.sink(input))
+    }
+
+    /// Low-level: polls internal buffer for compressed data, see `Compressor::poll`.
+    pub fn poll(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.poll(output))
+    }
+
+    /// Returns true if internal input buffer is full (16 bytes).
+    pub fn is_full(&self) -> bool {
+        dispatch!(AnyCompressor, &self.inner, c => c.is_full())
+    }
+
+    /// Sinks all of `input`, handing polled output to `out`, see `Compressor::sink_all`.
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) fn sink_all(
+        &mut self,
+        input: &[u8],
+        buf: &mut [u8],
+        out: &mut impl FnMut(&[u8]),
+    ) -> Result<usize, Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.sink_all(input, buf, out))
+    }
+
+    /// Flushes remaining data from internal buffers, see `Compressor::flush`.
+    pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.flush(output, write_token))
+    }
+
+    /// Flushes into `sink`, see `Compressor::flush_into`.
+    pub fn flush_into(&mut self, sink: &mut impl Sink, write_token: bool) -> Result<(), Error> {
+        dispatch!(AnyCompressor, &mut self.inner, c => c.flush_into(sink, write_token))
+    }
+}
+
+#[cfg(all(feature = "compressor", feature = "alloc"))]
+impl DynCompressor<'static> {
+    /// Creates a compressor with its window on the heap, for bindings that take the window
+    /// size from their caller.
+    pub fn allocate(config: Config, dictionary: Option<&[u8]>) -> Result<Self, Error> {
+        let inner = AnyCompressor::new(config, WindowSource::Boxed, dictionary)?;
+        Ok(Self { inner })
+    }
+}
+
+/// Streaming decompressor whose window size is only known at runtime.
+///
+/// Behaves like `Decompressor<N>` with `N == 2^config.window_bits`.
+#[cfg(feature = "decompressor")]
+pub struct DynDecompressor<'w> {
+    inner: AnyDecompressor<'w>,
+}
+
+#[cfg(feature = "decompressor")]
+impl<'w> DynDecompressor<'w> {
+    /// Creates a decompressor using `window` as its buffer.
+    /// `window.len()` must equal 2^config.window_bits.
+    pub fn new(config: Config, window: &'w mut [u8]) -> Result<Self, Error> {
+        let inner = AnyDecompressor::new(config, WindowSource::Borrowed(window), None)?;
+        Ok(Self { inner })
+    }
+
+    /// Creates a decompressor with optional dictionary initialization.
+    /// Dictionary must match the one used during compression.
+    #[cfg(feature = "dictionary")]
+    pub fn with_dictionary(
+        config: Config,
+        window: &'w mut [u8],
+        dictionary: Option<&[u8]>,
+    ) -> Result<Self, Error> {
+        let inner = AnyDecompressor::new(config, WindowSource::Borrowed(window), dictionary)?;
+        Ok(Self { inner })
+    }
+
+    /// RAM held by a `Decompressor<N>` for a window of `window_bits`, see
+    /// `Decompressor::MEMORY_BYTES`.
+    pub fn memory_bytes(window_bits: u8) -> Result<usize, Error> {
+        AnyDecompressor::memory_bytes(window_bits)
+    }
+
+    /// Creates decompressor by reading configuration from compressed stream header.
+    /// `window.len()` must match the window size found in the header.
+    /// Returns (decompressor, bytes_consumed_from_input).
+    pub fn from_header(input: &[u8], window: &'w mut [u8]) -> Result<(Self, usize), Error> {
+        let (config, input_consumed) = Config::from_header(input)?;
+        Ok((Self::new(config, window)?, input_consumed))
+    }
+
+    /// Decompresses input data into output buffer, see `Decompressor::decompress_chunk`.
+    /// Returns (input_consumed, output_written).
+    pub fn decompress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        dispatch!(AnyDecompressor, &mut self.inner, d => d.decompress_chunk(input, output))
+    }
+
+    /// Decompresses `input` into `sink`, see `Decompressor::decompress_into`.
+    /// Returns input bytes consumed.
+    pub fn decompress_into(&mut self, input: &[u8], sink: &mut impl Sink) -> Result<usize, Error> {
+        dispatch!(AnyDecompressor, &mut self.inner, d => d.decompress_into(input, sink))
+    }
+}
+
+#[cfg(all(feature = "decompressor", feature = "alloc"))]
+impl DynDecompressor<'static> {
+    /// Creates a decompressor with its window on the heap. The dictionary must match the
+    /// one used during compression.
+    pub fn allocate(config: Config, dictionary: Option<&[u8]>) -> Result<Self, Error> {
+        let inner = AnyDecompressor::new(config, WindowSource::Boxed, dictionary)?;
+        Ok(Self { inner })
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::Decompressor;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_dyn_round_trip() {
+        let data: [u8; 1000] = core::array::from_fn(|i| b"window "[i % 7] ^ (i / 300) as u8);
+        let mut window = [0u8; 512];
+        let config = Config::new().window_bits(9).unwrap();
+        let mut compressor = DynCompressor::new(config, &mut window).unwrap();
+        let mut compressed = [0u8; 1024];
+        let (consumed, mut len) = compressor.compress_chunk(&data, &mut compressed).unwrap();
+        assert_eq!(consumed, data.len());
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<512>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 1000];
+        let (_, n) = decompressor
+            .decompress_chunk(&compressed[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &data[..]);
+
+        let mut window = [0u8; 512];
+        let (mut decompressor, header) =
+            DynDecompressor::from_header(&compressed, &mut window).unwrap();
+        let (_, n) = decompressor
+            .decompress_chunk(&compressed[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &data[..]);
+
+        let mut small = [0u8; 256];
+        assert!(matches!(
+            DynDecompressor::from_header(&compressed, &mut small),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_dyn_allocate() {
+        extern crate alloc;
+        use alloc::vec::Vec;
+
+        let data = b"allocated allocated allocated window".repeat(40);
+        let config = Config::new().window_bits(12).unwrap();
+        let mut compressor = DynCompressor::allocate(config, None).unwrap();
+        let mut stream = Vec::new();
+        assert_eq!(compressor.compress_into(&data, &mut stream).unwrap(), data.len());
+        compressor.flush_into(&mut stream, false).unwrap();
+
+        let (config, header) = Config::from_header(&stream).unwrap();
+        let mut decompressor = DynDecompressor::allocate(config, None).unwrap();
+        let mut output = Vec::new();
+        decompressor.decompress_into(&stream[header..], &mut output).unwrap();
+        assert_eq!(output, data);
+
+        assert_eq!(DynCompressor::memory_bytes(12).unwrap(), Compressor::<4096>::MEMORY_BYTES);
+        assert_eq!(DynDecompressor::memory_bytes(8).unwrap(), Decompressor::<256>::MEMORY_BYTES);
+        assert!(DynCompressor::memory_bytes(16).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Shared plumbing for the filesystem adapters.
+//!
+//! Compressed output is handed to the file in fixed `C` byte chunks, and reads take the
+//! header from the start of the file before decompressing the rest. The filesystem itself
+//! is reached through closures, so each adapter only maps its own read and write calls.
+
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Compressor, Decompressor, Error};
+
+/// Errors from the filesystem adapters.
+#[derive(Debug)]
+pub enum FileError<E> {
+    /// Compression or decompression failed.
+    Tamp(Error),
+    /// The filesystem reported an error.
+    Io(E),
+}
+
+impl<E> From<Error> for FileError<E> {
+    fn from(error: Error) -> Self {
+        Self::Tamp(error)
+    }
+}
+
+/// Compressor appending its output to a file in `C` byte chunks.
+pub(crate) struct ChunkWriter<const N: usize, const C: usize> {
+    compressor: Compressor<N>,
+    chunks: ChunkBuffer<C>,
+}
+
+impl<const N: usize, const C: usize> ChunkWriter<N, C> {
+    pub(crate) fn new(compressor: Compressor<N>) -> Self {
+        Self {
+            compressor,
+            chunks: ChunkBuffer {
+                chunk: [0; C],
+                filled: 0,
+                len: 0,
+            },
+        }
+    }
+
+    /// Compresses `data`, passing each full chunk to `put`.
+    pub(crate) fn write<E>(
+        &mut self,
+        data: &[u8],
+        put: &mut impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), FileError<E>> {
+        let mut out = [0u8; MAX_POLL];
+        self.compressor
+            .try_sink_all(data, &mut out, &mut |bytes| self.chunks.append(bytes, put))?;
+        Ok(())
+    }
+
+    /// Flushes the compressor and passes the buffered chunk, even if partial, to `put`.
+    /// `write_token`: true to keep writing afterwards, false to finish the stream.
+    /// Returns the compressed length written so far.
+    pub(crate) fn flush<E>(
+        &mut self,
+        write_token: bool,
+        put: &mut impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<usize, FileError<E>> {
+        let mut out = [0u8; MAX_FLUSH];
+        let n = self
+            .compressor
+            .flush(&mut out, write_token)
+            .map_err(FileError::Tamp)?;
+        let chunks = &mut self.chunks;
+        chunks.append(&out[..n], put)?;
+        if chunks.filled > 0 {
+            put(&chunks.chunk[..chunks.filled]).map_err(FileError::Io)?;
+            chunks.filled = 0;
+        }
+        Ok(chunks.len)
+    }
+}
+
+/// The chunk a `ChunkWriter` fills before passing it to the file.
+struct ChunkBuffer<const C: usize> {
+    chunk: [u8; C],
+    filled: usize,
+    len: usize,
+}
+
+impl<const C: usize> ChunkBuffer<C> {
+    fn append<E>(
+        &mut self,
+        mut bytes: &[u8],
+        put: &mut impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), FileError<E>> {
+        self.len += bytes.len();
+        while !bytes.is_empty() {
+            let n = bytes.len().min(C - self.filled);
+            self.chunk[self.filled..self.filled + n].copy_from_slice(&bytes[..n]);
+            self.filled += n;
+            bytes = &bytes[n..];
+            if self.filled == C {
+                put(&self.chunk).map_err(FileError::Io)?;
+                self.filled = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decompressor reading a file in `C` byte chunks.
+pub(crate) struct ChunkReader<const N: usize, const C: usize> {
+    decompressor: Decompressor<N>,
+    input: [u8; C],
+    start: usize,
+    end: usize,
+}
+
+impl<const N: usize, const C: usize> ChunkReader<N, C> {
+    /// Reads the first chunk with `get` and sets up a decompressor from its header.
+    /// An empty file is reported as `InputExhausted`.
+    pub(crate) fn open<E>(
+        get: &mut impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<Self, FileError<E>> {
+        let mut input = [0u8; C];
+        let end = get(&mut input).map_err(FileError::Io)?;
+        if end == 0 {
+            return Err(FileError::Tamp(Error::InputExhausted));
+        }
+        let (decompressor, start) =
+            Decompressor::from_header(&input[..end]).map_err(FileError::Tamp)?;
+        Ok(Self {
+            decompressor,
+            input,
+            start,
+            end,
+        })
+    }
+
+    /// Decompresses into `output`, reading more of the file with `get` as needed.
+    /// Returns the bytes written, 0 once the file is exhausted.
+    pub(crate) fn read<E>(
+        &mut self,
+        output: &mut [u8],
+        get: &mut impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, FileError<E>> {
+        if output.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let mut eof = false;
+            if self.start == self.end {
+                self.end = get(&mut self.input).map_err(FileError::Io)?;
+                self.start = 0;
+                eof = self.end == 0;
+            }
+            let (consumed, written) = self
+                .decompressor
+                .decompress_chunk(&self.input[self.start..self.end], output)
+                .map_err(FileError::Tamp)?;
+            self.start += consumed;
+            if written > 0 {
+                return Ok(written);
+            }
+            if eof || (consumed == 0 && self.start < self.end) {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Config;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_chunked_file_round_trip() {
+        let data: [u8; 4000] =
+            core::array::from_fn(|i| b"12.5,13.1,ok\n"[i % 13] ^ (i / 900) as u8);
+        let compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut writer = ChunkWriter::<256, 64>::new(compressor);
+        let mut file = Vec::new();
+        let mut writes = 0;
+        let mut put = |chunk: &[u8]| -> Result<(), ()> {
+            file.extend_from_slice(chunk);
+            writes += 1;
+            Ok(())
+        };
+        for record in data.chunks(130) {
+            writer.write(record, &mut put).unwrap();
+        }
+        let len = writer.flush(false, &mut put).unwrap();
+        assert_eq!(writes, len.div_ceil(64));
+        assert_eq!(file.len(), len);
+
+        let mut pos = 0;
+        let mut get = |buf: &mut [u8]| -> Result<usize, ()> {
+            let n = buf.len().min(file.len() - pos);
+            buf[..n].copy_from_slice(&file[pos..pos + n]);
+            pos += n;
+            Ok(n)
+        };
+        let mut reader = ChunkReader::<256, 64>::open(&mut get).unwrap();
+        let mut output = Vec::new();
+        let mut buf = [0u8; 100];
+        loop {
+            let n = reader.read(&mut buf, &mut get).unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(output, data);
+    }
+}
@@ -0,0 +1,671 @@
+//! Reversible preprocessing filters applied in front of the compressor.
+//!
+//! Sensor data compresses far better after a delta or XOR pass. Filters are streaming and
+//! stateful: encode the input before compressing, and run `decode` with a freshly created
+//! filter of the same kind on the decompressor output.
+//!
+//! ```ignore
+//! let mut filter = Delta::new(2)?.chain(BitPack::new(4)?);
+//! let (consumed, written) = filter.encode(samples, &mut staged);
+//! compressor.compress_chunk(&staged[..written], &mut output)?;
+//! ```
+
+use crate::Error;
+
+/// A reversible streaming byte transform.
+pub trait Filter {
+    /// Transforms as much of `input` as fits into `output`.
+    /// Returns (input_consumed, output_written).
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize);
+
+    /// Reverses `encode`. Returns (input_consumed, output_written).
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize);
+
+    /// Writes any state still buffered by `encode` at the end of the stream.
+    /// Returns bytes written; call again while it fills `output` completely.
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        let _ = output;
+        0
+    }
+
+    /// Runs `next` on the output of this filter.
+    fn chain<B: Filter>(self, next: B) -> Chain<Self, B>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, next)
+    }
+}
+
+/// Replaces each byte with its difference from the byte `stride` positions earlier.
+/// Use the sample width as stride, e.g. 2 for little-endian `u16` samples.
+pub struct Delta {
+    history: [u8; 8],
+    stride: usize,
+    pos: usize,
+}
+
+impl Delta {
+    /// Creates a delta filter. `stride` must be between 1 and 8.
+    pub fn new(stride: usize) -> Result<Self, Error> {
+        if !(1..=8).contains(&stride) {
+            return Err(Error::InvalidConfig("stride must be between 1 and 8"));
+        }
+        Ok(Self {
+            history: [0; 8],
+            stride,
+            pos: 0,
+        })
+    }
+}
+
+impl Filter for Delta {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let n = input.len().min(output.len());
+        for (out, &byte) in output.iter_mut().zip(&input[..n]) {
+            *out = byte.wrapping_sub(self.history[self.pos]);
+            self.history[self.pos] = byte;
+            self.pos = (self.pos + 1) % self.stride;
+        }
+        (n, n)
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let n = input.len().min(output.len());
+        for (out, &byte) in output.iter_mut().zip(&input[..n]) {
+            *out = byte.wrapping_add(self.history[self.pos]);
+            self.history[self.pos] = *out;
+            self.pos = (self.pos + 1) % self.stride;
+        }
+        (n, n)
+    }
+}
+
+/// XORs each byte with the byte `stride` positions earlier.
+/// Suits flag words and floats whose high bytes rarely change.
+pub struct XorPrev {
+    history: [u8; 8],
+    stride: usize,
+    pos: usize,
+}
+
+impl XorPrev {
+    /// Creates an XOR filter. `stride` must be between 1 and 8.
+    pub fn new(stride: usize) -> Result<Self, Error> {
+        if !(1..=8).contains(&stride) {
+            return Err(Error::InvalidConfig("stride must be between 1 and 8"));
+        }
+        Ok(Self {
+            history: [0; 8],
+            stride,
+            pos: 0,
+        })
+    }
+}
+
+impl Filter for XorPrev {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let n = input.len().min(output.len());
+        for (out, &byte) in output.iter_mut().zip(&input[..n]) {
+            *out = byte ^ self.history[self.pos];
+            self.history[self.pos] = byte;
+            self.pos = (self.pos + 1) % self.stride;
+        }
+        (n, n)
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let n = input.len().min(output.len());
+        for (out, &byte) in output.iter_mut().zip(&input[..n]) {
+            *out = byte ^ self.history[self.pos];
+            self.history[self.pos] = *out;
+            self.pos = (self.pos + 1) % self.stride;
+        }
+        (n, n)
+    }
+}
+
+/// Packs the low `bits` bits of each byte densely, MSB first.
+///
+/// The final partial byte is zero padded by `finish()`, so decoding may yield up to
+/// `7 / bits` trailing zero values; keep stream lengths a multiple of 8 to avoid them.
+pub struct BitPack {
+    bits: u32,
+    acc: u32,
+    acc_bits: u32,
+}
+
+impl BitPack {
+    /// Creates a bit-packing filter. `bits` must be between 1 and 8.
+    pub fn new(bits: u8) -> Result<Self, Error> {
+        if !(1..=8).contains(&bits) {
+            return Err(Error::InvalidConfig("bits must be between 1 and 8"));
+        }
+        Ok(Self {
+            bits: bits as u32,
+            acc: 0,
+            acc_bits: 0,
+        })
+    }
+}
+
+impl Filter for BitPack {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.acc_bits >= 8 {
+                let Some(out) = output.get_mut(written) else {
+                    break;
+                };
+                self.acc_bits -= 8;
+                *out = (self.acc >> self.acc_bits) as u8;
+                written += 1;
+            } else if let Some(&byte) = input.get(consumed) {
+                let mask = (1u32 << self.bits) - 1;
+                self.acc = (self.acc << self.bits) | (byte as u32 & mask);
+                self.acc_bits += self.bits;
+                consumed += 1;
+            } else {
+                break;
+            }
+        }
+        (consumed, written)
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.acc_bits >= self.bits {
+                let Some(out) = output.get_mut(written) else {
+                    break;
+                };
+                self.acc_bits -= self.bits;
+                *out = ((self.acc >> self.acc_bits) & ((1 << self.bits) - 1)) as u8;
+                written += 1;
+            } else if let Some(&byte) = input.get(consumed) {
+                self.acc = (self.acc << 8) | byte as u32;
+                self.acc_bits += 8;
+                consumed += 1;
+            } else {
+                break;
+            }
+        }
+        (consumed, written)
+    }
+
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        let (_, mut written) = self.encode(&[], output);
+        if self.acc_bits > 0 && written < output.len() {
+            output[written] = (self.acc << (8 - self.acc_bits)) as u8;
+            self.acc_bits = 0;
+            written += 1;
+        }
+        written
+    }
+}
+
+/// Replaces little-endian `i32` or `i64` samples with the zig-zag varint of their difference
+/// from the previous sample.
+///
+/// Counters and timestamps advance by small steps, so most samples shrink to one or two bytes
+/// that tamp then finds matches in. Differences wrap like the sample type does. Input should
+/// be a whole number of samples; a trailing partial sample is never encoded.
+pub struct VarintDelta {
+    width: usize,
+    prev: i64,
+    sample: [u8; 8],
+    filled: usize,
+    acc: u64,
+    shift: u32,
+    pending: [u8; 10],
+    start: usize,
+    end: usize,
+}
+
+impl VarintDelta {
+    /// Creates a filter for samples of `width` bytes, which must be 4 or 8.
+    pub fn new(width: usize) -> Result<Self, Error> {
+        if width != 4 && width != 8 {
+            return Err(Error::InvalidConfig("width must be 4 or 8"));
+        }
+        Ok(Self {
+            width,
+            prev: 0,
+            sample: [0; 8],
+            filled: 0,
+            acc: 0,
+            shift: 0,
+            pending: [0; 10],
+            start: 0,
+            end: 0,
+        })
+    }
+
+    /// Sign-extends the low `width` bytes of `value`.
+    fn wrap(&self, value: i64) -> i64 {
+        if self.width == 4 {
+            value as i32 as i64
+        } else {
+            value
+        }
+    }
+
+    /// Copies pending bytes into `output`. Returns bytes written.
+    fn drain(&mut self, output: &mut [u8]) -> usize {
+        let n = (self.end - self.start).min(output.len());
+        output[..n].copy_from_slice(&self.pending[self.start..self.start + n]);
+        self.start += n;
+        n
+    }
+}
+
+impl Filter for VarintDelta {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.start < self.end {
+                let n = self.drain(&mut output[written..]);
+                if n == 0 {
+                    break;
+                }
+                written += n;
+            } else if let Some(&byte) = input.get(consumed) {
+                consumed += 1;
+                self.sample[self.filled] = byte;
+                self.filled += 1;
+                if self.filled < self.width {
+                    continue;
+                }
+                self.filled = 0;
+                let sample = self.wrap(i64::from_le_bytes(self.sample));
+                let delta = self.wrap(sample.wrapping_sub(self.prev));
+                self.prev = sample;
+                let mut zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+                self.start = 0;
+                self.end = 0;
+                loop {
+                    let low = (zigzag & 0x7F) as u8;
+                    zigzag >>= 7;
+                    self.pending[self.end] = if zigzag == 0 { low } else { low | 0x80 };
+                    self.end += 1;
+                    if zigzag == 0 {
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        (consumed, written)
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.start < self.end {
+                let n = self.drain(&mut output[written..]);
+                if n == 0 {
+                    break;
+                }
+                written += n;
+            } else if let Some(&byte) = input.get(consumed) {
+                consumed += 1;
+                if self.shift < 64 {
+                    self.acc |= ((byte & 0x7F) as u64) << self.shift;
+                    self.shift += 7;
+                }
+                if byte & 0x80 != 0 {
+                    continue;
+                }
+                let delta = ((self.acc >> 1) as i64) ^ -((self.acc & 1) as i64);
+                self.acc = 0;
+                self.shift = 0;
+                self.prev = self.wrap(self.prev.wrapping_add(delta));
+                self.pending[..8].copy_from_slice(&self.prev.to_le_bytes());
+                self.start = 0;
+                self.end = self.width;
+            } else {
+                break;
+            }
+        }
+        (consumed, written)
+    }
+}
+
+/// Lossy: replaces little-endian `f32` samples with `i32` multiples of a quantization step.
+///
+/// For sensor values whose low mantissa bits are noise. Each sample decodes to within half a
+/// step of its input, as long as `input / step` fits in an `i32`; larger values saturate and
+/// NaN becomes 0. The encoded stream starts with the step as `f32` LE, so reconstruction uses
+/// the exact step of the encoder and any `Quantize` decodes it. Chain a `VarintDelta::new(4)`
+/// behind it for slowly changing values.
+#[cfg(feature = "lossy")]
+pub struct Quantize {
+    step: f32,
+    started: bool,
+    sample: [u8; 4],
+    filled: usize,
+    pending: [u8; 4],
+    start: usize,
+    end: usize,
+}
+
+#[cfg(feature = "lossy")]
+impl Quantize {
+    /// Creates a quantizer with resolution `step`, e.g. `0.01` to keep two decimals.
+    /// `step` must be finite and positive.
+    pub fn new(step: f32) -> Result<Self, Error> {
+        if !(step.is_finite() && step > 0.0) {
+            return Err(Error::InvalidConfig("step must be finite and positive"));
+        }
+        Ok(Self {
+            step,
+            started: false,
+            sample: [0; 4],
+            filled: 0,
+            pending: [0; 4],
+            start: 0,
+            end: 0,
+        })
+    }
+
+    /// The step in use; when decoding, the one read from the stream.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Copies pending bytes into `output`. Returns bytes written.
+    fn drain(&mut self, output: &mut [u8]) -> usize {
+        let n = (self.end - self.start).min(output.len());
+        output[..n].copy_from_slice(&self.pending[self.start..self.start + n]);
+        self.start += n;
+        n
+    }
+
+    /// Runs `convert` on every complete 4-byte word of `input`, draining results to `output`.
+    fn pump(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        convert: impl Fn(&mut Self, [u8; 4]) -> Option<[u8; 4]>,
+    ) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            if self.start < self.end {
+                let n = self.drain(&mut output[written..]);
+                if n == 0 {
+                    break;
+                }
+                written += n;
+            } else if let Some(&byte) = input.get(consumed) {
+                consumed += 1;
+                self.sample[self.filled] = byte;
+                self.filled += 1;
+                if self.filled == 4 {
+                    self.filled = 0;
+                    let sample = self.sample;
+                    if let Some(word) = convert(self, sample) {
+                        self.pending = word;
+                        self.start = 0;
+                        self.end = 4;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        (consumed, written)
+    }
+}
+
+#[cfg(feature = "lossy")]
+impl Filter for Quantize {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        if !self.started {
+            self.started = true;
+            self.pending = self.step.to_le_bytes();
+            self.start = 0;
+            self.end = 4;
+        }
+        self.pump(input, output, |q, word| {
+            let scaled = f32::from_le_bytes(word) / q.step;
+            // Round half away from zero; `as` saturates and maps NaN to 0
+            let rounded = if scaled < 0.0 { scaled - 0.5 } else { scaled + 0.5 };
+            Some((rounded as i32).to_le_bytes())
+        })
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        self.pump(input, output, |q, word| {
+            if !q.started {
+                q.started = true;
+                q.step = f32::from_le_bytes(word);
+                return None;
+            }
+            Some((i32::from_le_bytes(word) as f32 * q.step).to_le_bytes())
+        })
+    }
+
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        self.encode(&[], output).1
+    }
+}
+
+/// Two filters applied in sequence, created with `Filter::chain`.
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    buf: [u8; 32],
+    start: usize,
+    end: usize,
+}
+
+impl<A: Filter, B: Filter> Chain<A, B> {
+    fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            buf: [0; 32],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Moves buffered bytes through `stage` into `output`, refilling the buffer with `fill`.
+    fn pump(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        fill: impl Fn(&mut Self, &[u8]) -> usize,
+        stage: impl Fn(&mut Self, &mut [u8]) -> usize,
+    ) -> (usize, usize) {
+        let (mut consumed, mut written) = (0, 0);
+        loop {
+            let mut progress = false;
+            if self.start == self.end {
+                self.start = 0;
+                self.end = 0;
+                let n = fill(self, &input[consumed..]);
+                consumed += n;
+                progress = n > 0 || self.end > 0;
+            }
+            let start = self.start;
+            let n = stage(self, &mut output[written..]);
+            written += n;
+            if !progress && n == 0 && self.start == start {
+                return (consumed, written);
+            }
+        }
+    }
+}
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn encode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        self.pump(
+            input,
+            output,
+            |c, input| {
+                let (n, w) = c.first.encode(input, &mut c.buf);
+                c.end = w;
+                n
+            },
+            |c, output| {
+                let (n, w) = c.second.encode(&c.buf[c.start..c.end], output);
+                c.start += n;
+                w
+            },
+        )
+    }
+
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> (usize, usize) {
+        self.pump(
+            input,
+            output,
+            |c, input| {
+                let (n, w) = c.second.decode(input, &mut c.buf);
+                c.end = w;
+                n
+            },
+            |c, output| {
+                let (n, w) = c.first.decode(&c.buf[c.start..c.end], output);
+                c.start += n;
+                w
+            },
+        )
+    }
+
+    fn finish(&mut self, output: &mut [u8]) -> usize {
+        let (_, mut written) = self.encode(&[], output);
+        if self.start == self.end {
+            self.end = self.first.finish(&mut self.buf);
+            self.start = 0;
+            written += self.encode(&[], &mut output[written..]).1;
+        }
+        if self.start == self.end {
+            written += self.second.finish(&mut output[written..]);
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    fn run(filter: &mut impl Filter, input: &[u8], encode: bool) -> Vec<u8> {
+        let mut result = Vec::new();
+        let mut out = [0u8; 7];
+        for chunk in input.chunks(5) {
+            let mut chunk = chunk;
+            loop {
+                let (n, w) = if encode {
+                    filter.encode(chunk, &mut out)
+                } else {
+                    filter.decode(chunk, &mut out)
+                };
+                result.extend_from_slice(&out[..w]);
+                chunk = &chunk[n..];
+                if n == 0 && w == 0 {
+                    break;
+                }
+            }
+        }
+        if encode {
+            loop {
+                let w = filter.finish(&mut out);
+                result.extend_from_slice(&out[..w]);
+                if w < out.len() {
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_filter_chain_round_trip() {
+        // Slowly rising little-endian u16 samples
+        let samples: Vec<u8> = (0..64u16)
+            .flat_map(|i| (1000 + i * 3).to_le_bytes())
+            .collect();
+        let delta = || Delta::new(2).unwrap().chain(XorPrev::new(1).unwrap());
+        let encoded = run(&mut delta(), &samples, true);
+        assert_ne!(encoded, samples);
+        let decoded = run(&mut delta(), &encoded, false);
+        assert_eq!(decoded, samples);
+
+        let nibbles: Vec<u8> = (0..64u8).map(|i| i % 16).collect();
+        let pack = || XorPrev::new(1).unwrap().chain(BitPack::new(4).unwrap());
+        let packed = run(&mut pack(), &nibbles, true);
+        assert_eq!(packed.len(), 32);
+        let unpacked = run(&mut pack(), &packed, false);
+        assert_eq!(unpacked[..nibbles.len()], nibbles[..]);
+    }
+
+    #[test]
+    fn test_filter_invalid_parameters() {
+        assert!(matches!(Delta::new(0), Err(Error::InvalidConfig(_))));
+        assert!(matches!(XorPrev::new(9), Err(Error::InvalidConfig(_))));
+        assert!(matches!(BitPack::new(0), Err(Error::InvalidConfig(_))));
+        assert!(matches!(VarintDelta::new(2), Err(Error::InvalidConfig(_))));
+        #[cfg(feature = "lossy")]
+        for step in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(Quantize::new(step), Err(Error::InvalidConfig(_))));
+        }
+    }
+
+    #[test]
+    fn test_varint_delta() {
+        // Millisecond timestamps with jitter, then a clock step backwards
+        let mut stamps: Vec<i64> = (0..64).map(|i| 1_700_000_000_000 + i * 1000 + i % 3).collect();
+        stamps.push(1_600_000_000_000);
+        let input: Vec<u8> = stamps.iter().flat_map(|t| t.to_le_bytes()).collect();
+        let stamp = || VarintDelta::new(8).unwrap();
+        let encoded = run(&mut stamp(), &input, true);
+        assert!(encoded.len() < 64 * 2 + 16);
+        assert_eq!(run(&mut stamp(), &encoded, false), input);
+
+        // A 32-bit counter wrapping around, through a chain
+        let counter: Vec<u8> = (0..40u32)
+            .flat_map(|i| (u32::MAX - 20).wrapping_add(i * 7).to_le_bytes())
+            .collect();
+        let mut filter = VarintDelta::new(4).unwrap().chain(XorPrev::new(1).unwrap());
+        let encoded = run(&mut filter, &counter, true);
+        assert!(encoded.len() < counter.len() / 3);
+        let mut filter = VarintDelta::new(4).unwrap().chain(XorPrev::new(1).unwrap());
+        assert_eq!(run(&mut filter, &encoded, false), counter);
+    }
+
+    #[cfg(feature = "lossy")]
+    #[test]
+    fn test_quantize_within_tolerance() {
+        let readings: Vec<f32> = (0..100)
+            .map(|i| 21.5 + (i % 17) as f32 * 0.123 - i as f32 * 0.011)
+            .chain([-0.004, 1e12, f32::NAN])
+            .collect();
+        let input: Vec<u8> = readings.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let quantize = |step| {
+            Quantize::new(step)
+                .unwrap()
+                .chain(VarintDelta::new(4).unwrap())
+        };
+        let encoded = run(&mut quantize(0.01), &input, true);
+        assert!(encoded.len() < input.len() / 2);
+
+        let decoded = run(&mut quantize(1.0), &encoded, false);
+        assert_eq!(decoded.len(), input.len());
+        let decoded: Vec<f32> = decoded
+            .chunks(4)
+            .map(|w| f32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        for (value, decoded) in readings[..100].iter().zip(&decoded) {
+            let error = value - decoded;
+            assert!((-0.00501..=0.00501).contains(&error), "{value} vs {decoded}");
+        }
+        assert_eq!(decoded[100], 0.0);
+        assert_eq!(decoded[101], i32::MAX as f32 * 0.01);
+        assert_eq!(decoded[102], 0.0);
+    }
+}
@@ -0,0 +1,344 @@
+//! Adapters between tamp streams and `embedded-storage` NOR flash.
+//!
+//! Flash is written in 256-byte chunks, erasing each sector just before its first write and
+//! reading every chunk back to verify it. Regions must start on an erase sector boundary.
+
+use embedded_storage::nor_flash::NorFlash;
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "compressor")]
+use crate::compressor::MAX_POLL;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+
+/// Flash write chunk size. Must be a multiple of the flash write and read sizes.
+const CHUNK: usize = 256;
+
+/// Errors from the flash adapters.
+#[derive(Debug)]
+pub enum FlashError<E, R = core::convert::Infallible> {
+    /// Compression or decompression failed.
+    Tamp(Error),
+    /// The flash driver reported an error.
+    Flash(E),
+    /// Reading the input stream failed.
+    Read(R),
+    /// Base address or flash geometry is not compatible with 256-byte chunked writes.
+    Alignment,
+    /// Data read back after writing did not match, at the given flash offset.
+    Verify(u32),
+}
+
+impl<E, R> From<Error> for FlashError<E, R> {
+    fn from(error: Error) -> Self {
+        Self::Tamp(error)
+    }
+}
+
+/// Sequential writer over a flash region that erases ahead and verifies what it writes.
+pub(crate) struct FlashCursor {
+    addr: u32,
+    erased_end: u32,
+}
+
+impl FlashCursor {
+    pub(crate) fn new<F: NorFlash, R>(base_addr: u32) -> Result<Self, FlashError<F::Error, R>> {
+        if !CHUNK.is_multiple_of(F::WRITE_SIZE)
+            || !CHUNK.is_multiple_of(F::READ_SIZE)
+            || !(base_addr as usize).is_multiple_of(F::ERASE_SIZE)
+        {
+            return Err(FlashError::Alignment);
+        }
+        Ok(Self {
+            addr: base_addr,
+            erased_end: base_addr,
+        })
+    }
+
+    /// Next flash offset to be written.
+    pub(crate) fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// Writes up to `CHUNK` bytes, padding the tail with 0xFF to the write size.
+    pub(crate) fn write<F: NorFlash, R>(
+        &mut self,
+        flash: &mut F,
+        data: &[u8],
+    ) -> Result<(), FlashError<F::Error, R>> {
+        let mut chunk = [0xFF; CHUNK];
+        chunk[..data.len()].copy_from_slice(data);
+        let len = data.len().next_multiple_of(F::WRITE_SIZE);
+        let end = self.addr + len as u32;
+        while self.erased_end < end {
+            let next = self.erased_end + F::ERASE_SIZE as u32;
+            flash
+                .erase(self.erased_end, next)
+                .map_err(FlashError::Flash)?;
+            self.erased_end = next;
+        }
+        flash
+            .write(self.addr, &chunk[..len])
+            .map_err(FlashError::Flash)?;
+
+        let mut readback = [0u8; CHUNK];
+        let read_len = len.next_multiple_of(F::READ_SIZE);
+        flash
+            .read(self.addr, &mut readback[..read_len])
+            .map_err(FlashError::Flash)?;
+        if readback[..len] != chunk[..len] {
+            return Err(FlashError::Verify(self.addr));
+        }
+        self.addr = end;
+        Ok(())
+    }
+}
+
+/// Decompresses an OTA image from `reader` straight into flash starting at `base_addr`.
+///
+/// `decompressor` must already be configured (e.g. with `Decompressor::from_header`) and
+/// `reader` positioned after the header. `base_addr` must be sector aligned; sectors are
+/// erased as they are reached and the tail is padded with 0xFF to the write size.
+/// Returns the decompressed image length.
+#[cfg(feature = "decompressor")]
+pub fn decompress_to_flash<const N: usize, R: embedded_io::Read, F: NorFlash>(
+    decompressor: &mut Decompressor<N>,
+    reader: &mut R,
+    flash: &mut F,
+    base_addr: u32,
+) -> Result<usize, FlashError<F::Error, R::Error>> {
+    let mut cursor = FlashCursor::new::<F, R::Error>(base_addr)?;
+    let mut input = [0u8; 64];
+    let mut chunk = [0u8; CHUNK];
+    let mut filled = 0;
+    let mut total = 0;
+    loop {
+        let n = reader.read(&mut input).map_err(FlashError::Read)?;
+        let mut pending = &input[..n];
+        loop {
+            let (consumed, written) = decompressor
+                .decompress_chunk(pending, &mut chunk[filled..])
+                .map_err(FlashError::Tamp)?;
+            pending = &pending[consumed..];
+            filled += written;
+            total += written;
+            if filled == CHUNK {
+                cursor.write(flash, &chunk)?;
+                filled = 0;
+            } else if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+    if filled > 0 {
+        cursor.write(flash, &chunk[..filled])?;
+    }
+    Ok(total)
+}
+
+/// Compresses incoming data and appends it to a flash region page by page.
+///
+/// Only full 256-byte chunks reach flash until `close()`, which writes the terminating flush
+/// and pads the final chunk with 0xFF. Data that has not been closed is lost on reset.
+#[cfg(feature = "compressor")]
+pub struct FlashWriter<'a, const N: usize, F: NorFlash> {
+    compressor: &'a mut Compressor<N>,
+    pages: PageBuffer<'a, F>,
+}
+
+#[cfg(feature = "compressor")]
+impl<'a, const N: usize, F: NorFlash> FlashWriter<'a, N, F> {
+    /// Creates a writer appending to `flash` from the sector-aligned `base_addr`.
+    pub fn new(
+        compressor: &'a mut Compressor<N>,
+        flash: &'a mut F,
+        base_addr: u32,
+    ) -> Result<Self, FlashError<F::Error>> {
+        Ok(Self {
+            compressor,
+            pages: PageBuffer {
+                cursor: FlashCursor::new::<F, _>(base_addr)?,
+                flash,
+                page: [0; CHUNK],
+                filled: 0,
+            },
+        })
+    }
+
+    /// Compresses `data`, writing each completed chunk to flash.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), FlashError<F::Error>> {
+        let mut out = [0u8; MAX_POLL];
+        self.compressor
+            .try_sink_all(data, &mut out, &mut |bytes| self.pages.append(bytes))?;
+        Ok(())
+    }
+
+    /// Flash offset just past the compressed data produced so far, including the unwritten chunk.
+    pub fn position(&self) -> u32 {
+        self.pages.position()
+    }
+
+    /// Writes the terminating flush and the final padded chunk.
+    /// Returns the flash offset just past the compressed stream.
+    pub fn close(mut self) -> Result<u32, FlashError<F::Error>> {
+        let mut out = [0u8; 64];
+        let n = self
+            .compressor
+            .flush(&mut out, false)
+            .map_err(FlashError::Tamp)?;
+        self.pages.append(&out[..n])?;
+        self.pages.finish()
+    }
+}
+
+/// The chunk a `FlashWriter` collects before writing it to flash.
+#[cfg(feature = "compressor")]
+struct PageBuffer<'a, F: NorFlash> {
+    flash: &'a mut F,
+    cursor: FlashCursor,
+    page: [u8; CHUNK],
+    filled: usize,
+}
+
+#[cfg(feature = "compressor")]
+impl<F: NorFlash> PageBuffer<'_, F> {
+    fn position(&self) -> u32 {
+        self.cursor.addr() + self.filled as u32
+    }
+
+    fn append(&mut self, mut bytes: &[u8]) -> Result<(), FlashError<F::Error>> {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(CHUNK - self.filled);
+            self.page[self.filled..self.filled + n].copy_from_slice(&bytes[..n]);
+            self.filled += n;
+            bytes = &bytes[n..];
+            if self.filled == CHUNK {
+                self.cursor.write(self.flash, &self.page)?;
+                self.filled = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the partial last chunk. Returns the flash offset just past the data.
+    fn finish(mut self) -> Result<u32, FlashError<F::Error>> {
+        let end = self.position();
+        if self.filled > 0 {
+            self.cursor.write(self.flash, &self.page[..self.filled])?;
+        }
+        Ok(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    /// RAM-backed flash with 4-byte writes and 512-byte sectors.
+    struct RamFlash([u8; 4096]);
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 512;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.0
+                .get_mut(from as usize..to as usize)
+                .ok_or(NorFlashErrorKind::OutOfBounds)?
+                .fill(0xFF);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            let dst = self
+                .0
+                .get_mut(offset..offset + bytes.len())
+                .ok_or(NorFlashErrorKind::OutOfBounds)?;
+            // NOR can only clear bits
+            for (d, s) in dst.iter_mut().zip(bytes) {
+                *d &= s;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(all(feature = "compressor", feature = "decompressor"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_decompress_to_flash() {
+        use crate::{Compressor, Config};
+
+        let image: [u8; 1500] = core::array::from_fn(|i| (i % 97) as u8);
+        let mut compressed = [0u8; 4096];
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let (consumed, mut len) = compressor.compress_chunk(&image, &mut compressed).unwrap();
+        assert_eq!(consumed, image.len());
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let mut flash = RamFlash([0; 4096]);
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut reader = &compressed[header..len];
+        let written = decompress_to_flash(&mut decompressor, &mut reader, &mut flash, 512).unwrap();
+        assert_eq!(written, image.len());
+        assert_eq!(&flash.0[512..512 + image.len()], &image[..]);
+        assert!(
+            flash.0[512 + image.len()..1536 + 512]
+                .iter()
+                .all(|&b| b == 0xFF)
+        );
+
+        assert!(matches!(
+            decompress_to_flash(&mut decompressor, &mut reader, &mut flash, 100),
+            Err(FlashError::Alignment)
+        ));
+    }
+
+    #[cfg(all(feature = "compressor", feature = "decompressor"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_flash_writer_round_trip() {
+        use crate::{Compressor, Config};
+
+        let samples: [u8; 3000] = core::array::from_fn(|i| (i / 7 % 50) as u8);
+        let mut flash = RamFlash([0; 4096]);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut writer = FlashWriter::new(&mut compressor, &mut flash, 0).unwrap();
+        for chunk in samples.chunks(100) {
+            writer.write(chunk).unwrap();
+        }
+        let end = writer.close().unwrap() as usize;
+        assert!(end < samples.len());
+
+        let compressed = flash.0;
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 3000];
+        let (_, written) = decompressor
+            .decompress_chunk(&compressed[header..end], &mut output)
+            .unwrap();
+        assert_eq!(&output[..written], &samples[..]);
+    }
+}
@@ -0,0 +1,134 @@
+use core::fmt;
+use crate::staging::Staging;
+use crate::{Compressor, Error};
+
+/// `core::fmt::Write` adapter that compresses formatted text on the fly.
+///
+/// Compressed bytes are passed to `sink` in chunks of at most 64 bytes, so `write!` output
+/// never needs a temporary formatting buffer. Call `finish()` to terminate the stream.
+///
+/// ```ignore
+/// let mut writer = CompressingFmtWriter::new(&mut compressor, |bytes| uart.write(bytes));
+/// writeln!(writer, "t={},v={}", t, v)?;
+/// writer.finish()?;
+/// ```
+pub struct CompressingFmtWriter<'a, const N: usize, F: FnMut(&[u8])> {
+    compressor: &'a mut Compressor<N>,
+    sink: F,
+    staging: Staging,
+    error: Option<Error>,
+}
+
+impl<'a, const N: usize, F: FnMut(&[u8])> CompressingFmtWriter<'a, N, F> {
+    /// Creates a writer feeding `compressor`, with compressed output passed to `sink`.
+    pub fn new(compressor: &'a mut Compressor<N>, sink: F) -> Self {
+        Self {
+            compressor,
+            sink,
+            staging: Staging::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the compression error behind the last `fmt::Error`, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Pushes all text written so far to the sink, keeping the stream open.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.staging.flush(self.compressor, true, &mut self.sink)
+    }
+
+    /// Performs the final flush, terminating the compressed stream.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.staging.flush(self.compressor, false, &mut self.sink)
+    }
+}
+
+impl<const N: usize, F: FnMut(&[u8])> fmt::Write for CompressingFmtWriter<'_, N, F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.staging
+            .write(self.compressor, s.as_bytes(), &mut self.sink)
+            .map_err(|e| {
+                self.error = Some(e);
+                fmt::Error
+            })
+    }
+}
+
+#[cfg(feature = "acid_io")]
+impl<const N: usize, F: FnMut(&[u8])> acid_io::Write for CompressingFmtWriter<'_, N, F> {
+    /// Compresses all of `buf`, for binary data or `acid_io` based serializers.
+    fn write(&mut self, buf: &[u8]) -> acid_io::Result<usize> {
+        match self.staging.write(self.compressor, buf, &mut self.sink) {
+            Ok(()) => Ok(buf.len()),
+            Err(e) => {
+                self.error = Some(e);
+                Err(acid_io::ErrorKind::Other.into())
+            }
+        }
+    }
+
+    /// Same as `CompressingFmtWriter::flush()`; the cause of an error is kept for
+    /// `take_error()`.
+    fn flush(&mut self) -> acid_io::Result<()> {
+        CompressingFmtWriter::flush(self).map_err(|e| {
+            self.error = Some(e);
+            acid_io::ErrorKind::Other.into()
+        })
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{Config, Decompressor};
+    use core::fmt::Write;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_fmt_writer_round_trip() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let mut writer =
+            CompressingFmtWriter::new(&mut compressor, |b| compressed.extend_from_slice(b));
+        let mut expected = std::string::String::new();
+        for t in 0..200 {
+            writeln!(writer, "t={},v={}", t, t * 3).unwrap();
+            writeln!(expected, "t={},v={}", t, t * 3).unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(compressed.len() < expected.len());
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = std::vec![0u8; expected.len()];
+        let (_, written) = decompressor
+            .decompress_chunk(&compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(&output[..written], expected.as_bytes());
+    }
+
+    #[cfg(feature = "acid_io")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_acid_io_write() {
+        let data: [u8; 500] = core::array::from_fn(|i| (i % 13) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let mut writer =
+            CompressingFmtWriter::new(&mut compressor, |b| compressed.extend_from_slice(b));
+        acid_io::Write::write_all(&mut writer, &data).unwrap();
+        acid_io::Write::flush(&mut writer).unwrap();
+        writer.finish().unwrap();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 500];
+        let (_, written) = decompressor
+            .decompress_chunk(&compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(&output[..written], &data[..]);
+    }
+}
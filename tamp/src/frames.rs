@@ -0,0 +1,264 @@
+//! Splitting a compressed stream into MTU-sized frames and joining them back.
+//!
+//! Each frame starts with a one-byte header: a 6-bit sequence number counting up from 0
+//! and wrapping, with `LAST` set on the final frame. The receiver checks the sequence, so a
+//! lost or reordered frame is reported instead of silently corrupting the stream.
+//!
+//! A final frame with `LENGTH` set ends with the uncompressed length of the stream as a u64
+//! LE. Checking it after decompression catches a stream cut short at a flush boundary, which
+//! otherwise decodes to a short but plausible payload.
+//!
+//! ```ignore
+//! let mut splitter = FrameSplitter::<64, _>::new(|frame| radio.send(frame));
+//! let mut writer = CompressingFmtWriter::new(&mut compressor, |bytes| splitter.write(bytes));
+//! write!(writer, "{reading}")?;
+//! writer.finish()?;
+//! splitter.finish();
+//! ```
+
+use crate::Error;
+
+/// Bytes of header at the start of every frame.
+pub const FRAME_HEADER: usize = 1;
+/// Header flag marking the final frame of a stream.
+pub const LAST: u8 = 0x80;
+/// Header flag marking a final frame that ends with the uncompressed length.
+pub const LENGTH: u8 = 0x40;
+pub(crate) const SEQUENCE: u8 = 0x3f;
+/// Bytes of the uncompressed length trailer.
+const LENGTH_TRAILER: usize = 8;
+
+/// Packs a byte stream into frames of at most `MTU` bytes, header included.
+pub struct FrameSplitter<const MTU: usize, F: FnMut(&[u8])> {
+    frame: [u8; MTU],
+    len: usize,
+    /// Frames emitted, the low 6 bits of which are the next sequence number.
+    frames: u32,
+    sink: F,
+}
+
+impl<const MTU: usize, F: FnMut(&[u8])> FrameSplitter<MTU, F> {
+    /// Creates a splitter passing each completed frame to `sink`.
+    pub fn new(sink: F) -> Self {
+        const { assert!(MTU > FRAME_HEADER, "MTU must leave room for payload") };
+        Self {
+            frame: [0; MTU],
+            len: FRAME_HEADER,
+            frames: 0,
+            sink,
+        }
+    }
+
+    /// Appends `bytes`, emitting a frame each time one fills up.
+    pub fn write(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.len == MTU {
+                self.emit(0);
+            }
+            let n = bytes.len().min(MTU - self.len);
+            self.frame[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+            self.len += n;
+            bytes = &bytes[n..];
+        }
+    }
+
+    /// Emits the pending bytes as a short frame, e.g. before the link goes idle.
+    pub fn flush(&mut self) {
+        if self.len > FRAME_HEADER {
+            self.emit(0);
+        }
+    }
+
+    /// Emits the pending bytes as the final frame. The final frame may carry no payload.
+    pub fn finish(mut self) {
+        self.emit(LAST);
+    }
+
+    /// Like `finish()`, but records the uncompressed length `len` for the receiver to check.
+    /// Requires `MTU` >= 9. May emit one extra frame to make room for the length.
+    pub fn finish_with_length(mut self, len: u64) {
+        const {
+            assert!(
+                MTU >= FRAME_HEADER + LENGTH_TRAILER,
+                "MTU too small for length"
+            )
+        };
+        if MTU - self.len < LENGTH_TRAILER {
+            self.emit(0);
+        }
+        self.frame[self.len..self.len + LENGTH_TRAILER].copy_from_slice(&len.to_le_bytes());
+        self.len += LENGTH_TRAILER;
+        self.emit(LAST | LENGTH);
+    }
+
+    /// Number of frames emitted so far.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    fn emit(&mut self, flags: u8) {
+        self.frame[0] = (self.frames as u8 & SEQUENCE) | flags;
+        (self.sink)(&self.frame[..self.len]);
+        self.frames = self.frames.wrapping_add(1);
+        self.len = FRAME_HEADER;
+    }
+}
+
+/// Checks frame headers from a `FrameSplitter` and passes the payloads on in order.
+pub struct FrameReassembler<F: FnMut(&[u8])> {
+    /// Sequence number of the last accepted frame, `None` before the first one.
+    last: Option<u8>,
+    done: bool,
+    expected_len: Option<u64>,
+    sink: F,
+}
+
+impl<F: FnMut(&[u8])> FrameReassembler<F> {
+    /// Creates a reassembler passing each frame's payload to `sink`.
+    pub fn new(sink: F) -> Self {
+        Self {
+            last: None,
+            done: false,
+            expected_len: None,
+            sink,
+        }
+    }
+
+    /// Accepts one received frame.
+    ///
+    /// A repeat of the previous frame, as sent by retransmitting links, is ignored. Any other
+    /// out-of-sequence frame, or a frame after the last one, returns `FrameSequence`.
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let (&header, payload) = frame.split_first().ok_or(Error::FrameSequence)?;
+        let sequence = header & SEQUENCE;
+        if self.last == Some(sequence) {
+            return Ok(());
+        }
+        if self.done || sequence != self.next_sequence() {
+            return Err(Error::FrameSequence);
+        }
+        let mut payload = payload;
+        if header & (LAST | LENGTH) == LAST | LENGTH {
+            let split = payload
+                .len()
+                .checked_sub(LENGTH_TRAILER)
+                .ok_or(Error::FrameSequence)?;
+            let (data, trailer) = payload.split_at(split);
+            self.expected_len = Some(u64::from_le_bytes(trailer.try_into().unwrap()));
+            payload = data;
+        }
+        (self.sink)(payload);
+        self.last = Some(sequence);
+        self.done = header & LAST != 0;
+        Ok(())
+    }
+
+    /// Sequence number of the next frame expected, the resume point after a lost link.
+    pub fn next_sequence(&self) -> u8 {
+        self.last.map_or(0, |last| (last + 1) & SEQUENCE)
+    }
+
+    /// Returns true once the final frame was received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Uncompressed length recorded by `FrameSplitter::finish_with_length`, once received.
+    pub fn expected_len(&self) -> Option<u64> {
+        self.expected_len
+    }
+
+    /// Checks the decompressed length against the recorded one.
+    /// Returns `LengthMismatch` if they differ; passes when no length was recorded.
+    pub fn check_len(&self, decompressed: u64) -> Result<(), Error> {
+        match self.expected_len {
+            Some(expected) if expected != decompressed => Err(Error::LengthMismatch),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut splitter = FrameSplitter::<20, _>::new(|frame: &[u8]| frames.push(frame.into()));
+        for chunk in data.chunks(33) {
+            splitter.write(chunk);
+        }
+        splitter.flush();
+        splitter.write(&[]);
+        splitter.finish();
+        assert!(frames.iter().all(|frame| frame.len() <= 20));
+        assert_eq!(frames.len(), 1000usize.div_ceil(19) + 1);
+
+        let reassemble = |frames: &[Vec<u8>]| -> Result<Vec<u8>, Error> {
+            let mut out = Vec::new();
+            let mut reassembler = FrameReassembler::new(|p: &[u8]| out.extend_from_slice(p));
+            for frame in frames {
+                reassembler.push(frame)?;
+            }
+            assert!(reassembler.is_done());
+            Ok(out)
+        };
+        assert_eq!(reassemble(&frames).unwrap(), data);
+
+        let mut repeated = frames.clone();
+        repeated.insert(3, frames[2].clone());
+        assert_eq!(reassemble(&repeated).unwrap(), data);
+
+        let mut lost = frames.clone();
+        lost.remove(3);
+        assert!(matches!(reassemble(&lost), Err(Error::FrameSequence)));
+    }
+
+    #[test]
+    fn test_sequence_wraps() {
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut splitter = FrameSplitter::<2, _>::new(|frame: &[u8]| frames.push(frame.into()));
+        splitter.write(&[0x55; 300]);
+        assert_eq!(splitter.frames(), 299);
+        splitter.finish();
+        assert_eq!(frames[63][0], 63);
+        assert_eq!(frames[64][0], 0);
+
+        let mut reassembler = FrameReassembler::new(|_: &[u8]| {});
+        assert!(matches!(reassembler.push(&frames[63]), Err(Error::FrameSequence)));
+        for frame in &frames {
+            reassembler.push(frame).unwrap();
+        }
+        assert!(reassembler.is_done());
+    }
+
+    #[test]
+    fn test_length_trailer() {
+        for payload in [0, 5, 11, 12, 19] {
+            let mut frames: Vec<Vec<u8>> = Vec::new();
+            let mut splitter =
+                FrameSplitter::<20, _>::new(|frame: &[u8]| frames.push(frame.into()));
+            splitter.write(&[7; 19][..payload]);
+            splitter.finish_with_length(1234);
+            assert!(frames.iter().all(|frame| frame.len() <= 20));
+
+            let mut out = Vec::new();
+            let mut reassembler = FrameReassembler::new(|p: &[u8]| out.extend_from_slice(p));
+            for frame in &frames {
+                reassembler.push(frame).unwrap();
+            }
+            assert!(reassembler.is_done());
+            assert_eq!(reassembler.expected_len(), Some(1234));
+            assert!(reassembler.check_len(1234).is_ok());
+            assert!(matches!(
+                reassembler.check_len(1000),
+                Err(Error::LengthMismatch)
+            ));
+            assert_eq!(out, &[7; 19][..payload]);
+        }
+    }
+}
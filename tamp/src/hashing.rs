@@ -0,0 +1,190 @@
+//! Hashing the uncompressed data while it passes through the codec.
+//!
+//! OTA pipelines need the image digest alongside the compressed image. These wrappers feed
+//! every uncompressed byte to a `digest::Update` hasher (CRC, SHA-256, ...) as it is consumed
+//! by the compressor or produced by the decompressor, avoiding a second pass over the data.
+
+use digest::Update;
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+
+/// Byte counts and hasher state of a hashing codec, see `stats()`.
+#[derive(Debug)]
+pub struct Stats<'a, D> {
+    /// Uncompressed bytes hashed so far.
+    pub uncompressed: u64,
+    /// Compressed bytes produced by the compressor, or consumed by the decompressor
+    /// after the header.
+    pub compressed: u64,
+    /// Hasher updated with every uncompressed byte. Clone it to finalize mid-stream.
+    pub hasher: &'a D,
+}
+
+/// Compressor wrapper that hashes the input it consumes.
+#[cfg(feature = "compressor")]
+pub struct HashingCompressor<'a, const N: usize, D> {
+    compressor: &'a mut Compressor<N>,
+    hasher: D,
+    uncompressed: u64,
+    compressed: u64,
+}
+
+#[cfg(feature = "compressor")]
+impl<'a, const N: usize, D: Update> HashingCompressor<'a, N, D> {
+    /// Wraps `compressor`, updating `hasher` with every consumed input byte.
+    pub fn new(compressor: &'a mut Compressor<N>, hasher: D) -> Self {
+        Self {
+            compressor,
+            hasher,
+            uncompressed: 0,
+            compressed: 0,
+        }
+    }
+
+    /// Compresses input data into output buffer, see `Compressor::compress_chunk`.
+    /// Only the consumed part of `input` is hashed.
+    pub fn compress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let (consumed, written) = self.compressor.compress_chunk(input, output)?;
+        self.hasher.update(&input[..consumed]);
+        self.uncompressed += consumed as u64;
+        self.compressed += written as u64;
+        Ok((consumed, written))
+    }
+
+    /// Flushes remaining data from internal buffers, see `Compressor::flush`.
+    pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        let written = self.compressor.flush(output, write_token)?;
+        self.compressed += written as u64;
+        Ok(written)
+    }
+
+    /// Returns the byte counts and the hasher.
+    pub fn stats(&self) -> Stats<'_, D> {
+        Stats {
+            uncompressed: self.uncompressed,
+            compressed: self.compressed,
+            hasher: &self.hasher,
+        }
+    }
+
+    /// Releases the compressor borrow, returning the hasher.
+    pub fn into_hasher(self) -> D {
+        self.hasher
+    }
+}
+
+/// Decompressor wrapper that hashes the output it produces.
+#[cfg(feature = "decompressor")]
+pub struct HashingDecompressor<'a, const N: usize, D> {
+    decompressor: &'a mut Decompressor<N>,
+    hasher: D,
+    uncompressed: u64,
+    compressed: u64,
+}
+
+#[cfg(feature = "decompressor")]
+impl<'a, const N: usize, D: Update> HashingDecompressor<'a, N, D> {
+    /// Wraps `decompressor`, updating `hasher` with every produced output byte.
+    pub fn new(decompressor: &'a mut Decompressor<N>, hasher: D) -> Self {
+        Self {
+            decompressor,
+            hasher,
+            uncompressed: 0,
+            compressed: 0,
+        }
+    }
+
+    /// Decompresses input data into output buffer, see `Decompressor::decompress_chunk`.
+    pub fn decompress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let (consumed, written) = self.decompressor.decompress_chunk(input, output)?;
+        self.hasher.update(&output[..written]);
+        self.uncompressed += written as u64;
+        self.compressed += consumed as u64;
+        Ok((consumed, written))
+    }
+
+    /// Returns the byte counts and the hasher.
+    pub fn stats(&self) -> Stats<'_, D> {
+        Stats {
+            uncompressed: self.uncompressed,
+            compressed: self.compressed,
+            hasher: &self.hasher,
+        }
+    }
+
+    /// Releases the decompressor borrow, returning the hasher.
+    pub fn into_hasher(self) -> D {
+        self.hasher
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use sha2::{Digest, Sha256};
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_hashing_round_trip() {
+        let image: [u8; 2000] = core::array::from_fn(|i| (i * 7 % 61) as u8);
+        let expected = Sha256::digest(image);
+
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut hashing = HashingCompressor::new(&mut compressor, Sha256::new());
+        let mut compressed = [0u8; 4096];
+        let mut len = 0;
+        for chunk in image.chunks(300) {
+            let (consumed, written) = hashing
+                .compress_chunk(chunk, &mut compressed[len..])
+                .unwrap();
+            assert_eq!(consumed, chunk.len());
+            len += written;
+        }
+        len += hashing.flush(&mut compressed[len..], false).unwrap();
+        let stats = hashing.stats();
+        assert_eq!(stats.uncompressed, image.len() as u64);
+        assert_eq!(stats.compressed, len as u64);
+        assert_eq!(stats.hasher.clone().finalize(), expected);
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut hashing = HashingDecompressor::new(&mut decompressor, Sha256::new());
+        let mut output = [0u8; 2000];
+        hashing
+            .decompress_chunk(&compressed[header..len], &mut output)
+            .unwrap();
+        assert_eq!(hashing.stats().uncompressed, image.len() as u64);
+        assert_eq!(hashing.into_hasher().finalize(), expected);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_hashing_small_output() {
+        let noise: [u8; 600] =
+            core::array::from_fn(|i| ((i as u32).wrapping_mul(2654435761) >> 13) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut hashing = HashingCompressor::new(&mut compressor, Sha256::new());
+        let mut out = [0u8; 16];
+        let (mut consumed, mut len) = (0, 0);
+        while consumed < noise.len() {
+            let (c, n) = hashing.compress_chunk(&noise[consumed..], &mut out).unwrap();
+            consumed += c;
+            len += n;
+        }
+        let stats = hashing.stats();
+        assert_eq!(stats.uncompressed, noise.len() as u64);
+        assert_eq!(stats.compressed, len as u64);
+        assert_eq!(hashing.into_hasher().finalize(), Sha256::digest(noise));
+    }
+}
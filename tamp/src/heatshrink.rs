@@ -0,0 +1,240 @@
+//! Decoder for the heatshrink format, for gateways that still receive data from devices
+//! running heatshrink while the fleet moves to tamp.
+//!
+//! heatshrink streams carry no header: the window and lookahead sizes the sender was built
+//! with (`-w` and `-l` of the heatshrink CLI) have to be known out of band. The decoder is
+//! pure Rust and has the same streaming interface as `Decompressor`, so a receiver can pick
+//! one per device and feed both the same way.
+//!
+//! ```ignore
+//! let mut legacy = HeatshrinkDecoder::<256>::new(8, 4)?;
+//! let (consumed, written) = legacy.decompress_chunk(&packet, &mut output)?;
+//! ```
+
+use crate::Error;
+
+/// Streaming heatshrink decoder with an inline window buffer.
+///
+/// `N` is the window buffer size in bytes and must equal 2^window_bits.
+pub struct HeatshrinkDecoder<const N: usize> {
+    window: [u8; N],
+    head: usize,
+    window_bits: u32,
+    lookahead_bits: u32,
+    /// Unconsumed input bits, in the low `bits` bits, first bit highest.
+    acc: u64,
+    bits: u32,
+    /// Distance and bytes left of the back-reference being copied.
+    offset: usize,
+    remaining: usize,
+}
+
+impl<const N: usize> HeatshrinkDecoder<N> {
+    /// Creates a decoder for streams compressed with `window_bits` (4 to 15) and
+    /// `lookahead_bits` (3 to `window_bits - 1`).
+    pub fn new(window_bits: u8, lookahead_bits: u8) -> Result<Self, Error> {
+        if !(4..=15).contains(&window_bits) || !(3..window_bits).contains(&lookahead_bits) {
+            return Err(Error::InvalidConfig("Invalid heatshrink parameters"));
+        }
+        if N != 1 << window_bits {
+            return Err(Error::InvalidConfig("Buffer size N must equal 2^window_bits"));
+        }
+        Ok(Self {
+            window: [0; N],
+            head: 0,
+            window_bits: window_bits as u32,
+            lookahead_bits: lookahead_bits as u32,
+            acc: 0,
+            bits: 0,
+            offset: 0,
+            remaining: 0,
+        })
+    }
+
+    /// Clears the window and any partial token to decode a new stream with the same
+    /// parameters.
+    pub fn reset(&mut self) {
+        self.window.fill(0);
+        self.head = 0;
+        self.acc = 0;
+        self.bits = 0;
+        self.remaining = 0;
+    }
+
+    /// Takes the next `n` buffered bits.
+    fn take(&mut self, n: u32) -> usize {
+        self.bits -= n;
+        ((self.acc >> self.bits) & ((1 << n) - 1)) as usize
+    }
+
+    /// Appends `byte` to the window and `output`.
+    fn emit(&mut self, byte: u8, output: &mut [u8], written: &mut usize) {
+        self.window[self.head % N] = byte;
+        self.head = self.head.wrapping_add(1);
+        output[*written] = byte;
+        *written += 1;
+    }
+
+    /// Decompresses input data into output buffer.
+    /// Returns (input_consumed, output_written). May not consume all input or fill all output.
+    /// Call repeatedly until input is exhausted or output is filled.
+    ///
+    /// Never fails: every bit sequence is a valid heatshrink stream. The `Result` keeps the
+    /// signature of `Decompressor::decompress_chunk`.
+    pub fn decompress_chunk(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        while written < output.len() {
+            if self.remaining > 0 {
+                let byte = self.window[self.head.wrapping_sub(self.offset) % N];
+                self.emit(byte, output, &mut written);
+                self.remaining -= 1;
+                continue;
+            }
+            // A tag bit, then 8 literal bits or the index and count of a back-reference
+            let needed = match self.bits {
+                0 => 1,
+                _ if (self.acc >> (self.bits - 1)) & 1 == 1 => 9,
+                _ => 1 + self.window_bits + self.lookahead_bits,
+            };
+            if self.bits < needed {
+                let Some(&byte) = input.get(consumed) else {
+                    break;
+                };
+                self.acc = (self.acc << 8) | byte as u64;
+                self.bits += 8;
+                consumed += 1;
+                continue;
+            }
+            if self.take(1) == 1 {
+                let byte = self.take(8) as u8;
+                self.emit(byte, output, &mut written);
+            } else {
+                self.offset = self.take(self.window_bits) + 1;
+                self.remaining = self.take(self.lookahead_bits) + 1;
+            }
+        }
+        Ok((consumed, written))
+    }
+
+    /// Fills all of `output`, advancing the `input` cursor past the consumed bytes.
+    ///
+    /// Returns `InputExhausted` if `input` runs out first; the bytes decoded so far are left
+    /// in `output` and the cursor is still advanced.
+    pub fn decompress_exact(&mut self, input: &mut &[u8], output: &mut [u8]) -> Result<(), Error> {
+        let mut filled = 0;
+        while filled < output.len() {
+            let (consumed, written) = self.decompress_chunk(input, &mut output[filled..])?;
+            *input = &input[consumed..];
+            filled += written;
+            if consumed == 0 && written == 0 {
+                return Err(Error::InputExhausted);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    /// Writes heatshrink tokens MSB first, zero padding the last byte like the encoder.
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        acc: u32,
+        n: u32,
+    }
+
+    impl Bits {
+        fn put(&mut self, value: u32, bits: u32) -> &mut Self {
+            for i in (0..bits).rev() {
+                self.acc = (self.acc << 1) | ((value >> i) & 1);
+                self.n += 1;
+                if self.n == 8 {
+                    self.bytes.push(self.acc as u8);
+                    (self.acc, self.n) = (0, 0);
+                }
+            }
+            self
+        }
+
+        fn literal(&mut self, byte: u8) -> &mut Self {
+            self.put(1, 1).put(byte as u32, 8)
+        }
+
+        fn backref(&mut self, offset: u32, count: u32) -> &mut Self {
+            self.put(0, 1).put(offset - 1, 8).put(count - 1, 4)
+        }
+
+        fn finish(&mut self) -> Vec<u8> {
+            if self.n > 0 {
+                self.put(0, 8 - self.n);
+            }
+            core::mem::take(&mut self.bytes)
+        }
+    }
+
+    #[test]
+    fn test_heatshrink_stream() {
+        let stream = Bits::default()
+            .backref(1, 2)
+            .literal(b'a')
+            .literal(b'b')
+            .literal(b'c')
+            .backref(3, 9)
+            .literal(b'!')
+            .backref(13, 16)
+            .finish();
+        let expected = b"\0\0abcabcabcabc!abcabcabcabc!abc";
+
+        let mut decoder = HeatshrinkDecoder::<256>::new(8, 4).unwrap();
+        let mut output = Vec::new();
+        let mut buf = [0u8; 3];
+        for byte in stream.chunks(1) {
+            let mut byte = byte;
+            loop {
+                let (consumed, written) = decoder.decompress_chunk(byte, &mut buf).unwrap();
+                output.extend_from_slice(&buf[..written]);
+                byte = &byte[consumed..];
+                if consumed == 0 && written == 0 {
+                    break;
+                }
+            }
+        }
+        assert_eq!(output, expected);
+
+        decoder.reset();
+        let mut output = [0u8; 31];
+        decoder.decompress_exact(&mut &stream[..], &mut output).unwrap();
+        assert_eq!(&output, expected);
+
+        assert!(HeatshrinkDecoder::<256>::new(8, 8).is_err());
+        assert!(HeatshrinkDecoder::<512>::new(8, 4).is_err());
+    }
+
+    /// Encoder output from heatshrink's own test suite (`test_heatshrink_dynamic.c`), as
+    /// `(window_bits, lookahead_bits, stream, input)`.
+    const REFERENCE: &[(u8, u8, &[u8], &[u8])] = &[
+        (8, 7, &[0x80, 0x40, 0x60, 0x50, 0x38, 0x20], &[0, 1, 2, 3, 4]),
+        (8, 7, &[0xb0, 0x80, 0x01, 0x80], b"aaaaa"),
+        (8, 3, &[0xb0, 0xd8, 0xac, 0x76, 0x40, 0x1b], b"abcdabcd"),
+        (8, 3, &[0xb0, 0xd8, 0xac, 0x76, 0x40, 0x1b, 0xb2, 0x80], b"abcdabcde"),
+    ];
+
+    #[test]
+    fn test_heatshrink_reference() {
+        for &(window_bits, lookahead_bits, stream, input) in REFERENCE {
+            let mut decoder = HeatshrinkDecoder::<256>::new(window_bits, lookahead_bits).unwrap();
+            let mut output = [0u8; 16];
+            let (consumed, written) = decoder.decompress_chunk(stream, &mut output).unwrap();
+            assert_eq!((consumed, &output[..written]), (stream.len(), input));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! JSON minification in front of the compressor, for host tools and gateways.
+//!
+//! Device JSON headed for the cloud is often pretty-printed. Whitespace between tokens
+//! carries no data, so `compress_json()` strips it before compressing and reports what each
+//! step saved.
+//!
+//! ```ignore
+//! let (compressed, report) = json::compress_json(&payload, Config::new())?;
+//! log::info!("minified {:.1}x, compressed {:.1}x", report.minification().factor(),
+//!     report.compression().factor());
+//! ```
+
+use alloc::vec::Vec;
+use crate::{Config, Error, Ratio, compress_to_vec};
+
+/// Sizes of a payload through `compress_json()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JsonReport {
+    /// Input length in bytes.
+    pub original: usize,
+    /// Length after minification.
+    pub minified: usize,
+    /// Compressed length, header included.
+    pub compressed: usize,
+}
+
+impl JsonReport {
+    /// Savings of the minification step alone.
+    pub fn minification(&self) -> Ratio {
+        Ratio {
+            uncompressed: self.original,
+            compressed: self.minified,
+        }
+    }
+
+    /// Savings of the compression step alone, on the minified payload.
+    pub fn compression(&self) -> Ratio {
+        Ratio {
+            uncompressed: self.minified,
+            compressed: self.compressed,
+        }
+    }
+
+    /// Savings of both steps together.
+    pub fn total(&self) -> Ratio {
+        Ratio {
+            uncompressed: self.original,
+            compressed: self.compressed,
+        }
+    }
+}
+
+/// Removes whitespace outside of strings from `input`, appending the result to `output`.
+///
+/// The input is not validated beyond string boundaries: fails with `Serialization` only if
+/// it ends inside a string. String contents, escapes included, are copied unchanged.
+pub fn minify(input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    let (mut in_string, mut escaped) = (false, false);
+    output.reserve(input.len());
+    for &byte in input {
+        if in_string {
+            output.push(byte);
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+        } else if !matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+            output.push(byte);
+            in_string = byte == b'"';
+        }
+    }
+    if in_string {
+        return Err(Error::Serialization);
+    }
+    Ok(())
+}
+
+/// Minifies `input`, compresses the result into a complete stream and reports the sizes.
+pub fn compress_json(input: &[u8], config: Config) -> Result<(Vec<u8>, JsonReport), Error> {
+    let mut minified = Vec::new();
+    minify(input, &mut minified)?;
+    let compressed = compress_to_vec(&minified, config)?;
+    let report = JsonReport {
+        original: input.len(),
+        minified: minified.len(),
+        compressed: compressed.len(),
+    };
+    Ok((compressed, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minify() {
+        let mut output = Vec::new();
+        minify(b"{\n  \"name\": \"a b\\\" c\",\n  \"v\": [1, 2]\n}\n", &mut output).unwrap();
+        assert_eq!(output, b"{\"name\":\"a b\\\" c\",\"v\":[1,2]}");
+        assert!(minify(b"{\"open: 1}", &mut Vec::new()).is_err());
+    }
+
+    #[cfg(feature = "decompressor")]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_json() {
+        let mut input = Vec::from(&b"[\n"[..]);
+        for i in 0..50 {
+            let entry = alloc::format!("  {{ \"id\": {i}, \"temp\": 21.5, \"ok\": true }},\n");
+            input.extend_from_slice(entry.as_bytes());
+        }
+        input.extend_from_slice(b"  {}\n]\n");
+        let (compressed, report) = compress_json(&input, Config::new()).unwrap();
+        assert_eq!(report.compressed, compressed.len());
+        assert!(report.minification().saves(20));
+        assert!(report.compression().saves(50));
+
+        let mut expected = Vec::new();
+        minify(&input, &mut expected).unwrap();
+        assert_eq!(crate::decompress_to_vec(&compressed).unwrap(), expected);
+    }
+}
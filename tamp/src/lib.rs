@@ -2,25 +2,210 @@
 //!
 //! Provides safe, heapless streaming compression and decompression using const generics
 //! for compile-time buffer allocation. Designed for embedded systems without heap allocation.
+//! The default build is `no_std`; host conveniences such as the `std::io` adapters live
+//! behind the `alloc` and `std` features.
 #![no_std]
 #![deny(missing_docs)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 use tamp_sys::{
     TAMP_EXCESS_BITS, TAMP_INPUT_EXHAUSTED, TAMP_INVALID_CONF, TAMP_OK, TAMP_OUTPUT_FULL, tamp_res,
 };
 
+#[macro_use]
+mod trace;
+
+mod config;
+
+pub use config::{Config, Search};
+
+mod sink;
+
+pub mod budget;
+
+pub use sink::{Full, Sink, SliceSink};
+
+mod window;
+
+pub use window::WindowStorage;
+
 #[cfg(feature = "compressor")]
 mod compressor;
 
 #[cfg(feature = "compressor")]
-pub use compressor::{Config, Compressor};
+pub use compressor::{ChunksConsumed, Compressor, Ratio, estimate_ratio};
+
+#[cfg(feature = "compressor")]
+mod static_compressor;
+
+#[cfg(feature = "compressor")]
+pub use static_compressor::StaticCompressor;
 
 #[cfg(feature = "decompressor")]
 mod decompressor;
 
 #[cfg(feature = "decompressor")]
-pub use decompressor::Decompressor;
+pub use decompressor::{Decompressor, decompress_zero_copy};
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+mod dynamic;
+
+#[cfg(feature = "compressor")]
+pub use dynamic::DynCompressor;
+
+#[cfg(feature = "decompressor")]
+pub use dynamic::DynDecompressor;
+
+#[cfg(all(feature = "alloc", feature = "compressor"))]
+mod recommend;
+
+#[cfg(all(feature = "alloc", any(feature = "compressor", feature = "decompressor")))]
+mod oneshot;
+
+#[cfg(all(feature = "alloc", feature = "compressor"))]
+pub use oneshot::compress_to_vec;
+
+#[cfg(all(feature = "alloc", feature = "decompressor"))]
+pub use oneshot::decompress_to_vec;
+
+#[cfg(feature = "compressor")]
+mod staging;
+
+#[cfg(feature = "compressor")]
+mod fmt;
+
+#[cfg(feature = "compressor")]
+pub use fmt::CompressingFmtWriter;
+
+#[cfg(all(feature = "std", feature = "compressor"))]
+mod writer;
+
+#[cfg(all(feature = "std", feature = "compressor"))]
+pub use writer::CompressWriter;
+
+#[cfg(all(feature = "alloc", feature = "compressor"))]
+pub mod json;
+
+#[cfg(feature = "ufmt")]
+mod ufmt;
+
+#[cfg(feature = "ufmt")]
+pub use ufmt::CompressingUWriter;
+
+#[cfg(feature = "compressor")]
+pub mod ringlog;
+
+#[cfg(feature = "compressor")]
+pub mod paged;
+
+#[cfg(all(feature = "compressor", feature = "heapless"))]
+pub mod pipeline;
+
+#[cfg(feature = "compressor")]
+pub mod dma;
+
+#[cfg(feature = "compressor")]
+pub mod session;
+
+#[cfg(feature = "portable-atomic")]
+pub mod stats;
+
+pub mod dictionary;
+
+#[cfg(all(feature = "dictionary", any(feature = "compressor", feature = "decompressor")))]
+pub mod delta;
+
+pub mod filter;
+
+pub mod tokens;
+
+#[cfg(feature = "rust-backend")]
+pub mod backend;
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub mod samples;
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub mod ascii;
+
+pub mod charset;
+
+pub mod armor;
+
+pub mod frames;
+
+pub mod ble;
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub mod mux;
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub mod pool;
+
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub mod batch;
+
+#[cfg(feature = "embedded-storage")]
+pub mod flash;
+
+#[cfg(any(feature = "littlefs2", feature = "embedded-sdmmc"))]
+mod file;
+
+#[cfg(feature = "littlefs2")]
+pub mod littlefs;
+
+#[cfg(feature = "embedded-sdmmc")]
+pub mod sdmmc;
+
+#[cfg(feature = "defmt")]
+mod defmt_logger;
+
+#[cfg(feature = "defmt")]
+pub use defmt_logger::DefmtCompressor;
+
+#[cfg(feature = "serde")]
+mod compressed;
+
+#[cfg(feature = "serde")]
+pub use compressed::Compressed;
+
+#[cfg(feature = "digest")]
+pub mod hashing;
+
+#[cfg(feature = "aead")]
+pub mod sealed;
+
+#[cfg(feature = "decompressor")]
+pub mod blocks;
+
+#[cfg(feature = "decompressor")]
+pub mod bufread;
+
+#[cfg(feature = "rayon")]
+pub mod parallel;
+
+#[cfg(feature = "heatshrink")]
+pub mod heatshrink;
 
+#[cfg(feature = "bytes")]
+mod buf;
+
+#[cfg(feature = "postcard")]
+mod serialize;
+
+#[cfg(feature = "postcard")]
+pub use serialize::{compress_postcard, decompress_postcard};
+
+#[cfg(feature = "minicbor")]
+mod cbor;
+
+#[cfg(feature = "minicbor")]
+pub use cbor::{compress_cbor, decompress_cbor};
 
 /// Errors that can occur during compression or decompression.
 #[derive(Debug)]
@@ -33,13 +218,35 @@ pub enum Error {
     InvalidConfig(&'static str),
     /// Symbol has more bits than configured literal size.
     ExcessBits,
-    /// Heapless buffer cannot be resized to required size.
+    /// A caller-provided buffer is too small.
     BufferTooSmall,
+    /// Serializing or deserializing a payload failed.
+    Serialization,
+    /// Sealed data failed authentication, was truncated or is malformed.
+    Authentication,
+    /// Armored text has a character outside its alphabet or ends inside a group.
+    InvalidArmor,
+    /// A frame is missing, out of order, or arrived after the final frame.
+    FrameSequence,
+    /// Decompressed length differs from the length recorded alongside the stream.
+    LengthMismatch,
+    /// Every instance of a `pool::Pool` is in use.
+    PoolExhausted,
+    /// Byte at this offset is not 7-bit ASCII. Use `ascii::AsciiMode::Force` or 8-bit literals.
+    NonAscii(usize),
+    /// New data arrived before the previous `dma::DoubleBuffer` half was compressed.
+    Overrun,
+    /// A stored dictionary is corrupt or of an unknown version, or not the expected one.
+    InvalidDictionary,
+    /// A stream names a dictionary ID missing from the `dictionary::DictionaryRegistry`.
+    UnknownDictionary(u8),
+    /// The codec still holds buffered input; flush it or finish decoding it first.
+    InputPending,
 }
 
 impl Error {
     fn from_tamp_res(res: tamp_res) -> Result<(), Self> {
-        match res {
+        let result = match res {
             x if x == TAMP_OK as tamp_res => Ok(()),
             x if x == TAMP_OUTPUT_FULL as tamp_res => Err(Error::OutputFull),
             x if x == TAMP_INPUT_EXHAUSTED as tamp_res => Err(Error::InputExhausted),
@@ -48,39 +255,110 @@ impl Error {
                 Err(Error::InvalidConfig("Invalid parameters"))
             }
             _ => Err(Error::InvalidConfig("Unknown error")),
+        };
+        #[cfg(any(feature = "log", feature = "tracing"))]
+        if let Err(err) = &result {
+            trace_event!("C library returned {}: {}", res, err);
+        }
+        result
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutputFull => f.write_str("output buffer is full"),
+            Error::InputExhausted => f.write_str("input exhausted"),
+            Error::InvalidConfig(reason) => write!(f, "invalid configuration: {reason}"),
+            Error::ExcessBits => f.write_str("symbol exceeds the configured literal size"),
+            Error::BufferTooSmall => f.write_str("buffer is too small"),
+            Error::Serialization => f.write_str("serialization failed"),
+            Error::Authentication => f.write_str("authentication failed"),
+            Error::InvalidArmor => f.write_str("invalid armored text"),
+            Error::FrameSequence => f.write_str("frame out of sequence"),
+            Error::LengthMismatch => f.write_str("decompressed length mismatch"),
+            Error::PoolExhausted => f.write_str("pool exhausted"),
+            Error::NonAscii(offset) => write!(f, "non-ASCII byte at offset {offset}"),
+            Error::Overrun => f.write_str("buffer overrun"),
+            Error::InvalidDictionary => f.write_str("invalid or mismatched dictionary"),
+            Error::UnknownDictionary(id) => write!(f, "unknown dictionary ID {id}"),
+            Error::InputPending => f.write_str("input pending"),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    /// Picks the closest `io::ErrorKind`, keeping the original error as the source.
+    fn from(err: Error) -> Self {
+        use std::io::ErrorKind;
+        let kind = match err {
+            Error::OutputFull | Error::BufferTooSmall => ErrorKind::WriteZero,
+            Error::InputExhausted => ErrorKind::UnexpectedEof,
+            Error::PoolExhausted => ErrorKind::WouldBlock,
+            Error::Overrun | Error::InputPending => ErrorKind::Other,
+            Error::InvalidConfig(_) | Error::ExcessBits | Error::NonAscii(_) => {
+                ErrorKind::InvalidInput
+            }
+            Error::Serialization
+            | Error::Authentication
+            | Error::InvalidArmor
+            | Error::FrameSequence
+            | Error::LengthMismatch
+            | Error::InvalidDictionary
+            | Error::UnknownDictionary(_) => ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
 /// Compressor with 256-byte window (8-bit window). Minimal memory usage.
+#[cfg(feature = "compressor")]
 pub type Compressor256 = Compressor<256>;
 /// Compressor with 512-byte window (9-bit window). Low memory usage.
+#[cfg(feature = "compressor")]
 pub type Compressor512 = Compressor<512>;
 /// Compressor with 1KB window (10-bit window). Default and recommended for most uses.
+#[cfg(feature = "compressor")]
 pub type Compressor1K = Compressor<1024>;
 /// Compressor with 2KB window (11-bit window). Better compression for larger data.
+#[cfg(feature = "compressor")]
 pub type Compressor2K = Compressor<2048>;
 /// Compressor with 4KB window (12-bit window). Best compression but high memory usage.
+#[cfg(feature = "compressor")]
 pub type Compressor4K = Compressor<4096>;
 
 /// Decompressor with 256-byte window (8-bit window). Minimal memory usage.
+#[cfg(feature = "decompressor")]
 pub type Decompressor256 = Decompressor<256>;
 /// Decompressor with 512-byte window (9-bit window). Low memory usage.
+#[cfg(feature = "decompressor")]
 pub type Decompressor512 = Decompressor<512>;
 /// Decompressor with 1KB window (10-bit window). Default and recommended for most uses.
+#[cfg(feature = "decompressor")]
 pub type Decompressor1K = Decompressor<1024>;
 /// Decompressor with 2KB window (11-bit window). Better compression for larger data.
+#[cfg(feature = "decompressor")]
 pub type Decompressor2K = Decompressor<2048>;
 /// Decompressor with 4KB window (12-bit window). Best compression but high memory usage.
+#[cfg(feature = "decompressor")]
 pub type Decompressor4K = Decompressor<4096>;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
 mod tests {
     extern crate std;
     use super::*;
+    use crate::compressor::MAX_POLL;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::borrow::ToOwned;
     use std::format;
+    use std::vec::Vec;
 
+    #[cfg_attr(miri, ignore = "calls into the C library")]
     #[test]
     fn test_corpus() {
         test_compress_decompress_canterbury_corpus::<256>(Config::new().window_bits(8).unwrap());
@@ -280,4 +558,175 @@ mod tests {
             let _ = remove_file(&compressed_path);
         }
     }
+    /// Compresses `input` fed in pieces of the given sizes, polling into `poll_size` buffers.
+    fn compress_split<const N: usize>(
+        config: Config,
+        input: &[u8],
+        splits: &[usize],
+        poll_size: usize,
+    ) -> Vec<u8> {
+        let mut compressor = Compressor::<N>::new(config).unwrap();
+        let mut compressed = Vec::new();
+        let mut buf = [0u8; 64];
+        let mut rest = input;
+        for &split in splits.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let piece;
+            (piece, rest) = rest.split_at(split.min(rest.len()));
+            compressor
+                .sink_all(piece, &mut buf[..poll_size], &mut |polled| {
+                    compressed.extend_from_slice(polled)
+                })
+                .unwrap();
+        }
+        let n = compressor.flush(&mut buf, false).unwrap();
+        compressed.extend_from_slice(&buf[..n]);
+        compressed
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_golden_vectors() {
+        use std::format;
+        use std::path::Path;
+
+        // Every configuration generate.py writes; a missing stream fails the test
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/golden");
+        let read = |name: &str| {
+            std::fs::read(dir.join(name))
+                .unwrap_or_else(|e| panic!("{name}: {e}, run fixtures/golden/generate.py"))
+        };
+        let input = read("input.bin");
+        for window_bits in [8, 10, 12] {
+            for literal_bits in [7, 8] {
+                let name = format!("w{window_bits}_l{literal_bits}_greedy.tamp");
+                let expected = read(&name);
+                let config = Config::new()
+                    .window_bits(window_bits)
+                    .unwrap()
+                    .literal_bits(literal_bits)
+                    .unwrap();
+                match window_bits {
+                    8 => check_golden::<256>(config, &input, &expected, &name),
+                    10 => check_golden::<1024>(config, &input, &expected, &name),
+                    _ => check_golden::<4096>(config, &input, &expected, &name),
+                }
+            }
+        }
+    }
+
+    fn check_golden<const N: usize>(config: Config, input: &[u8], expected: &[u8], name: &str) {
+        let (mut decompressor, header) = Decompressor::<N>::from_header(expected).unwrap();
+        let mut output = std::vec![0u8; input.len()];
+        let (_, written) = decompressor
+            .decompress_chunk(&expected[header..], &mut output)
+            .unwrap();
+        assert_eq!(&output[..written], input, "{name}: decoded output differs");
+
+        let compressed = compress_split::<N>(config, input, &[1024], 64);
+        assert!(compressed == expected, "{name}: compressed output differs");
+    }
+
+    fn input_strategy() -> impl Strategy<Value = Vec<u8>> {
+        // Random bytes rarely match; a small alphabet exercises long and overlapping matches
+        prop_oneof![vec(any::<u8>(), 0..1500), vec(0u8..4, 0..1500)]
+    }
+
+    /// Compresses `input` through `compress_chunk()` into `output_size` byte buffers.
+    fn compress_chunked<const N: usize>(
+        config: Config,
+        input: &[u8],
+        output_size: usize,
+    ) -> Vec<u8> {
+        let mut compressor = Compressor::<N>::new(config).unwrap();
+        let mut compressed = Vec::new();
+        let mut output = std::vec![0u8; output_size];
+        let mut rest = input;
+        while !rest.is_empty() {
+            let (consumed, written) = compressor.compress_chunk(rest, &mut output).unwrap();
+            assert!(
+                consumed > 0 || written > 0,
+                "no progress into {output_size} bytes"
+            );
+            rest = &rest[consumed..];
+            compressed.extend_from_slice(&output[..written]);
+        }
+        let mut buf = [0u8; 64];
+        let n = compressor.flush(&mut buf, false).unwrap();
+        compressed.extend_from_slice(&buf[..n]);
+        compressed
+    }
+
+    /// Checks that every way of feeding the compressor yields the same stream, and that the
+    /// stream decodes back to `input`. `sizes` are the poll, `compress_chunk()` output and
+    /// `decompress_chunk()` output buffer sizes.
+    fn round_trip<const N: usize>(
+        config: Config,
+        input: &[u8],
+        splits: &[usize],
+        sizes: (usize, usize, usize),
+    ) -> Result<(), TestCaseError> {
+        let (poll_size, compress_size, decompress_size) = sizes;
+        let compressed = compress_split::<N>(config.clone(), input, splits, poll_size);
+        let chunked = compress_chunked::<N>(config.clone(), input, compress_size);
+        let whole = compress_split::<N>(config, input, &[input.len().max(1)], 64);
+        prop_assert_eq!(&compressed, &whole);
+        prop_assert_eq!(&chunked, &whole);
+
+        let (mut decompressor, header) = Decompressor::<N>::from_header(&compressed).unwrap();
+        let mut stream = &compressed[header..];
+        let mut output = std::vec![0u8; decompress_size];
+        let mut decompressed = Vec::new();
+        loop {
+            let (consumed, written) = decompressor.decompress_chunk(stream, &mut output).unwrap();
+            stream = &stream[consumed..];
+            decompressed.extend_from_slice(&output[..written]);
+            if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+        prop_assert_eq!(decompressed, input);
+        Ok(())
+    }
+
+    proptest! {
+        #[cfg_attr(miri, ignore = "calls into the C library")]
+        #[test]
+        fn prop_round_trip(
+            input in input_strategy(),
+            window_bits in 8u8..=15,
+            splits in vec(1usize..300, 1..16),
+            poll_size in MAX_POLL..64,
+            compress_size in MAX_POLL..256,
+            decompress_size in 1usize..64,
+            lazy in any::<bool>(),
+        ) {
+            let config = Config::new().window_bits(window_bits).unwrap().lazy_matching(lazy);
+            let sizes = (poll_size, compress_size, decompress_size);
+            match window_bits {
+                8 => round_trip::<256>(config, &input, &splits, sizes)?,
+                9 => round_trip::<512>(config, &input, &splits, sizes)?,
+                10 => round_trip::<1024>(config, &input, &splits, sizes)?,
+                11 => round_trip::<2048>(config, &input, &splits, sizes)?,
+                12 => round_trip::<4096>(config, &input, &splits, sizes)?,
+                13 => round_trip::<8192>(config, &input, &splits, sizes)?,
+                14 => round_trip::<16384>(config, &input, &splits, sizes)?,
+                _ => round_trip::<32768>(config, &input, &splits, sizes)?,
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_error() {
+        use std::io::ErrorKind;
+        let err = std::io::Error::from(Error::OutputFull);
+        assert_eq!(err.kind(), ErrorKind::WriteZero);
+        let err = std::io::Error::from(Error::Authentication);
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let source = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert!(matches!(*source, Error::Authentication));
+    }
 }
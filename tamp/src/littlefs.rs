@@ -0,0 +1,85 @@
+//! Transparent compression for `littlefs2` files.
+//!
+//! `CompressedFileWriter` compresses everything written through it into an open file, header
+//! first, and `CompressedFileReader` reads that header back from the start of the file before
+//! decompressing. Both borrow the file, so they fit inside littlefs2's `open_file_and_then`
+//! style closures.
+//!
+//! ```ignore
+//! fs.create_file_and_then(path!("log.tamp"), |file| {
+//!     let mut writer = CompressedFileWriter::new(file, Compressor1K::new_default());
+//!     writer.write(b"boot ok\n")?;
+//!     writer.finish()
+//! })?;
+//! ```
+
+use littlefs2::io::{Error as IoError, Read, Write};
+use crate::file::{ChunkReader, ChunkWriter};
+use crate::Compressor;
+
+pub use crate::file::FileError;
+
+/// Bytes handed to littlefs per call, matching a typical cache size.
+const CHUNK: usize = 256;
+
+/// Compresses into a littlefs2 file, header included.
+///
+/// Compressed data reaches the file in 256-byte chunks until `flush()` or `finish()`.
+pub struct CompressedFileWriter<'f, F: Write, const N: usize> {
+    file: &'f F,
+    inner: ChunkWriter<N, CHUNK>,
+}
+
+impl<'f, F: Write, const N: usize> CompressedFileWriter<'f, F, N> {
+    /// Creates a writer compressing with a fresh `compressor` into `file`.
+    pub fn new(file: &'f F, compressor: Compressor<N>) -> Self {
+        Self {
+            file,
+            inner: ChunkWriter::new(compressor),
+        }
+    }
+
+    /// Compresses `data` into the file.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), FileError<IoError>> {
+        let file = self.file;
+        self.inner.write(data, &mut |chunk| file.write_all(chunk))
+    }
+
+    /// Writes everything compressed so far to the file and flushes it, keeping the stream
+    /// open, e.g. before a power-down the log has to survive.
+    pub fn flush(&mut self) -> Result<(), FileError<IoError>> {
+        let file = self.file;
+        self.inner.flush(true, &mut |chunk| file.write_all(chunk))?;
+        file.flush().map_err(FileError::Io)
+    }
+
+    /// Performs the final flush and returns the compressed length of the file.
+    pub fn finish(mut self) -> Result<usize, FileError<IoError>> {
+        let file = self.file;
+        let len = self.inner.flush(false, &mut |chunk| file.write_all(chunk))?;
+        file.flush().map_err(FileError::Io)?;
+        Ok(len)
+    }
+}
+
+/// Decompresses a littlefs2 file written by `CompressedFileWriter`, or any tamp stream.
+pub struct CompressedFileReader<'f, F: Read, const N: usize> {
+    file: &'f F,
+    inner: ChunkReader<N, CHUNK>,
+}
+
+impl<'f, F: Read, const N: usize> CompressedFileReader<'f, F, N> {
+    /// Reads the header from the start of `file`. Its window size must match `N`.
+    pub fn open(file: &'f F) -> Result<Self, FileError<IoError>> {
+        Ok(Self {
+            file,
+            inner: ChunkReader::open(&mut |buf| file.read(buf))?,
+        })
+    }
+
+    /// Decompresses into `output`, returning the bytes written; 0 at the end of the file.
+    pub fn read(&mut self, output: &mut [u8]) -> Result<usize, FileError<IoError>> {
+        let file = self.file;
+        self.inner.read(output, &mut |buf| file.read(buf))
+    }
+}
@@ -0,0 +1,229 @@
+//! Several compressed streams interleaved over one transport.
+//!
+//! Each channel is an independent tamp stream with its own window, so logs, telemetry and
+//! events can share a UART without serializing them globally. On the wire every piece of a
+//! channel's stream is a frame `[u8 channel][u8 len][len bytes]`; frames of different
+//! channels may interleave freely.
+//!
+//! ```ignore
+//! let mut mux = Mux::<1024, 3, _>::new(config, |frame| uart.write(frame))?;
+//! mux.write(LOGS, b"boot ok\n")?;
+//! mux.write(TELEMETRY, &sample)?;
+//! mux.flush(LOGS)?;
+//! ```
+
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+#[cfg(feature = "compressor")]
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+#[cfg(feature = "compressor")]
+use crate::{Compressor, Config};
+
+/// Largest payload of one frame.
+#[cfg(feature = "compressor")]
+const MAX_PAYLOAD: usize = u8::MAX as usize;
+/// Channel and length bytes in front of every payload.
+const FRAME_HEADER: usize = 2;
+
+/// Compresses up to `C` channels with `N`-byte windows into one framed byte stream.
+///
+/// A channel's compressor is created on its first write, so unused channels cost no CPU.
+#[cfg(feature = "compressor")]
+pub struct Mux<const N: usize, const C: usize, F: FnMut(&[u8])> {
+    config: Config,
+    compressors: [Option<Compressor<N>>; C],
+    frame: [u8; FRAME_HEADER + MAX_PAYLOAD],
+    sink: F,
+}
+
+#[cfg(feature = "compressor")]
+impl<const N: usize, const C: usize, F: FnMut(&[u8])> Mux<N, C, F> {
+    /// Creates a multiplexer compressing every channel with `config`, passing frames to `sink`.
+    pub fn new(config: Config, sink: F) -> Result<Self, Error> {
+        const { assert!(C <= 256, "at most 256 channels") };
+        if config.window_size() != N {
+            return Err(Error::InvalidConfig(
+                "Buffer size N must equal 2^window_bits",
+            ));
+        }
+        Ok(Self {
+            config,
+            compressors: core::array::from_fn(|_| None),
+            frame: [0; FRAME_HEADER + MAX_PAYLOAD],
+            sink,
+        })
+    }
+
+    /// Compresses `data` on `channel`, emitting frames for the output it produces.
+    pub fn write(&mut self, channel: usize, data: &[u8]) -> Result<(), Error> {
+        let slot = self
+            .compressors
+            .get_mut(channel)
+            .ok_or(Error::InvalidConfig("Mux channel out of range"))?;
+        if slot.is_none() {
+            *slot = Some(Compressor::new(self.config.clone())?);
+        }
+        let compressor = slot.as_mut().unwrap();
+        let mut buf = [0u8; MAX_POLL];
+        let mut len = 0;
+        compressor.sink_all(data, &mut buf, &mut |polled| {
+            if len + polled.len() > MAX_PAYLOAD {
+                emit(&mut self.sink, &mut self.frame, channel, len);
+                len = 0;
+            }
+            self.frame[FRAME_HEADER + len..][..polled.len()].copy_from_slice(polled);
+            len += polled.len();
+        })?;
+        if len > 0 {
+            emit(&mut self.sink, &mut self.frame, channel, len);
+        }
+        Ok(())
+    }
+
+    /// Emits everything written to `channel` so far, keeping its stream open.
+    pub fn flush(&mut self, channel: usize) -> Result<(), Error> {
+        self.flush_channel(channel, true)
+    }
+
+    /// Terminates the stream of every channel that was written to.
+    pub fn finish(mut self) -> Result<(), Error> {
+        for channel in 0..C {
+            self.flush_channel(channel, false)?;
+        }
+        Ok(())
+    }
+
+    fn flush_channel(&mut self, channel: usize, write_token: bool) -> Result<(), Error> {
+        let compressor = match self.compressors.get_mut(channel) {
+            Some(Some(compressor)) => compressor,
+            Some(None) => return Ok(()),
+            None => return Err(Error::InvalidConfig("Mux channel out of range")),
+        };
+        let out = &mut self.frame[FRAME_HEADER..][..MAX_FLUSH];
+        let len = compressor.flush(out, write_token)?;
+        if len > 0 {
+            emit(&mut self.sink, &mut self.frame, channel, len);
+        }
+        Ok(())
+    }
+}
+
+/// Fills in the frame header and passes the frame to `sink`.
+#[cfg(feature = "compressor")]
+fn emit(sink: &mut impl FnMut(&[u8]), frame: &mut [u8], channel: usize, len: usize) {
+    frame[0] = channel as u8;
+    frame[1] = len as u8;
+    sink(&frame[..FRAME_HEADER + len]);
+}
+
+/// Splits a `Mux` byte stream back into channels, decompressing each.
+///
+/// Decompressed bytes are passed to `sink` together with their channel. Each channel's
+/// decompressor is configured from its stream header and must use an `N`-byte window.
+#[cfg(feature = "decompressor")]
+pub struct Demux<const N: usize, const C: usize, F: FnMut(usize, &[u8])> {
+    decompressors: [Option<Decompressor<N>>; C],
+    header: [u8; FRAME_HEADER],
+    header_len: usize,
+    remaining: usize,
+    sink: F,
+}
+
+#[cfg(feature = "decompressor")]
+impl<const N: usize, const C: usize, F: FnMut(usize, &[u8])> Demux<N, C, F> {
+    /// Creates a demultiplexer passing `(channel, bytes)` to `sink`.
+    pub fn new(sink: F) -> Self {
+        Self {
+            decompressors: core::array::from_fn(|_| None),
+            header: [0; FRAME_HEADER],
+            header_len: 0,
+            remaining: 0,
+            sink,
+        }
+    }
+
+    /// Accepts multiplexed bytes in any split.
+    /// Returns `InvalidConfig` for a frame on a channel at or above `C`.
+    pub fn write(&mut self, mut input: &[u8]) -> Result<(), Error> {
+        while let Some((&byte, rest)) = input.split_first() {
+            if self.remaining == 0 {
+                self.header[self.header_len] = byte;
+                self.header_len += 1;
+                input = rest;
+                if self.header_len == FRAME_HEADER {
+                    if self.header[0] as usize >= C {
+                        return Err(Error::InvalidConfig("Mux channel out of range"));
+                    }
+                    self.remaining = self.header[1] as usize;
+                    self.header_len = 0;
+                }
+                continue;
+            }
+            let channel = self.header[0] as usize;
+            let n = self.remaining.min(input.len());
+            let mut payload = &input[..n];
+            input = &input[n..];
+            self.remaining -= n;
+
+            let decompressor = match &mut self.decompressors[channel] {
+                Some(decompressor) => decompressor,
+                slot => {
+                    let (decompressor, header) = Decompressor::from_header(payload)?;
+                    payload = &payload[header..];
+                    slot.insert(decompressor)
+                }
+            };
+            let mut out = [0u8; 64];
+            loop {
+                let (consumed, written) = decompressor.decompress_chunk(payload, &mut out)?;
+                (self.sink)(channel, &out[..written]);
+                payload = &payload[consumed..];
+                if consumed == 0 && written == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_mux_round_trip() {
+        let logs: Vec<u8> = (0..1480)
+            .map(|i| b"log line ok\n"[i % 12] ^ (i / 500) as u8)
+            .collect();
+        let telemetry: Vec<u8> = (0..2000u32).map(|i| (i / 16 % 40) as u8).collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let mut wire = Vec::new();
+        let mut mux =
+            Mux::<256, 3, _>::new(config, |frame: &[u8]| wire.extend_from_slice(frame)).unwrap();
+        for (log, sample) in logs.chunks(37).zip(telemetry.chunks(50)) {
+            mux.write(0, log).unwrap();
+            mux.write(2, sample).unwrap();
+        }
+        mux.flush(0).unwrap();
+        mux.finish().unwrap();
+
+        let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+        let mut demux = Demux::<256, 3, _>::new(|channel, bytes: &[u8]| {
+            channels[channel].extend_from_slice(bytes)
+        });
+        for chunk in wire.chunks(23) {
+            demux.write(chunk).unwrap();
+        }
+        assert_eq!(channels[0], logs);
+        assert!(channels[1].is_empty());
+        assert_eq!(channels[2], telemetry);
+
+        let mut demux = Demux::<256, 2, _>::new(|_, _: &[u8]| {});
+        assert!(demux.write(&[2, 1, 0]).is_err());
+    }
+}
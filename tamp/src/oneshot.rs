@@ -0,0 +1,93 @@
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "compressor")]
+use crate::DynCompressor;
+#[cfg(feature = "compressor")]
+use crate::compressor::MAX_FLUSH;
+#[cfg(feature = "decompressor")]
+use crate::DynDecompressor;
+use crate::{Config, Error};
+
+/// Compresses all of `input` into a new `Vec`, header and final flush included.
+///
+/// The output is sized up front with `Config::max_compressed_size()`, so it is allocated
+/// once and shrunk to fit at the end.
+#[cfg(feature = "compressor")]
+pub fn compress_to_vec(input: &[u8], config: Config) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::trace_span!(target: "tamp", "compress_to_vec", len = input.len()).entered();
+    let mut output = vec![0u8; config.max_compressed_size(input.len()) + MAX_FLUSH];
+    let mut window = vec![0u8; config.window_size()];
+    let mut compressor = DynCompressor::new(config, &mut window)?;
+    let mut rest = input;
+    let mut written = 0;
+    while !rest.is_empty() {
+        if output.len() - written < MAX_FLUSH {
+            output.resize(output.len() * 2, 0);
+        }
+        let (consumed, n) = compressor.compress_chunk(rest, &mut output[written..])?;
+        rest = &rest[consumed..];
+        written += n;
+    }
+    if output.len() - written < MAX_FLUSH {
+        output.resize(written + MAX_FLUSH, 0);
+    }
+    written += compressor.flush(&mut output[written..], false)?;
+    output.truncate(written);
+    output.shrink_to_fit();
+    Ok(output)
+}
+
+/// Decompresses a complete stream, header included, into a new `Vec`.
+///
+/// The window size comes from the header. The output starts at twice the input length and
+/// doubles whenever it fills up.
+#[cfg(feature = "decompressor")]
+pub fn decompress_to_vec(input: &[u8]) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::trace_span!(target: "tamp", "decompress_to_vec", len = input.len()).entered();
+    let (config, header) = Config::from_header(input)?;
+    let mut window = vec![0u8; config.window_size()];
+    let mut decompressor = DynDecompressor::new(config, &mut window)?;
+    let mut output = vec![0u8; (input.len() * 2).max(64)];
+    let mut rest = &input[header..];
+    let mut written = 0;
+    loop {
+        if written == output.len() {
+            output.resize(output.len() * 2, 0);
+        }
+        let (consumed, n) = decompressor.decompress_chunk(rest, &mut output[written..])?;
+        rest = &rest[consumed..];
+        written += n;
+        if consumed == 0 && n == 0 {
+            break;
+        }
+    }
+    output.truncate(written);
+    Ok(output)
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_vec_round_trip() {
+        let text: Vec<u8> = (0..20_000u32).map(|i| b"sensor,42,ok\n"[(i % 13) as usize]).collect();
+        let config = Config::new().window_bits(12).unwrap();
+        let compressed = compress_to_vec(&text, config).unwrap();
+        assert!(compressed.len() * 10 < text.len());
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), text);
+
+        let noise: Vec<u8> = (0..3000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let compressed = compress_to_vec(&noise, config.clone()).unwrap();
+        assert!(compressed.len() <= config.max_compressed_size(noise.len()));
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), noise);
+    }
+}
@@ -0,0 +1,140 @@
+//! Page-aligned compressed output for flash with a fixed program granularity.
+//!
+//! NOR and NAND parts often have to be programmed a whole page at a time. `PagedCompressor`
+//! collects the compressed stream in a `P` byte page buffer and hands each page to a commit
+//! callback exactly once, when it is full. `finish()` commits the last page padded with the
+//! erased value, so the callback only ever sees complete pages.
+//!
+//! ```ignore
+//! let mut pages = PagedCompressor::<1024, 256>::new(&mut compressor);
+//! pages.write(&record, |page| flash.program(addr.next(), page))?;
+//! let len = pages.finish(|page| flash.program(addr.next(), page))?;
+//! ```
+
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Compressor, Error};
+
+/// Compressor wrapper that emits its output in whole `P` byte pages.
+pub struct PagedCompressor<'a, const N: usize, const P: usize> {
+    compressor: &'a mut Compressor<N>,
+    pages: PageBuffer<P>,
+}
+
+impl<'a, const N: usize, const P: usize> PagedCompressor<'a, N, P> {
+    /// Wraps `compressor`, padding the last page with 0xFF, the erased value of NOR flash.
+    pub fn new(compressor: &'a mut Compressor<N>) -> Self {
+        const { assert!(P > 0, "Page size must be non-zero") };
+        Self {
+            compressor,
+            pages: PageBuffer {
+                page: [0; P],
+                fill: 0,
+                padding: 0xFF,
+                len: 0,
+            },
+        }
+    }
+
+    /// Pads the last page with `byte` instead, e.g. 0x00 for flash that erases to zero.
+    pub fn with_padding(mut self, byte: u8) -> Self {
+        self.pages.padding = byte;
+        self
+    }
+
+    /// Compresses `input`, passing every page that fills up to `commit`.
+    pub fn write(&mut self, input: &[u8], mut commit: impl FnMut(&[u8; P])) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_POLL];
+        self.compressor.sink_all(input, &mut buf, &mut |bytes| {
+            self.pages.push(bytes, &mut commit)
+        })?;
+        Ok(())
+    }
+
+    /// Compresses everything written so far into the page buffer, keeping the stream open.
+    /// Full pages are committed; a partial page stays buffered.
+    pub fn flush(&mut self, mut commit: impl FnMut(&[u8; P])) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut buf, true)?;
+        self.pages.push(&buf[..n], &mut commit);
+        Ok(())
+    }
+
+    /// Performs the final flush and commits the last page, padded.
+    /// Returns the length of the compressed stream without the padding.
+    pub fn finish(mut self, mut commit: impl FnMut(&[u8; P])) -> Result<usize, Error> {
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut buf, false)?;
+        let pages = &mut self.pages;
+        pages.push(&buf[..n], &mut commit);
+        if pages.fill > 0 {
+            pages.page[pages.fill..].fill(pages.padding);
+            commit(&pages.page);
+        }
+        Ok(pages.len)
+    }
+
+    /// Compressed bytes produced so far, including those still in the page buffer.
+    pub fn len(&self) -> usize {
+        self.pages.len
+    }
+
+    /// Returns true if no compressed bytes were produced yet.
+    pub fn is_empty(&self) -> bool {
+        self.pages.len == 0
+    }
+}
+
+/// The page a `PagedCompressor` fills before committing it.
+struct PageBuffer<const P: usize> {
+    page: [u8; P],
+    fill: usize,
+    padding: u8,
+    len: usize,
+}
+
+impl<const P: usize> PageBuffer<P> {
+    fn push(&mut self, mut bytes: &[u8], commit: &mut impl FnMut(&[u8; P])) {
+        self.len += bytes.len();
+        while !bytes.is_empty() {
+            let n = bytes.len().min(P - self.fill);
+            self.page[self.fill..self.fill + n].copy_from_slice(&bytes[..n]);
+            self.fill += n;
+            bytes = &bytes[n..];
+            if self.fill == P {
+                commit(&self.page);
+                self.fill = 0;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{Config, Decompressor};
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_pages() {
+        let data: [u8; 3000] = core::array::from_fn(|i| ((i % 31) ^ (i / 200)) as u8);
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut pages = PagedCompressor::<256, 64>::new(&mut compressor);
+        let mut flash = Vec::new();
+        for record in data.chunks(100) {
+            pages.write(record, |page| flash.push(*page)).unwrap();
+        }
+        let len = pages.finish(|page| flash.push(*page)).unwrap();
+        assert_eq!(flash.len(), len.div_ceil(64));
+        let stream = flash.concat();
+        assert!(stream[len..].iter().all(|&b| b == 0xFF));
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 3000];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &data[..]);
+    }
+}
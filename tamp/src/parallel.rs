@@ -0,0 +1,110 @@
+//! Multi-threaded block compression for host-side archival.
+//!
+//! The input is split into fixed-size blocks that are compressed independently on the rayon
+//! thread pool, written in the block-indexed format described in `tamp::blocks`, and decoded
+//! the same way.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use rayon::prelude::*;
+use crate::blocks::{BlockTable, TABLE_HEADER};
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Config, DynCompressor, Error};
+
+/// Compresses `input` in independent `block_size` blocks in parallel.
+/// Returns the block table followed by the compressed blocks.
+pub fn compress_blocks(input: &[u8], block_size: usize, config: Config) -> Result<Vec<u8>, Error> {
+    if block_size == 0 || u32::try_from(block_size).is_err() {
+        return Err(Error::InvalidConfig(
+            "Block size must be between 1 and u32::MAX",
+        ));
+    }
+    let blocks = input
+        .par_chunks(block_size)
+        .map(|block| compress_block(block, &config))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let streams: usize = blocks.iter().map(Vec::len).sum();
+    let mut output = Vec::with_capacity(TABLE_HEADER + 8 * blocks.len() + streams);
+    output.extend_from_slice(&(block_size as u32).to_le_bytes());
+    output.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    output.extend_from_slice(&(input.len() as u64).to_le_bytes());
+    let mut end = 0u64;
+    for block in &blocks {
+        end += block.len() as u64;
+        output.extend_from_slice(&end.to_le_bytes());
+    }
+    for block in &blocks {
+        output.extend_from_slice(block);
+    }
+    Ok(output)
+}
+
+/// Compresses one block into a complete stream.
+fn compress_block(input: &[u8], config: &Config) -> Result<Vec<u8>, Error> {
+    let mut window = vec![0u8; config.window_size()];
+    let mut compressor = DynCompressor::new(config.clone(), &mut window)?;
+    let mut output = Vec::with_capacity(input.len() / 2);
+    let mut buf = [0u8; MAX_FLUSH];
+    compressor.sink_all(input, &mut buf[..MAX_POLL], &mut |polled| {
+        output.extend_from_slice(polled)
+    })?;
+    let n = compressor.flush(&mut buf, false)?;
+    output.extend_from_slice(&buf[..n]);
+    Ok(output)
+}
+
+/// Decompresses every block of an archive from `compress_blocks` in parallel.
+pub fn decompress_blocks(archive: &[u8]) -> Result<Vec<u8>, Error> {
+    let table = BlockTable::parse(archive)?;
+    let total_len = usize::try_from(table.total_len())
+        .map_err(|_| Error::InvalidConfig("Archive too large for this platform"))?;
+    let mut output = vec![0u8; total_len];
+    output
+        .par_chunks_mut(table.block_size())
+        .enumerate()
+        .try_for_each(|(index, block)| {
+            let stream = table
+                .block(index)
+                .ok_or(Error::InvalidConfig("Block index out of range"))?;
+            let (config, _) = Config::from_header(stream)?;
+            let mut window = vec![0u8; config.window_size()];
+            table.decompress_block(index, &mut window, block).map(|_| ())
+        })?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decompressor;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_compress_blocks() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251 / 3) as u8).collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let archive = compress_blocks(&input, 4096, config).unwrap();
+        assert_eq!(&archive[..8], &[0, 16, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(archive[8..16], (input.len() as u64).to_le_bytes());
+
+        let table_end = TABLE_HEADER + 3 * 8;
+        let mut start = 0;
+        for (i, block) in input.chunks(4096).enumerate() {
+            let offset = TABLE_HEADER + 8 * i;
+            let end = u64::from_le_bytes(archive[offset..offset + 8].try_into().unwrap()) as usize;
+            let stream = &archive[table_end + start..table_end + end];
+            let (mut decompressor, header) = Decompressor::<256>::from_header(stream).unwrap();
+            let mut output = vec![0u8; block.len()];
+            let (_, n) = decompressor
+                .decompress_chunk(&stream[header..], &mut output)
+                .unwrap();
+            assert_eq!(&output[..n], block);
+            start = end;
+        }
+        assert_eq!(table_end + start, archive.len());
+
+        assert_eq!(decompress_blocks(&archive).unwrap(), input);
+        assert!(compress_blocks(&input, 0, Config::new()).is_err());
+    }
+}
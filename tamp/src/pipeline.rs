@@ -0,0 +1,122 @@
+//! Acquisition pipeline: an ISR fills a `heapless::spsc::Queue`, a task compresses it.
+//!
+//! The ISR side only copies bytes into the queue with `produce()`, which never blocks and
+//! reports how much fit. The task side calls `pump()` whenever it runs; it moves queued
+//! bytes into the compressor and polls compressed output into a `Sink`. When the sink is
+//! short of room, `pump()` stops dequeuing, so the queue fills up and `produce()` starts
+//! refusing bytes: backpressure reaches the ISR instead of output being dropped.
+//!
+//! ```ignore
+//! static mut QUEUE: Queue<u8, 512> = Queue::new();
+//! let (mut producer, mut consumer) = unsafe { QUEUE.split() };
+//! // in the ADC interrupt
+//! overruns += samples.len() - pipeline::produce(&mut producer, &samples);
+//! // in the main loop
+//! pipeline::pump(&mut compressor, &mut consumer, &mut uart_buffer)?;
+//! ```
+
+use heapless::spsc::{Consumer, Producer};
+use crate::compressor::{INPUT_BUFFER, MAX_POLL};
+use crate::sink::Sink;
+use crate::{Compressor, Error};
+
+/// Enqueues as many of `bytes` as fit, returning how many did. Safe to call from an ISR.
+pub fn produce<const Q: usize>(producer: &mut Producer<'_, u8, Q>, bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take_while(|&&b| producer.enqueue(b).is_ok())
+        .count()
+}
+
+/// Compresses queued bytes into `sink` until the queue is empty or `sink` is short of room.
+/// Returns the number of bytes dequeued. Call it from thread context, never from the ISR.
+pub fn pump<const N: usize, const Q: usize>(
+    compressor: &mut Compressor<N>,
+    consumer: &mut Consumer<'_, u8, Q>,
+    sink: &mut impl Sink,
+) -> Result<usize, Error> {
+    let mut buf = [0u8; MAX_POLL];
+    let mut dequeued = 0;
+    loop {
+        if compressor.is_full() {
+            if sink.remaining() < MAX_POLL {
+                return Ok(dequeued);
+            }
+            let n = compressor.poll(&mut buf)?;
+            sink.push(&buf[..n]).map_err(|_| Error::OutputFull)?;
+        }
+        // Dequeue only what the compressor can take, so the rest stays queued, and sink it
+        // in one call
+        let mut staged = [0u8; INPUT_BUFFER];
+        let free = INPUT_BUFFER - compressor.pending_input_bytes();
+        let n = staged[..free]
+            .iter_mut()
+            .zip(core::iter::from_fn(|| consumer.dequeue()))
+            .map(|(slot, byte)| *slot = byte)
+            .count();
+        compressor.sink(&staged[..n]);
+        dequeued += n;
+        if n < free {
+            return Ok(dequeued);
+        }
+    }
+}
+
+/// Pumps the queue dry and performs the final flush into `sink`.
+/// Returns `OutputFull` if `sink` runs out of room first; the stream is then unfinished.
+pub fn finish<const N: usize, const Q: usize>(
+    compressor: &mut Compressor<N>,
+    consumer: &mut Consumer<'_, u8, Q>,
+    sink: &mut impl Sink,
+) -> Result<(), Error> {
+    pump(compressor, consumer, sink)?;
+    if consumer.ready() {
+        return Err(Error::OutputFull);
+    }
+    compressor.flush_into(sink, false)
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::sink::SliceSink;
+    use crate::{Config, Decompressor};
+    use heapless::spsc::Queue;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_pipeline_backpressure() {
+        let samples: [u8; 2000] = core::array::from_fn(|i| (i % 50 + i / 400) as u8);
+        let mut queue = Queue::<u8, 64>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut stream = [0u8; 2048];
+        let mut len = 0;
+        let mut sent = 0;
+        let mut refused = false;
+        while sent < samples.len() {
+            // The "ISR" offers 40 bytes per tick, faster than the task drains the queue
+            let end = (sent + 40).min(samples.len());
+            let n = produce(&mut producer, &samples[sent..end]);
+            refused |= n < end - sent;
+            sent += n;
+            // The "task" drains into a 16 byte transmit buffer per tick
+            let mut tx = [0u8; 16];
+            let mut sink = SliceSink::new(&mut tx);
+            pump(&mut compressor, &mut consumer, &mut sink).unwrap();
+            stream[len..len + sink.len()].copy_from_slice(sink.written());
+            len += sink.len();
+        }
+        assert!(refused);
+        let mut sink = SliceSink::new(&mut stream[len..]);
+        finish(&mut compressor, &mut consumer, &mut sink).unwrap();
+        len += sink.len();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&stream).unwrap();
+        let mut output = [0u8; 2000];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &samples[..]);
+    }
+}
@@ -0,0 +1,197 @@
+//! Fixed-capacity pools of codec instances for many short-lived streams.
+//!
+//! Gateways handling one stream per connection can keep `K` compressors or decompressors
+//! inline in a `Pool` instead of creating a new window for every stream. `acquire()` resets
+//! a free instance in place and hands it out behind a guard that returns it on drop.
+//!
+//! ```ignore
+//! let pool = Pool::<Decompressor<1024>, 8>::new();
+//! let mut decompressor = pool.acquire(config)?;
+//! decompressor.decompress_chunk(&packet, &mut output)?;
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::{Config, Error};
+
+/// A codec that a `Pool` can create and reset.
+pub trait Poolable: Sized {
+    /// Creates an instance for a new stream.
+    fn create(config: Config) -> Result<Self, Error>;
+    /// Prepares an existing instance for a new stream.
+    fn reset(&mut self, config: Config) -> Result<(), Error>;
+}
+
+#[cfg(feature = "compressor")]
+impl<const N: usize> Poolable for Compressor<N> {
+    fn create(config: Config) -> Result<Self, Error> {
+        Self::new(config)
+    }
+
+    fn reset(&mut self, config: Config) -> Result<(), Error> {
+        Compressor::reset(self, config)
+    }
+}
+
+#[cfg(feature = "decompressor")]
+impl<const N: usize> Poolable for Decompressor<N> {
+    fn create(config: Config) -> Result<Self, Error> {
+        Self::new(config)
+    }
+
+    fn reset(&mut self, config: Config) -> Result<(), Error> {
+        Decompressor::reset(self, config)
+    }
+}
+
+/// Usage counters of a `Pool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Successful `acquire()` calls.
+    pub acquired: u64,
+    /// `acquire()` calls that found every instance in use.
+    pub exhausted: u64,
+    /// Instances currently handed out.
+    pub in_use: usize,
+    /// Most instances handed out at once.
+    pub high_water: usize,
+}
+
+/// Up to `K` instances of `T`, created on first use and reset on every later one.
+pub struct Pool<T, const K: usize> {
+    slots: [UnsafeCell<Option<T>>; K],
+    used: [Cell<bool>; K],
+    stats: Cell<PoolStats>,
+}
+
+impl<T: Poolable, const K: usize> Pool<T, K> {
+    /// Creates a pool with no instances created yet.
+    pub fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| UnsafeCell::new(None)),
+            used: core::array::from_fn(|_| Cell::new(false)),
+            stats: Cell::new(PoolStats::default()),
+        }
+    }
+
+    /// Hands out a free instance configured with `config`.
+    /// Returns `PoolExhausted` if all `K` instances are in use.
+    pub fn acquire(&self, config: Config) -> Result<PoolGuard<'_, T>, Error> {
+        let mut stats = self.stats.get();
+        let Some(index) = self.used.iter().position(|used| !used.get()) else {
+            stats.exhausted += 1;
+            self.stats.set(stats);
+            return Err(Error::PoolExhausted);
+        };
+        // SAFETY: `used[index]` was clear, so no guard references this slot
+        let slot = unsafe { &mut *self.slots[index].get() };
+        match slot {
+            Some(value) => value.reset(config)?,
+            None => *slot = Some(T::create(config)?),
+        }
+        self.used[index].set(true);
+        stats.acquired += 1;
+        stats.in_use += 1;
+        stats.high_water = stats.high_water.max(stats.in_use);
+        self.stats.set(stats);
+        Ok(PoolGuard {
+            value: slot.as_mut().unwrap(),
+            used: &self.used[index],
+            stats: &self.stats,
+        })
+    }
+
+    /// Returns the usage counters.
+    pub fn stats(&self) -> PoolStats {
+        self.stats.get()
+    }
+}
+
+impl<T: Poolable, const K: usize> Default for Pool<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An instance borrowed from a `Pool`, returned to it on drop.
+pub struct PoolGuard<'a, T> {
+    value: &'a mut T,
+    used: &'a Cell<bool>,
+    stats: &'a Cell<PoolStats>,
+}
+
+impl<T> Deref for PoolGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for PoolGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Drop for PoolGuard<'_, T> {
+    fn drop(&mut self) {
+        self.used.set(false);
+        let mut stats = self.stats.get();
+        stats.in_use -= 1;
+        self.stats.set(stats);
+    }
+}
+
+#[cfg(all(test, feature = "compressor"))]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_pool_reuse() {
+        let data: [u8; 500] = core::array::from_fn(|i| b"session "[i % 8] ^ (i / 100) as u8);
+        let config = Config::new().window_bits(8).unwrap();
+        let mut expected = [0u8; 600];
+        let len = Compressor::<256>::new(config.clone())
+            .unwrap()
+            .compress_all(&data, &mut expected)
+            .unwrap();
+
+        let pool = Pool::<Compressor<256>, 2>::new();
+        let mut first = pool.acquire(config.clone()).unwrap();
+        let second = pool.acquire(config.clone()).unwrap();
+        assert!(matches!(
+            pool.acquire(config.clone()),
+            Err(Error::PoolExhausted)
+        ));
+        let mut output = [0u8; 600];
+        let (_, n) = first.compress_chunk(&data[..100], &mut output).unwrap();
+        assert!(n > 0);
+        drop(first);
+
+        // The reused instance starts a fresh stream
+        let mut again = pool.acquire(config.clone()).unwrap();
+        assert_eq!(again.compress_all(&data, &mut output).unwrap(), len);
+        assert_eq!(output[..len], expected[..len]);
+        drop((again, second));
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                acquired: 3,
+                exhausted: 1,
+                in_use: 0,
+                high_water: 2,
+            }
+        );
+        assert!(matches!(
+            pool.acquire(Config::new()),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert_eq!(pool.stats().in_use, 0);
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::vec;
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Compressor, Config, DynCompressor, Error, budget};
+
+/// Compressed length of `sample`, header included, discarding the output.
+fn compressed_len(sample: &[u8], config: &Config) -> Result<usize, Error> {
+    let mut window = vec![0u8; config.window_size()];
+    let mut compressor = DynCompressor::new(config.clone(), &mut window)?;
+    let mut buf = [0u8; MAX_FLUSH];
+    let compressed = compressor.sink_all(sample, &mut buf[..MAX_POLL], &mut |_| {})?;
+    Ok(compressed + compressor.flush(&mut buf, false)?)
+}
+
+impl Config {
+    /// Picks the window size and literal width that compress `samples` best while a
+    /// `Compressor` fits in `ram_budget` bytes.
+    ///
+    /// Every sample is compressed as its own stream with each candidate; literal widths
+    /// below 8 bits are only tried when all sample bytes fit. On a tie the smaller window
+    /// wins. Returns `InvalidConfig` if no window fits the budget.
+    pub fn recommend(samples: &[&[u8]], ram_budget: usize) -> Result<Config, Error> {
+        let overhead = Compressor::<256>::MEMORY_BYTES - 256;
+        let largest = budget::largest_window(ram_budget, overhead);
+        if largest == 0 {
+            return Err(Error::InvalidConfig(
+                "RAM budget too small for any tamp window",
+            ));
+        }
+        let max_byte = samples
+            .iter()
+            .flat_map(|s| s.iter())
+            .copied()
+            .max()
+            .unwrap_or(0);
+        let min_literal = (8 - max_byte.leading_zeros() as u8).max(5);
+
+        let mut best: Option<(usize, Config)> = None;
+        for window_bits in 8..=largest.trailing_zeros() as u8 {
+            for literal_bits in min_literal..=8 {
+                let config = Config::new()
+                    .window_bits(window_bits)?
+                    .literal_bits(literal_bits)?;
+                let mut total = 0;
+                for sample in samples {
+                    total += compressed_len(sample, &config)?;
+                }
+                if best.as_ref().is_none_or(|(len, _)| total < *len) {
+                    best = Some((total, config));
+                }
+            }
+        }
+        Ok(best.map(|(_, config)| config).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_recommend() {
+        let digits: [u8; 3000] = core::array::from_fn(|i| b'0' + (i * i / 7 % 10) as u8);
+        let config = Config::recommend(&[&digits, &digits[..1000]], 1400).unwrap();
+        assert!(config.window_size() <= 1024);
+        assert!(config.literal_bits <= 6);
+        let chosen = compressed_len(&digits, &config).unwrap();
+        assert!(chosen <= compressed_len(&digits, &Config::new().window_bits(8).unwrap()).unwrap());
+
+        assert!(Config::recommend(&[&digits], 100).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Black box recorder: a fixed-capacity ring of compressed log frames.
+//!
+//! Records are compressed into a frame buffer. A full frame is sealed as an independent tamp
+//! stream and appended to the ring, evicting the oldest frames to make room. `drain()` dumps
+//! the ring as a sequence of `[u16 LE length][tamp stream]` frames, oldest first.
+
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Compressor, Config, Error};
+
+/// Room kept free in the frame buffer for a poll plus the final flush.
+const FLUSH_MARGIN: usize = MAX_POLL + MAX_FLUSH;
+/// Length prefix stored in front of every frame.
+const PREFIX: usize = 2;
+
+/// Ring of compressed log frames.
+///
+/// `N` is the compressor window size, `F` the frame buffer size and `C` the ring capacity,
+/// all in bytes. Memory usage: ~N + F + C bytes.
+pub struct RingLog<const N: usize, const F: usize, const C: usize> {
+    config: Config,
+    compressor: Compressor<N>,
+    frame: [u8; F],
+    frame_len: usize,
+    pending: bool,
+    ring: [u8; C],
+    head: usize,
+    used: usize,
+    frames: usize,
+}
+
+impl<const N: usize, const F: usize, const C: usize> RingLog<N, F, C> {
+    /// Creates an empty log. Requires `F` > 40, `F` <= 65535 and `C` >= `F` + 2.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        if F <= FLUSH_MARGIN || F > u16::MAX as usize || C < F + PREFIX {
+            return Err(Error::InvalidConfig("Ring log buffers too small"));
+        }
+        Ok(Self {
+            compressor: Compressor::new(config.clone())?,
+            config,
+            frame: [0; F],
+            frame_len: 0,
+            pending: false,
+            ring: [0; C],
+            head: 0,
+            used: 0,
+            frames: 0,
+        })
+    }
+
+    /// Appends a record to the current frame, sealing it first if it is full.
+    pub fn log(&mut self, mut record: &[u8]) -> Result<(), Error> {
+        self.pending |= !record.is_empty();
+        while !record.is_empty() {
+            if self.compressor.is_full() {
+                if F - self.frame_len < FLUSH_MARGIN {
+                    self.seal()?;
+                    self.pending = true;
+                }
+                self.frame_len += self.compressor.poll(&mut self.frame[self.frame_len..])?;
+            }
+            let consumed = self.compressor.sink(record);
+            record = &record[consumed..];
+        }
+        Ok(())
+    }
+
+    /// Terminates the current frame and moves it into the ring.
+    /// Does nothing if no record was logged since the last seal.
+    pub fn seal(&mut self) -> Result<(), Error> {
+        if !self.pending {
+            return Ok(());
+        }
+        self.frame_len += self
+            .compressor
+            .flush(&mut self.frame[self.frame_len..], false)?;
+        let len = self.frame_len;
+        while C - self.used < PREFIX + len {
+            self.evict();
+        }
+        let tail = (self.head + self.used) % C;
+        copy_in(&mut self.ring, tail, &(len as u16).to_le_bytes());
+        copy_in(&mut self.ring, tail + PREFIX, &self.frame[..len]);
+        self.used += PREFIX + len;
+        self.frames += 1;
+
+        self.compressor = Compressor::new(self.config.clone())?;
+        self.frame_len = 0;
+        self.pending = false;
+        Ok(())
+    }
+
+    /// Number of sealed frames held in the ring.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns true if the ring holds no sealed frames.
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Removes the oldest sealed frame, copying its tamp stream into `output`.
+    /// Returns `None` when the ring is empty, `BufferTooSmall` (frame kept) if `output` is too short.
+    pub fn pop_frame(&mut self, output: &mut [u8]) -> Result<Option<usize>, Error> {
+        if self.frames == 0 {
+            return Ok(None);
+        }
+        let len = self.frame_len_at(self.head);
+        let dst = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        for (i, byte) in dst.iter_mut().enumerate() {
+            *byte = self.ring[(self.head + PREFIX + i) % C];
+        }
+        self.evict();
+        Ok(Some(len))
+    }
+
+    /// Seals the current frame, then passes every frame (length prefix included) to `sink`,
+    /// oldest first, and empties the ring.
+    pub fn drain(&mut self, mut sink: impl FnMut(&[u8])) -> Result<(), Error> {
+        self.seal()?;
+        let end = self.head + self.used;
+        if end <= C {
+            sink(&self.ring[self.head..end]);
+        } else {
+            sink(&self.ring[self.head..]);
+            sink(&self.ring[..end - C]);
+        }
+        self.head = 0;
+        self.used = 0;
+        self.frames = 0;
+        Ok(())
+    }
+
+    fn frame_len_at(&self, pos: usize) -> usize {
+        u16::from_le_bytes([self.ring[pos], self.ring[(pos + 1) % C]]) as usize
+    }
+
+    fn evict(&mut self) {
+        let size = PREFIX + self.frame_len_at(self.head);
+        self.head = (self.head + size) % C;
+        self.used -= size;
+        self.frames -= 1;
+    }
+}
+
+/// Copies `bytes` into `ring` starting at `pos`, wrapping around the end.
+fn copy_in(ring: &mut [u8], pos: usize, bytes: &[u8]) {
+    for (i, &byte) in bytes.iter().enumerate() {
+        ring[(pos + i) % ring.len()] = byte;
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Decompressor;
+    use std::format;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_ringlog_evicts_oldest_frames() {
+        let mut log = RingLog::<256, 64, 256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        for i in 0..500 {
+            log.log(format!("sensor {} ok\n", i).as_bytes()).unwrap();
+        }
+        let mut dump = Vec::new();
+        log.drain(|bytes| dump.extend_from_slice(bytes)).unwrap();
+        assert!(dump.len() <= 256);
+        assert!(log.is_empty());
+
+        let mut text = Vec::new();
+        let mut rest = &dump[..];
+        while !rest.is_empty() {
+            let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+            let frame = &rest[PREFIX..PREFIX + len];
+            let (mut decompressor, header) = Decompressor::<256>::from_header(frame).unwrap();
+            let mut output = [0u8; 1024];
+            let (_, written) = decompressor
+                .decompress_chunk(&frame[header..], &mut output)
+                .unwrap();
+            text.extend_from_slice(&output[..written]);
+            rest = &rest[PREFIX + len..];
+        }
+        // Oldest records were evicted, the newest survive intact
+        assert!(!text.starts_with(b"sensor 0 ok"));
+        assert!(text.ends_with(b"sensor 499 ok\n"));
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_ringlog_seals_full_frame() {
+        let mut log = RingLog::<256, 96, 256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        // Incompressible bytes, one record each, until the frame reaches the seal boundary
+        let mut state = 0x1234_5678u32;
+        let mut logged = Vec::new();
+        while 96 - log.frame_len >= FLUSH_MARGIN {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let byte = state as u8;
+            log.log(&[byte]).unwrap();
+            logged.push(byte);
+        }
+        assert_eq!(log.frames(), 0);
+        log.seal().unwrap();
+        assert_eq!(log.frames(), 1);
+
+        let mut frame = [0u8; 96];
+        let len = log.pop_frame(&mut frame).unwrap().unwrap();
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&frame[..len]).unwrap();
+        let mut output = [0u8; 256];
+        let (_, written) = decompressor
+            .decompress_chunk(&frame[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..written], &logged[..]);
+    }
+}
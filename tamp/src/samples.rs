@@ -0,0 +1,259 @@
+//! 16-bit sample compression for ADC/IMU pipelines.
+//!
+//! Samples are serialized with the chosen byte order, optionally passed through a stride-2
+//! `Delta` filter, and compressed. Partial samples are carried across calls on both ends,
+//! so chunk boundaries may fall anywhere in the byte stream.
+
+#[cfg(feature = "compressor")]
+use crate::Compressor;
+#[cfg(feature = "compressor")]
+use crate::compressor::MAX_POLL;
+#[cfg(feature = "decompressor")]
+use crate::Decompressor;
+use crate::Error;
+use crate::filter::{Delta, Filter};
+
+/// Byte order of samples in the compressed stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u16 {}
+    impl Sealed for i16 {}
+}
+
+/// A 16-bit sample type, implemented for `u16` and `i16`.
+pub trait Sample: sealed::Sealed + Copy {
+    /// Serializes the sample in `endian` byte order.
+    fn to_bytes(self, endian: Endian) -> [u8; 2];
+    /// Deserializes a sample stored in `endian` byte order.
+    fn from_bytes(bytes: [u8; 2], endian: Endian) -> Self;
+}
+
+impl Sample for u16 {
+    fn to_bytes(self, endian: Endian) -> [u8; 2] {
+        match endian {
+            Endian::Little => self.to_le_bytes(),
+            Endian::Big => self.to_be_bytes(),
+        }
+    }
+
+    fn from_bytes(bytes: [u8; 2], endian: Endian) -> Self {
+        match endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes),
+        }
+    }
+}
+
+impl Sample for i16 {
+    fn to_bytes(self, endian: Endian) -> [u8; 2] {
+        (self as u16).to_bytes(endian)
+    }
+
+    fn from_bytes(bytes: [u8; 2], endian: Endian) -> Self {
+        u16::from_bytes(bytes, endian) as i16
+    }
+}
+
+/// Compressor front-end accepting 16-bit samples.
+#[cfg(feature = "compressor")]
+pub struct SampleCompressor<'a, const N: usize> {
+    compressor: &'a mut Compressor<N>,
+    endian: Endian,
+    delta: Option<Delta>,
+    pending: [u8; 16],
+    start: usize,
+    end: usize,
+}
+
+#[cfg(feature = "compressor")]
+impl<'a, const N: usize> SampleCompressor<'a, N> {
+    /// Creates a sample compressor writing samples in `endian` byte order.
+    pub fn new(compressor: &'a mut Compressor<N>, endian: Endian) -> Self {
+        Self {
+            compressor,
+            endian,
+            delta: None,
+            pending: [0; 16],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Applies a stride-2 delta filter before compression. Decompress with `with_delta` too.
+    pub fn with_delta(mut self) -> Self {
+        self.delta = Some(Delta::new(2).expect("stride 2 is valid"));
+        self
+    }
+
+    /// Compresses samples into `output`.
+    /// Returns (samples_consumed, output_written); stops early when `output` is nearly full.
+    pub fn compress<S: Sample>(
+        &mut self,
+        samples: &[S],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        while output.len() - written >= MAX_POLL {
+            if self.compressor.is_full() {
+                written += self.compressor.poll(&mut output[written..])?;
+            } else if self.start < self.end {
+                self.start += self.compressor.sink(&self.pending[self.start..self.end]);
+            } else if consumed < samples.len() {
+                let batch = &samples[consumed..samples.len().min(consumed + 8)];
+                let mut bytes = [0u8; 16];
+                for (chunk, &sample) in bytes.chunks_exact_mut(2).zip(batch) {
+                    chunk.copy_from_slice(&sample.to_bytes(self.endian));
+                }
+                let len = batch.len() * 2;
+                match &mut self.delta {
+                    Some(delta) => delta.encode(&bytes[..len], &mut self.pending),
+                    None => {
+                        self.pending[..len].copy_from_slice(&bytes[..len]);
+                        (len, len)
+                    }
+                };
+                self.start = 0;
+                self.end = len;
+                consumed += batch.len();
+            } else {
+                break;
+            }
+        }
+        Ok((consumed, written))
+    }
+
+    /// Flushes the compressor once all samples passed to `compress` have been consumed.
+    /// Needs about 32 bytes of `output`; see `Compressor::flush` for `write_token`.
+    pub fn flush(&mut self, output: &mut [u8], write_token: bool) -> Result<usize, Error> {
+        let mut written = 0;
+        while self.start < self.end {
+            if self.compressor.is_full() {
+                written += self.compressor.poll(&mut output[written..])?;
+            }
+            self.start += self.compressor.sink(&self.pending[self.start..self.end]);
+        }
+        Ok(written + self.compressor.flush(&mut output[written..], write_token)?)
+    }
+}
+
+/// Decompressor back-end producing 16-bit samples.
+#[cfg(feature = "decompressor")]
+pub struct SampleDecompressor<'a, const N: usize> {
+    decompressor: &'a mut Decompressor<N>,
+    endian: Endian,
+    delta: Option<Delta>,
+    odd: Option<u8>,
+}
+
+#[cfg(feature = "decompressor")]
+impl<'a, const N: usize> SampleDecompressor<'a, N> {
+    /// Creates a sample decompressor reading samples in `endian` byte order.
+    pub fn new(decompressor: &'a mut Decompressor<N>, endian: Endian) -> Self {
+        Self {
+            decompressor,
+            endian,
+            delta: None,
+            odd: None,
+        }
+    }
+
+    /// Reverses the delta filter applied by `SampleCompressor::with_delta`.
+    pub fn with_delta(mut self) -> Self {
+        self.delta = Some(Delta::new(2).expect("stride 2 is valid"));
+        self
+    }
+
+    /// Decompresses `input` into `samples`.
+    /// Returns (input_consumed, samples_written).
+    pub fn decompress<S: Sample>(
+        &mut self,
+        input: &[u8],
+        samples: &mut [S],
+    ) -> Result<(usize, usize), Error> {
+        let (mut consumed, mut written) = (0, 0);
+        let mut buf = [0u8; 64];
+        while written < samples.len() {
+            let room = (2 * (samples.len() - written) - self.odd.is_some() as usize).min(64);
+            let (c, w) = self
+                .decompressor
+                .decompress_chunk(&input[consumed..], &mut buf[..room])?;
+            consumed += c;
+            if let Some(delta) = &mut self.delta {
+                let raw = buf;
+                delta.decode(&raw[..w], &mut buf[..w]);
+            }
+            for &byte in &buf[..w] {
+                match self.odd.take() {
+                    None => self.odd = Some(byte),
+                    Some(first) => {
+                        samples[written] = S::from_bytes([first, byte], self.endian);
+                        written += 1;
+                    }
+                }
+            }
+            if c == 0 && w == 0 {
+                break;
+            }
+        }
+        Ok((consumed, written))
+    }
+
+    /// A trailing byte that did not complete a sample, if the stream has odd length.
+    pub fn remainder(&self) -> Option<u8> {
+        self.odd
+    }
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Config;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_samples_round_trip() {
+        let samples: Vec<i16> = (0..500).map(|i| (i * 13 % 400) as i16 - 200).collect();
+        for endian in [Endian::Little, Endian::Big] {
+            let config = Config::new().window_bits(8).unwrap();
+            let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+            let mut encoder = SampleCompressor::new(&mut compressor, endian).with_delta();
+            let mut compressed = Vec::new();
+            let mut out = [0u8; 40];
+            for chunk in samples.chunks(37) {
+                let mut chunk = chunk;
+                while !chunk.is_empty() {
+                    let (n, w) = encoder.compress(chunk, &mut out).unwrap();
+                    compressed.extend_from_slice(&out[..w]);
+                    chunk = &chunk[n..];
+                }
+            }
+            let w = encoder.flush(&mut out, false).unwrap();
+            compressed.extend_from_slice(&out[..w]);
+            assert!(compressed.len() < samples.len() * 2);
+
+            let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+            let mut decoder = SampleDecompressor::new(&mut decompressor, endian).with_delta();
+            let mut decoded = std::vec![0i16; samples.len()];
+            let mut input = &compressed[header..];
+            let mut total = 0;
+            while total < decoded.len() {
+                let end = (total + 11).min(decoded.len());
+                let (c, w) = decoder.decompress(input, &mut decoded[total..end]).unwrap();
+                input = &input[c..];
+                total += w;
+            }
+            assert_eq!(decoded, samples);
+            assert_eq!(decoder.remainder(), None);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Transparent compression for `embedded-sdmmc` files on SD cards.
+//!
+//! Mirrors `tamp::littlefs`: the header is stored at the start of the file, and data reaches
+//! the card in 512-byte chunks so that, appended from the start of the file, every write
+//! covers whole sectors until the final one.
+//!
+//! ```ignore
+//! let mut file = volume_mgr.open_file_in_dir(dir, "LOG.TMP", Mode::ReadWriteCreateOrTruncate)?;
+//! let mut writer = CompressedFileWriter::new(&mut file, Compressor1K::new_default());
+//! writer.write(b"12.5,13.1,ok\n")?;
+//! writer.finish()?;
+//! ```
+
+use embedded_sdmmc::{BlockDevice, Error as SdError, File, TimeSource};
+use crate::file::{ChunkReader, ChunkWriter};
+use crate::Compressor;
+
+pub use crate::file::FileError;
+
+/// SD card sector size.
+const SECTOR: usize = 512;
+
+/// Compresses into an `embedded-sdmmc` file, header included.
+pub struct CompressedFileWriter<
+    'f,
+    'a,
+    D: BlockDevice,
+    T: TimeSource,
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+    const N: usize,
+> {
+    file: &'f mut File<'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    inner: ChunkWriter<N, SECTOR>,
+}
+
+impl<
+    'f,
+    'a,
+    D: BlockDevice,
+    T: TimeSource,
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+    const N: usize,
+> CompressedFileWriter<'f, 'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES, N>
+{
+    /// Creates a writer compressing with a fresh `compressor` into `file`, which should be
+    /// empty.
+    pub fn new(
+        file: &'f mut File<'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+        compressor: Compressor<N>,
+    ) -> Self {
+        Self {
+            file,
+            inner: ChunkWriter::new(compressor),
+        }
+    }
+
+    /// Compresses `data` into the file, writing each full sector.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), FileError<SdError<D::Error>>> {
+        let file = &mut *self.file;
+        self.inner.write(data, &mut |chunk| file.write(chunk))
+    }
+
+    /// Writes everything compressed so far to the card, keeping the stream open. Later
+    /// writes then start mid-sector, costing a read-modify-write, so flush sparingly.
+    pub fn flush(&mut self) -> Result<(), FileError<SdError<D::Error>>> {
+        let file = &mut *self.file;
+        self.inner.flush(true, &mut |chunk| file.write(chunk))?;
+        Ok(())
+    }
+
+    /// Performs the final flush and returns the compressed length of the file. The
+    /// directory entry picks up the new length when the file is closed.
+    pub fn finish(self) -> Result<usize, FileError<SdError<D::Error>>> {
+        let Self { file, mut inner } = self;
+        inner.flush(false, &mut |chunk| file.write(chunk))
+    }
+}
+
+/// Decompresses an `embedded-sdmmc` file, reading it a sector at a time.
+pub struct CompressedFileReader<
+    'f,
+    'a,
+    D: BlockDevice,
+    T: TimeSource,
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+    const N: usize,
+> {
+    file: &'f mut File<'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    inner: ChunkReader<N, SECTOR>,
+}
+
+impl<
+    'f,
+    'a,
+    D: BlockDevice,
+    T: TimeSource,
+    const MAX_DIRS: usize,
+    const MAX_FILES: usize,
+    const MAX_VOLUMES: usize,
+    const N: usize,
+> CompressedFileReader<'f, 'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES, N>
+{
+    /// Reads the header from the start of `file`. Its window size must match `N`.
+    pub fn open(
+        file: &'f mut File<'a, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>,
+    ) -> Result<Self, FileError<SdError<D::Error>>> {
+        let inner = ChunkReader::open(&mut |buf| file.read(buf))?;
+        Ok(Self { file, inner })
+    }
+
+    /// Decompresses into `output`, returning the bytes written; 0 at the end of the file.
+    pub fn read(&mut self, output: &mut [u8]) -> Result<usize, FileError<SdError<D::Error>>> {
+        let file = &mut *self.file;
+        self.inner.read(output, &mut |buf| file.read(buf))
+    }
+}
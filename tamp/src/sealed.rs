@@ -0,0 +1,354 @@
+//! Compress-then-encrypt pipelines over any `aead` cipher.
+//!
+//! Data is always compressed before encryption; ciphertext does not compress. The compressed
+//! stream is cut into frames of at most 256 bytes, each sealed with the STREAM construction
+//! (`aead::stream::StreamBE32`), which derives per-frame nonces from a caller nonce prefix
+//! and a counter and authenticates the final frame so truncation is detected. On the wire
+//! each frame is `[u16 LE len | LAST_FRAME][ciphertext with tag]`.
+//!
+//! ```ignore
+//! let cipher = ChaCha20Poly1305::new(&key);
+//! let mut sealer = Sealer::new(&mut compressor, cipher, &nonce_prefix, |frame| radio.send(frame));
+//! sealer.write(&reading)?;
+//! sealer.finish()?;
+//! ```
+
+use aead::consts::U5;
+use aead::generic_array::typenum::Unsigned;
+use aead::generic_array::ArrayLength;
+use aead::stream::{DecryptorBE32, EncryptorBE32, Nonce, StreamBE32};
+use aead::{AeadInPlace, KeyInit};
+use core::ops::Sub;
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::{Compressor, Decompressor, Error};
+
+/// Maximum compressed bytes per frame.
+const FRAME: usize = 256;
+/// Room reserved for the authentication tag.
+const MAX_TAG: usize = 32;
+/// Set in the frame header of the final frame.
+const LAST_FRAME: u16 = 0x8000;
+
+/// Fixed-capacity `aead::Buffer` over a frame array.
+struct FrameBuffer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl AsRef<[u8]> for FrameBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl AsMut<[u8]> for FrameBuffer<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.len]
+    }
+}
+
+impl aead::Buffer for FrameBuffer<'_> {
+    fn extend_from_slice(&mut self, other: &[u8]) -> aead::Result<()> {
+        let end = self.len + other.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(aead::Error)?
+            .copy_from_slice(other);
+        self.len = end;
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+}
+
+/// Compresses data and emits it as encrypted, authenticated frames.
+pub struct Sealer<'a, const N: usize, A, F>
+where
+    A: AeadInPlace,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+{
+    compressor: &'a mut Compressor<N>,
+    frames: SealedFrames<A, F>,
+}
+
+impl<'a, const N: usize, A, F> Sealer<'a, N, A, F>
+where
+    A: AeadInPlace + KeyInit,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+    F: FnMut(&[u8]),
+{
+    /// Creates a sealer feeding `compressor`, with each sealed frame passed to `sink`.
+    ///
+    /// `nonce_prefix` (7 bytes for 96-bit nonces) must never be reused with the same key.
+    pub fn new(
+        compressor: &'a mut Compressor<N>,
+        cipher: A,
+        nonce_prefix: &Nonce<A, StreamBE32<A>>,
+        sink: F,
+    ) -> Self {
+        Self {
+            compressor,
+            frames: SealedFrames {
+                encryptor: EncryptorBE32::from_aead(cipher, nonce_prefix),
+                sink,
+                frame: [0; 2 + FRAME + MAX_TAG],
+                filled: 0,
+            },
+        }
+    }
+
+    /// Compresses `data`, sealing and emitting every completed frame.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut out = [0u8; MAX_POLL];
+        self.compressor
+            .try_sink_all(data, &mut out, &mut |bytes| self.frames.append(bytes))?;
+        Ok(())
+    }
+
+    /// Flushes the compressor and emits the final, authenticated frame.
+    pub fn finish(mut self) -> Result<(), Error> {
+        let mut out = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut out, false)?;
+        self.frames.append(&out[..n])?;
+        self.frames.finish()
+    }
+}
+
+/// The frame a `Sealer` fills with compressed bytes before sealing it.
+struct SealedFrames<A, F>
+where
+    A: AeadInPlace,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+{
+    encryptor: EncryptorBE32<A>,
+    sink: F,
+    frame: [u8; 2 + FRAME + MAX_TAG],
+    filled: usize,
+}
+
+impl<A, F> SealedFrames<A, F>
+where
+    A: AeadInPlace,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+    F: FnMut(&[u8]),
+{
+    fn append(&mut self, mut bytes: &[u8]) -> Result<(), Error> {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(FRAME - self.filled);
+            self.frame[2 + self.filled..2 + self.filled + n].copy_from_slice(&bytes[..n]);
+            self.filled += n;
+            bytes = &bytes[n..];
+            if self.filled == FRAME {
+                let mut buffer = FrameBuffer {
+                    buf: &mut self.frame[2..],
+                    len: FRAME,
+                };
+                self.encryptor
+                    .encrypt_next_in_place(&[], &mut buffer)
+                    .map_err(|_| Error::Authentication)?;
+                let len = buffer.len;
+                self.frame[..2].copy_from_slice(&(len as u16).to_le_bytes());
+                (self.sink)(&self.frame[..2 + len]);
+                self.filled = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Seals and emits the partial last frame.
+    fn finish(mut self) -> Result<(), Error> {
+        let mut buffer = FrameBuffer {
+            buf: &mut self.frame[2..],
+            len: self.filled,
+        };
+        self.encryptor
+            .encrypt_last_in_place(&[], &mut buffer)
+            .map_err(|_| Error::Authentication)?;
+        let len = buffer.len;
+        self.frame[..2].copy_from_slice(&(len as u16 | LAST_FRAME).to_le_bytes());
+        (self.sink)(&self.frame[..2 + len]);
+        Ok(())
+    }
+}
+
+/// Verifies and decrypts frames from a `Sealer`, decompressing the plaintext.
+///
+/// The decompressor is configured from the stream header inside the first frame; its window
+/// size must be `N`.
+pub struct Opener<const N: usize, A, F>
+where
+    A: AeadInPlace,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+{
+    decompressor: Option<Decompressor<N>>,
+    decryptor: Option<DecryptorBE32<A>>,
+    sink: F,
+    frame: [u8; 2 + FRAME + MAX_TAG],
+    filled: usize,
+}
+
+impl<const N: usize, A, F> Opener<N, A, F>
+where
+    A: AeadInPlace + KeyInit,
+    A::NonceSize: Sub<U5>,
+    <A::NonceSize as Sub<U5>>::Output: ArrayLength<u8>,
+    F: FnMut(&[u8]),
+{
+    /// Creates an opener passing decompressed output to `sink`.
+    /// `cipher` and `nonce_prefix` must match the sealer's.
+    pub fn new(cipher: A, nonce_prefix: &Nonce<A, StreamBE32<A>>, sink: F) -> Self {
+        Self {
+            decompressor: None,
+            decryptor: Some(DecryptorBE32::from_aead(cipher, nonce_prefix)),
+            sink,
+            frame: [0; 2 + FRAME + MAX_TAG],
+            filled: 0,
+        }
+    }
+
+    /// Accepts sealed bytes in any split, opening each frame once it is complete.
+    /// Returns `Authentication` if a frame fails verification, has a length that cannot hold a
+    /// tag, or data follows the last frame.
+    pub fn write(&mut self, mut sealed: &[u8]) -> Result<(), Error> {
+        while !sealed.is_empty() {
+            if self.decryptor.is_none() {
+                return Err(Error::Authentication);
+            }
+            let want = if self.filled < 2 {
+                2
+            } else {
+                let len = u16::from_le_bytes([self.frame[0], self.frame[1]]) & !LAST_FRAME;
+                2 + len as usize
+            };
+            // Every sealed frame carries at least a tag; a shorter length, zero included,
+            // would never complete a frame
+            if (self.filled >= 2 && want < 2 + A::TagSize::USIZE) || want > self.frame.len() {
+                return Err(Error::Authentication);
+            }
+            let n = sealed.len().min(want - self.filled);
+            self.frame[self.filled..self.filled + n].copy_from_slice(&sealed[..n]);
+            self.filled += n;
+            sealed = &sealed[n..];
+            if self.filled == want && want > 2 {
+                self.open_frame()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the final frame was received.
+    pub fn finish(self) -> Result<(), Error> {
+        match self.decryptor {
+            None => Ok(()),
+            Some(_) => Err(Error::Authentication),
+        }
+    }
+
+    fn open_frame(&mut self) -> Result<(), Error> {
+        let header = u16::from_le_bytes([self.frame[0], self.frame[1]]);
+        let mut buffer = FrameBuffer {
+            buf: &mut self.frame[2..],
+            len: (header & !LAST_FRAME) as usize,
+        };
+        let result = if header & LAST_FRAME != 0 {
+            let decryptor = self.decryptor.take().ok_or(Error::Authentication)?;
+            decryptor.decrypt_last_in_place(&[], &mut buffer)
+        } else {
+            let decryptor = self.decryptor.as_mut().ok_or(Error::Authentication)?;
+            decryptor.decrypt_next_in_place(&[], &mut buffer)
+        };
+        result.map_err(|_| Error::Authentication)?;
+        let len = buffer.len;
+
+        let mut plaintext = &self.frame[2..2 + len];
+        let decompressor = match &mut self.decompressor {
+            Some(decompressor) => decompressor,
+            None if plaintext.is_empty() => return Ok(()),
+            None => {
+                let (decompressor, header) = Decompressor::from_header(plaintext)?;
+                plaintext = &plaintext[header..];
+                self.decompressor.insert(decompressor)
+            }
+        };
+        let mut out = [0u8; 64];
+        loop {
+            let (consumed, written) = decompressor.decompress_chunk(plaintext, &mut out)?;
+            (self.sink)(&out[..written]);
+            plaintext = &plaintext[consumed..];
+            if consumed == 0 && written == 0 {
+                break;
+            }
+        }
+        self.filled = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Config;
+    use chacha20poly1305::ChaCha20Poly1305;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_seal_open_round_trip() {
+        let key = [7u8; 32].into();
+        let nonce = [1u8; 7].into();
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 17 + i / 1000) as u8).collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = Compressor::<256>::new(config).unwrap();
+        let mut sealed = Vec::new();
+        let mut sealer = Sealer::new(
+            &mut compressor,
+            ChaCha20Poly1305::new(&key),
+            &nonce,
+            |frame: &[u8]| sealed.extend_from_slice(frame),
+        );
+        for chunk in data.chunks(100) {
+            sealer.write(chunk).unwrap();
+        }
+        sealer.finish().unwrap();
+
+        let open = |sealed: &[u8]| -> Result<Vec<u8>, Error> {
+            let mut opened = Vec::new();
+            let mut opener =
+                Opener::<256, _, _>::new(ChaCha20Poly1305::new(&key), &nonce, |bytes: &[u8]| {
+                    opened.extend_from_slice(bytes)
+                });
+            for chunk in sealed.chunks(37) {
+                opener.write(chunk)?;
+            }
+            opener.finish()?;
+            Ok(opened)
+        };
+        assert!(sealed.len() < data.len());
+        assert_eq!(open(&sealed).unwrap(), data);
+
+        let mut tampered = sealed.clone();
+        tampered[10] ^= 1;
+        assert!(matches!(open(&tampered), Err(Error::Authentication)));
+        let truncated = &sealed[..sealed.len() - 20];
+        assert!(open(truncated).is_err());
+    }
+
+    #[test]
+    fn test_open_rejects_short_frame() {
+        let key = [7u8; 32].into();
+        let nonce = [1u8; 7].into();
+        for frame in [&[0u8, 0, 1, 2][..], &[5, 0x80, 1, 2, 3, 4, 5]] {
+            let mut opener =
+                Opener::<256, _, _>::new(ChaCha20Poly1305::new(&key), &nonce, |_: &[u8]| {});
+            assert!(matches!(opener.write(frame), Err(Error::Authentication)));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use crate::{Compressor, Decompressor, Error};
+
+impl From<postcard::Error> for Error {
+    fn from(e: postcard::Error) -> Self {
+        match e {
+            postcard::Error::SerializeBufferFull => Error::BufferTooSmall,
+            _ => Error::Serialization,
+        }
+    }
+}
+
+/// Serializes `value` with postcard into `scratch`, then compresses it into `output`.
+///
+/// Writes a complete stream (header and final flush), so `compressor` should be freshly
+/// created. Returns bytes written to `output`. `BufferTooSmall` means `scratch` could not
+/// hold the serialized value, `OutputFull` that `output` could not hold the compressed one.
+pub fn compress_postcard<T: Serialize + ?Sized, const N: usize>(
+    compressor: &mut Compressor<N>,
+    value: &T,
+    scratch: &mut [u8],
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    let payload = postcard::to_slice(value, scratch)?;
+    compressor.compress_all(payload, output)
+}
+
+/// Decompresses a complete stream into `scratch`, then deserializes a `T` from it.
+///
+/// The window size is read from the header and must match `N`. The returned value may
+/// borrow from `scratch` (e.g. `&str` fields), avoiding a copy.
+pub fn decompress_postcard<'a, T: Deserialize<'a>, const N: usize>(
+    input: &[u8],
+    scratch: &'a mut [u8],
+) -> Result<T, Error> {
+    let (mut decompressor, header_len) = Decompressor::<N>::from_header(input)?;
+    let len = decompressor.decompress_all(&input[header_len..], scratch)?;
+    Ok(postcard::from_bytes(&scratch[..len])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_postcard_round_trip() {
+        let value = (42u32, "temperature=21.5;temperature=21.5;temperature=21.6");
+        let mut scratch = [0u8; 128];
+        let mut output = [0u8; 128];
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let len = compress_postcard(&mut compressor, &value, &mut scratch, &mut output).unwrap();
+
+        let mut scratch = [0u8; 128];
+        let decoded: (u32, &str) =
+            decompress_postcard::<_, 256>(&output[..len], &mut scratch).unwrap();
+        assert_eq!(decoded, value);
+
+        let mut small = [0u8; 8];
+        assert!(matches!(
+            decompress_postcard::<(u32, &str), 256>(&output[..len], &mut small),
+            Err(Error::OutputFull)
+        ));
+    }
+}
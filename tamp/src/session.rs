@@ -0,0 +1,260 @@
+//! Compressed logs that survive a reset or brown-out.
+//!
+//! A `Session` compresses into an append-only log and every `interval` input bytes writes a
+//! checkpoint to a `CheckpointStore`: it flushes the compressor to a byte boundary and saves
+//! the window together with the log length at that point. After a reset, `resume()` rebuilds
+//! the compressor from the checkpoint. The caller truncates the log to `stream_len()` and
+//! logs again whatever came after `consumed()`, so the stream stays one valid tamp stream.
+//! At most the input since the last checkpoint is lost.
+//!
+//! ```ignore
+//! let mut session = Session::<_, 1024>::resume(config, FlashSlots::new(&mut flash), 4096)?;
+//! log.truncate(session.stream_len())?;
+//! loop {
+//!     session.write(&next_record()?, |bytes| log.append(bytes))?;
+//! }
+//! ```
+//!
+//! A checkpoint is a 24 byte header (magic `TSES`, window bits, literal bits, window
+//! position u16 LE, log length u64 LE, input length u64 LE), the `N` byte window and a
+//! CRC-32 of both, u32 LE.
+
+use crate::compressor::{MAX_FLUSH, MAX_POLL};
+use crate::dictionary::crc32_update;
+use crate::{Compressor, Config, Error};
+
+/// Magic bytes at the start of a checkpoint.
+const MAGIC: [u8; 4] = *b"TSES";
+/// Bytes of checkpoint before the window.
+const HEADER: usize = 24;
+
+/// Persistent storage for the latest checkpoint, e.g. two flash slots used alternately.
+pub trait CheckpointStore {
+    /// Error reported by the storage.
+    type Error;
+
+    /// Replaces the stored checkpoint with `parts` concatenated. Should not destroy the
+    /// previous checkpoint before the new one is complete; a torn write is caught by the
+    /// checksum, but then both are lost.
+    fn save(&mut self, parts: &[&[u8]]) -> Result<(), Self::Error>;
+
+    /// Fills `buf` from the stored checkpoint at `offset`. Returns false if none is stored.
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<bool, Self::Error>;
+}
+
+/// Errors from a `Session`.
+#[derive(Debug)]
+pub enum SessionError<E> {
+    /// Compression failed, or the checkpoint is corrupt or for another configuration.
+    Tamp(Error),
+    /// The checkpoint store reported an error.
+    Store(E),
+}
+
+/// Compressor writing periodic checkpoints to `S`.
+pub struct Session<S: CheckpointStore, const N: usize> {
+    compressor: Compressor<N>,
+    config: Config,
+    store: S,
+    interval: u64,
+    consumed: u64,
+    stream_len: u64,
+    checkpointed: u64,
+}
+
+impl<S: CheckpointStore, const N: usize> Session<S, N> {
+    /// Starts a new stream, checkpointing every `interval` input bytes.
+    /// The log must be empty; the first output is the stream header.
+    pub fn start(
+        config: Config,
+        store: S,
+        interval: usize,
+    ) -> Result<Self, SessionError<S::Error>> {
+        let compressor = Compressor::new(config.clone()).map_err(SessionError::Tamp)?;
+        Ok(Self {
+            compressor,
+            config,
+            store,
+            interval: interval as u64,
+            consumed: 0,
+            stream_len: 0,
+            checkpointed: 0,
+        })
+    }
+
+    /// Continues the stream from the stored checkpoint, or starts a new one if there is none.
+    /// Fails with `Serialization` for a corrupt checkpoint and `InvalidConfig` for one written
+    /// with another window or literal size.
+    pub fn resume(
+        config: Config,
+        mut store: S,
+        interval: usize,
+    ) -> Result<Self, SessionError<S::Error>> {
+        let mut header = [0u8; HEADER];
+        if !store.read(0, &mut header).map_err(SessionError::Store)? {
+            return Self::start(config, store, interval);
+        }
+        let mut window = [0u8; N];
+        let mut checksum = [0u8; 4];
+        store.read(HEADER, &mut window).map_err(SessionError::Store)?;
+        store.read(HEADER + N, &mut checksum).map_err(SessionError::Store)?;
+        let crc = !crc32_update(crc32_update(!0, &header), &window);
+        if header[..4] != MAGIC || crc != u32::from_le_bytes(checksum) {
+            return Err(SessionError::Tamp(Error::Serialization));
+        }
+        if header[4] != config.window_bits || header[5] != config.literal_bits {
+            return Err(SessionError::Tamp(Error::InvalidConfig(
+                "Checkpoint written with another configuration",
+            )));
+        }
+        let pos = u16::from_le_bytes([header[6], header[7]]) as usize;
+        let stream_len = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let consumed = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let compressor =
+            Compressor::restore(config.clone(), &window, pos).map_err(SessionError::Tamp)?;
+        Ok(Self {
+            compressor,
+            config,
+            store,
+            interval: interval as u64,
+            consumed,
+            stream_len,
+            checkpointed: consumed,
+        })
+    }
+
+    /// Compresses `data`, passing compressed bytes to `out` and checkpointing when due.
+    pub fn write(
+        &mut self,
+        data: &[u8],
+        mut out: impl FnMut(&[u8]),
+    ) -> Result<(), SessionError<S::Error>> {
+        let mut buf = [0u8; MAX_POLL];
+        let polled = self
+            .compressor
+            .sink_all(data, &mut buf, &mut out)
+            .map_err(SessionError::Tamp)?;
+        self.stream_len += polled as u64;
+        self.consumed += data.len() as u64;
+        if self.consumed - self.checkpointed >= self.interval {
+            self.checkpoint(out)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes to a byte boundary and saves a checkpoint now, e.g. before sleeping.
+    /// `out` receives the flushed bytes, which must reach the log before the checkpoint
+    /// is relied on.
+    pub fn checkpoint(
+        &mut self,
+        mut out: impl FnMut(&[u8]),
+    ) -> Result<(), SessionError<S::Error>> {
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut buf, true).map_err(SessionError::Tamp)?;
+        out(&buf[..n]);
+        self.stream_len += n as u64;
+
+        let (window, pos) = self.compressor.window_state();
+        let mut header = [0u8; HEADER];
+        header[..4].copy_from_slice(&MAGIC);
+        header[4] = self.config.window_bits;
+        header[5] = self.config.literal_bits;
+        header[6..8].copy_from_slice(&(pos as u16).to_le_bytes());
+        header[8..16].copy_from_slice(&self.stream_len.to_le_bytes());
+        header[16..24].copy_from_slice(&self.consumed.to_le_bytes());
+        let crc = !crc32_update(crc32_update(!0, &header), window);
+        self.store
+            .save(&[&header, window, &crc.to_le_bytes()])
+            .map_err(SessionError::Store)?;
+        self.checkpointed = self.consumed;
+        Ok(())
+    }
+
+    /// Performs the final flush and returns the log length and the store, whose checkpoint
+    /// is now stale and should be cleared before the next stream.
+    pub fn finish(
+        mut self,
+        mut out: impl FnMut(&[u8]),
+    ) -> Result<(u64, S), SessionError<S::Error>> {
+        let mut buf = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut buf, false).map_err(SessionError::Tamp)?;
+        out(&buf[..n]);
+        Ok((self.stream_len + n as u64, self.store))
+    }
+
+    /// Compressed bytes in the log so far. Right after `resume()`, the length to truncate
+    /// the log to.
+    pub fn stream_len(&self) -> u64 {
+        self.stream_len
+    }
+
+    /// Input bytes covered by the stream so far. Right after `resume()`, input from this
+    /// offset on has to be logged again.
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Decompressor;
+    use std::vec::Vec;
+
+    /// A single slot in RAM standing in for flash.
+    #[derive(Default)]
+    struct RamSlot(Vec<u8>);
+
+    impl CheckpointStore for RamSlot {
+        type Error = ();
+
+        fn save(&mut self, parts: &[&[u8]]) -> Result<(), ()> {
+            self.0 = parts.concat();
+            Ok(())
+        }
+
+        fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<bool, ()> {
+            if self.0.is_empty() {
+                return Ok(false);
+            }
+            buf.copy_from_slice(&self.0[offset..offset + buf.len()]);
+            Ok(true)
+        }
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_resume_after_reset() {
+        let data: Vec<u8> = (0..3000usize)
+            .map(|i| b"v=3.31;i=0.12;"[i % 14] ^ (i / 700) as u8)
+            .collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let mut log = Vec::new();
+        let mut session =
+            Session::<_, 256>::resume(config.clone(), RamSlot::default(), 500).unwrap();
+        for record in data[..1700].chunks(100) {
+            session.write(record, |bytes| log.extend_from_slice(bytes)).unwrap();
+        }
+        // Brown-out: the session is lost, the log and the store survive
+        let RamSlot(slot) = core::mem::take(&mut session.store);
+        session.compressor.discard();
+
+        let mut session = Session::<_, 256>::resume(config, RamSlot(slot), 500).unwrap();
+        assert_eq!(session.consumed(), 1500);
+        log.truncate(session.stream_len() as usize);
+        let start = session.consumed() as usize;
+        for record in data[start..].chunks(100) {
+            session.write(record, |bytes| log.extend_from_slice(bytes)).unwrap();
+        }
+        let (len, _) = session.finish(|bytes| log.extend_from_slice(bytes)).unwrap();
+        assert_eq!(len as usize, log.len());
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&log).unwrap();
+        let mut output = std::vec![0u8; 3000];
+        let (_, n) = decompressor
+            .decompress_chunk(&log[header..], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], &data[..]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Output sinks that track how much of their storage is used.
+
+/// The sink has no room for the bytes pushed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full;
+
+/// Destination for compressed or decompressed bytes.
+///
+/// `remaining()` lets the codecs check for space before producing output, since compressor
+/// output cannot be taken back once polled.
+pub trait Sink {
+    /// Appends all of `bytes`, or nothing if they do not fit.
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Full>;
+
+    /// Number of bytes that can still be pushed.
+    fn remaining(&self) -> usize;
+}
+
+/// A `&mut [u8]` with a cursor marking the written prefix.
+pub struct SliceSink<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    /// Creates an empty sink writing to the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    /// The bytes written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if nothing was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forgets the written bytes so the buffer can be reused.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Sink for SliceSink<'_> {
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        let dst = self
+            .buf
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(Full)?;
+        dst.copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.len
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> Sink for heapless::Vec<u8, N> {
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        self.extend_from_slice(bytes).map_err(|_| Full)
+    }
+
+    fn remaining(&self) -> usize {
+        N - self.len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Sink for alloc::vec::Vec<u8> {
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Full> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn remaining(&self) -> usize {
+        isize::MAX as usize - self.len()
+    }
+}
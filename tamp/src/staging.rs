@@ -0,0 +1,60 @@
+use crate::compressor::MAX_POLL;
+use crate::{Compressor, Error};
+
+/// Staging buffer size. Must hold a final flush of the compressor's 16-byte input buffer.
+const STAGING_SIZE: usize = 64;
+
+/// Collects compressor output and hands it to a sink in small chunks.
+/// Shared by the text formatting and logging adapters.
+pub(crate) struct Staging {
+    buf: [u8; STAGING_SIZE],
+    len: usize,
+}
+
+impl Staging {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buf: [0; STAGING_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Feeds `input` through `compressor`, passing full staging buffers to `sink`.
+    pub(crate) fn write<const N: usize>(
+        &mut self,
+        compressor: &mut Compressor<N>,
+        input: &[u8],
+        sink: &mut impl FnMut(&[u8]),
+    ) -> Result<(), Error> {
+        let mut buf = [0u8; MAX_POLL];
+        compressor.sink_all(input, &mut buf, &mut |polled| {
+            if STAGING_SIZE - self.len < polled.len() {
+                self.drain(sink);
+            }
+            self.buf[self.len..][..polled.len()].copy_from_slice(polled);
+            self.len += polled.len();
+        })?;
+        Ok(())
+    }
+
+    /// Compresses everything written so far and passes it to `sink`.
+    /// `write_token`: true to keep writing afterwards, false to finish the stream.
+    pub(crate) fn flush<const N: usize>(
+        &mut self,
+        compressor: &mut Compressor<N>,
+        write_token: bool,
+        sink: &mut impl FnMut(&[u8]),
+    ) -> Result<(), Error> {
+        self.drain(sink);
+        self.len = compressor.flush(&mut self.buf, write_token)?;
+        self.drain(sink);
+        Ok(())
+    }
+
+    fn drain(&mut self, sink: &mut impl FnMut(&[u8])) {
+        if self.len > 0 {
+            sink(&self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+}
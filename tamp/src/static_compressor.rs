@@ -0,0 +1,104 @@
+//! Compressors in `static` memory, for large windows on small stacks.
+//!
+//! `Compressor::new()` builds the window on the stack before moving it into place, which
+//! overflows a 2 KiB task stack with a 4 KiB window. `static_compressor!` declares the
+//! compressor in a zero-initialized `static` instead, so it lands in `.bss`, and initializes
+//! it there on first use:
+//!
+//! ```ignore
+//! let compressor: &'static mut Compressor<4096> =
+//!     tamp::static_compressor!(4096, Config::new().window_bits(12)?)?;
+//! ```
+//!
+//! The static is hidden inside the expansion, so every `static_compressor!` call site owns
+//! one compressor and can run once; a second run panics, like `StaticCell::init()`.
+//! Targets without compare-and-swap, e.g. `thumbv6m`, need the `portable-atomic` feature.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+#[cfg(not(feature = "portable-atomic"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "portable-atomic")]
+use portable_atomic::{AtomicBool, Ordering};
+use crate::{Compressor, Config, Error};
+
+/// Storage for one `Compressor<N>` in a `static`, handed out once. See `static_compressor!`.
+pub struct StaticCompressor<const N: usize> {
+    taken: AtomicBool,
+    slot: UnsafeCell<MaybeUninit<Compressor<N>>>,
+}
+
+// SAFETY: the slot is only reached through the single `&'static mut` that `init()` hands out,
+// and the compressor being `!Send` keeps that reference in the initializing context.
+unsafe impl<const N: usize> Sync for StaticCompressor<N> {}
+
+impl<const N: usize> StaticCompressor<N> {
+    /// Creates zeroed storage, usable in a `static`.
+    pub const fn new() -> Self {
+        Self {
+            taken: AtomicBool::new(false),
+            slot: UnsafeCell::new(MaybeUninit::zeroed()),
+        }
+    }
+
+    /// Initializes the compressor in place and returns it. Panics if called before, even if
+    /// that call failed on an invalid `config`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn init(&'static self, config: Config) -> Result<&'static mut Compressor<N>, Error> {
+        if self.taken.swap(true, Ordering::AcqRel) {
+            panic!("StaticCompressor already initialized");
+        }
+        // SAFETY: the flag grants exclusive access once, and every field of a compressor is
+        // valid when zeroed: null C pointers, empty buffers, zero counters.
+        let compressor = unsafe { (*self.slot.get()).assume_init_mut() };
+        compressor.reset(config)?;
+        Ok(compressor)
+    }
+}
+
+impl<const N: usize> Default for StaticCompressor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Declares a `Compressor<N>` in a hidden `static` and initializes it in place, without
+/// building the window on the stack. Evaluates to
+/// `Result<&'static mut Compressor<N>, Error>`; panics if the same call site runs twice.
+///
+/// ```ignore
+/// let compressor = tamp::static_compressor!(4096, config)?;
+/// ```
+#[macro_export]
+macro_rules! static_compressor {
+    ($n:expr, $config:expr) => {{
+        static COMPRESSOR: $crate::StaticCompressor<{ $n }> = $crate::StaticCompressor::new();
+        COMPRESSOR.init($config)
+    }};
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Decompressor;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_static_compressor() {
+        let make = || static_compressor!(4096, Config::new().window_bits(12).unwrap());
+        let compressor = make().unwrap();
+        let input = b"static static static static";
+        let mut stream = [0u8; 64];
+        let (_, mut len) = compressor.compress_chunk(input, &mut stream).unwrap();
+        len += compressor.flush(&mut stream[len..], false).unwrap();
+
+        let (mut decompressor, header) = Decompressor::<4096>::from_header(&stream).unwrap();
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[header..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], input);
+        assert!(std::panic::catch_unwind(make).is_err());
+    }
+}
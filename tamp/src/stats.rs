@@ -0,0 +1,120 @@
+//! Compression counters readable from another execution context.
+//!
+//! The byte counters on the codecs are plain fields, only visible to whoever owns the codec.
+//! When the compressor runs in an interrupt-driven pipeline, a status task cannot read them
+//! without a data race. `SharedStats` keeps the counters in atomics instead: the context
+//! driving the codec adds to them, any other context reads a `Stats` snapshot.
+//!
+//! Targets without native 64-bit atomics, e.g. `thumbv6m`, need a portable-atomic fallback
+//! such as its `critical-section` feature enabled in the application.
+//!
+//! ```ignore
+//! static STATS: SharedStats = SharedStats::new();
+//! // in the ISR-driven pipeline
+//! let n = pipeline::pump(&mut compressor, &mut consumer, &mut packet)?;
+//! STATS.add_input(n);
+//! STATS.add_output(packet.len());
+//! STATS.add_frame();
+//! // in the status task
+//! let Stats { bytes_in, bytes_out, .. } = STATS.snapshot();
+//! ```
+
+use portable_atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Snapshot of a `SharedStats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Uncompressed bytes fed to the codec.
+    pub bytes_in: u64,
+    /// Compressed bytes produced by the codec.
+    pub bytes_out: u64,
+    /// Frames, packets or records completed.
+    pub frames: u32,
+}
+
+/// Counters updated by the context driving a codec and read from any other.
+///
+/// Each counter is updated atomically, but a snapshot taken during an update may see
+/// `bytes_in` from after it and `bytes_out` from before it.
+#[derive(Debug, Default)]
+pub struct SharedStats {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    frames: AtomicU32,
+}
+
+impl SharedStats {
+    /// Creates zeroed counters, usable in a `static`.
+    pub const fn new() -> Self {
+        Self {
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            frames: AtomicU32::new(0),
+        }
+    }
+
+    /// Counts `n` uncompressed bytes.
+    pub fn add_input(&self, n: usize) {
+        self.bytes_in.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Counts `n` compressed bytes.
+    pub fn add_output(&self, n: usize) {
+        self.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Counts one completed frame. Wraps on overflow.
+    pub fn add_frame(&self) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reads the current counters.
+    pub fn snapshot(&self) -> Stats {
+        Stats {
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            frames: self.frames.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reads the current counters and sets them to zero, e.g. for per-interval rates.
+    pub fn take(&self) -> Stats {
+        Stats {
+            bytes_in: self.bytes_in.swap(0, Ordering::Relaxed),
+            bytes_out: self.bytes_out.swap(0, Ordering::Relaxed),
+            frames: self.frames.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_shared_stats() {
+        static STATS: SharedStats = SharedStats::new();
+        let worker = std::thread::spawn(|| {
+            for _ in 0..1000 {
+                STATS.add_input(100);
+                STATS.add_output(40);
+                STATS.add_frame();
+            }
+        });
+        let mut last = Stats::default();
+        while !worker.is_finished() {
+            let now = STATS.snapshot();
+            assert!(now.bytes_in >= last.bytes_in && now.frames >= last.frames);
+            last = now;
+        }
+        worker.join().unwrap();
+        let expected = Stats {
+            bytes_in: 100_000,
+            bytes_out: 40_000,
+            frames: 1000,
+        };
+        assert_eq!(STATS.take(), expected);
+        assert_eq!(STATS.snapshot(), Stats::default());
+    }
+}
@@ -0,0 +1,365 @@
+//! Token-level view of a compressed stream.
+//!
+//! The decompressor only hands out bytes. `Tokens` parses a stream into the literals,
+//! matches and flush markers it is made of, for analysing what the compressor found,
+//! visualising streams or building post-processors. `TokenEncoder` goes the other way,
+//! writing tokens from an experimental match finder or transcoder as a valid stream. Both
+//! are pure Rust and need no codec.
+//!
+//! ```ignore
+//! let tokens = Tokens::new(&compressed)?;
+//! let matched: usize = tokens.filter_map(|t| match t {
+//!     Token::Match { len, .. } => Some(len as usize),
+//!     _ => None,
+//! }).sum();
+//! ```
+//!
+//! After the one byte header, the stream is a sequence of bits, most significant bit of
+//! each byte first. A literal is a 1 bit followed by the `literal_bits` bit value. A match
+//! is a 0 bit, a Huffman-coded length and the `window_bits` bit window offset. A flush
+//! marker is a 0 bit and a reserved length code; the rest of the byte is padding.
+
+use crate::sink::Sink;
+use crate::{Config, Error};
+
+/// Match length codes, indexed by length minus `Config::min_match_len()`, as
+/// (code, bits). Codes include the leading 0 bit of a match.
+pub(crate) const LENGTH_CODES: [(u16, u32); 14] = [
+    (0x00, 2),
+    (0x03, 3),
+    (0x08, 5),
+    (0x0B, 5),
+    (0x14, 6),
+    (0x24, 7),
+    (0x26, 7),
+    (0x2B, 7),
+    (0x4B, 8),
+    (0x54, 8),
+    (0x94, 9),
+    (0x95, 9),
+    (0xAA, 9),
+    (0x27, 7),
+];
+/// Code of a flush marker, in the same form as `LENGTH_CODES`.
+pub(crate) const FLUSH_CODE: (u16, u32) = (0xAB, 9);
+
+/// One element of a compressed stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// A byte stored as is.
+    Literal(u8),
+    /// `len` bytes copied from the window, starting at absolute window position `offset`.
+    Match {
+        /// Window position the copy starts at, not a distance back.
+        offset: u16,
+        /// Bytes copied.
+        len: u8,
+    },
+    /// A flush with `write_token`; decoding continues at the next byte boundary.
+    FlushMarker,
+}
+
+/// Iterator over the tokens of a compressed stream.
+///
+/// Ends at the end of the input, including when it stops inside a token, e.g. the zero
+/// padding after the final flush. Compare `bit_position()` with the input length to tell
+/// a complete stream from a truncated one.
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    input: &'a [u8],
+    bit: usize,
+    config: Config,
+}
+
+impl<'a> Tokens<'a> {
+    /// Parses the header at the start of `input` and iterates over the tokens after it.
+    pub fn new(input: &'a [u8]) -> Result<Self, Error> {
+        let &header = input.first().ok_or(Error::InputExhausted)?;
+        if header & 0b11 != 0 {
+            return Err(Error::InvalidConfig("Unsupported header extension"));
+        }
+        let config = Config {
+            window_bits: (header >> 5) + 8,
+            literal_bits: ((header >> 3) & 0b11) + 5,
+            lazy_matching: false,
+            use_custom_dictionary: header & 0b100 != 0,
+            ..Config::new()
+        };
+        Ok(Self {
+            input,
+            bit: 8,
+            config,
+        })
+    }
+
+    /// Iterates over the tokens of a stream whose header was stripped or never written.
+    pub fn with_config(config: Config, input: &'a [u8]) -> Self {
+        Self {
+            input,
+            bit: 0,
+            config,
+        }
+    }
+
+    /// The stream configuration.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Bits of the input parsed so far, header included.
+    pub fn bit_position(&self) -> usize {
+        self.bit
+    }
+
+    /// Reads `n` bits at `bit`, or None past the end of the input.
+    fn bits(&self, bit: usize, n: u32) -> Option<u32> {
+        if bit + n as usize > self.input.len() * 8 {
+            return None;
+        }
+        let mut value = 0;
+        for i in bit..bit + n as usize {
+            value = (value << 1) | ((self.input[i / 8] >> (7 - i % 8)) & 1) as u32;
+        }
+        Some(value)
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if self.bits(self.bit, 1)? == 1 {
+            let value = self.bits(self.bit + 1, self.config.literal_bits as u32)?;
+            self.bit += 1 + self.config.literal_bits as usize;
+            return Some(Token::Literal(value as u8));
+        }
+        // The codes are prefix free and complete, so one of them matches within 9 bits
+        let mut bits = 2;
+        let index = loop {
+            let code = self.bits(self.bit, bits)? as u16;
+            if (code, bits) == FLUSH_CODE {
+                self.bit = (self.bit + bits as usize).next_multiple_of(8);
+                return Some(Token::FlushMarker);
+            }
+            if let Some(index) = LENGTH_CODES.iter().position(|&c| c == (code, bits)) {
+                break index;
+            }
+            bits += 1;
+        };
+        let window_bits = self.config.window_bits as u32;
+        let offset = self.bits(self.bit + bits as usize, window_bits)?;
+        self.bit += (bits + window_bits) as usize;
+        Some(Token::Match {
+            offset: offset as u16,
+            len: (self.config.min_match_len() + index) as u8,
+        })
+    }
+}
+
+/// Writes tokens as a compressed stream, checking them against the configuration.
+///
+/// The encoder does not know the window contents, so it cannot check that a match copies
+/// what the caller meant; the window starts as the decompressor initializes it.
+pub struct TokenEncoder {
+    config: Config,
+    /// Bits not yet written, in the low `bits` bits, first bit highest.
+    acc: u32,
+    bits: u32,
+}
+
+impl TokenEncoder {
+    /// Creates an encoder whose output starts with the stream header for `config`.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let mut encoder = Self::headerless(config)?;
+        encoder.acc = (((encoder.config.window_bits - 8) << 5)
+            | ((encoder.config.literal_bits - 5) << 3)
+            | ((encoder.config.use_custom_dictionary as u8) << 2)) as u32;
+        encoder.bits = 8;
+        Ok(encoder)
+    }
+
+    /// Creates an encoder that writes no header, e.g. to continue an existing stream at a
+    /// byte boundary.
+    pub fn headerless(config: Config) -> Result<Self, Error> {
+        if !(8..=15).contains(&config.window_bits) || !(5..=8).contains(&config.literal_bits) {
+            return Err(Error::InvalidConfig("Invalid parameters"));
+        }
+        Ok(Self {
+            config,
+            acc: 0,
+            bits: 0,
+        })
+    }
+
+    /// Appends `token`, writing the completed bytes to `sink`.
+    ///
+    /// Fails with `ExcessBits` for a literal wider than `literal_bits`, `InvalidConfig` for
+    /// a match length outside `Config::min_match_len()..=max_match_len()` or a match reaching
+    /// past the end of the window, and `OutputFull` if `sink` lacks room; nothing is written
+    /// on failure.
+    pub fn push(&mut self, token: Token, sink: &mut impl Sink) -> Result<(), Error> {
+        let (code, bits) = match token {
+            Token::Literal(byte) => {
+                if byte as u32 >> self.config.literal_bits != 0 {
+                    return Err(Error::ExcessBits);
+                }
+                let bits = 1 + self.config.literal_bits as u32;
+                ((1 << (bits - 1)) | byte as u32, bits)
+            }
+            Token::Match { offset, len } => {
+                let index = (len as usize)
+                    .checked_sub(self.config.min_match_len())
+                    .filter(|&index| index < LENGTH_CODES.len())
+                    .ok_or(Error::InvalidConfig("Match length out of range"))?;
+                if offset as usize + len as usize > self.config.window_size() {
+                    return Err(Error::InvalidConfig("Match reaches past the window"));
+                }
+                let (code, bits) = LENGTH_CODES[index];
+                let window_bits = self.config.window_bits as u32;
+                (((code as u32) << window_bits) | offset as u32, bits + window_bits)
+            }
+            Token::FlushMarker => {
+                let (code, bits) = FLUSH_CODE;
+                let padding = (8 - (self.bits + bits) % 8) % 8;
+                ((code as u32) << padding, bits + padding)
+            }
+        };
+        if ((self.bits + bits) / 8) as usize > sink.remaining() {
+            return Err(Error::OutputFull);
+        }
+        self.acc = (self.acc << bits) | code;
+        self.bits += bits;
+        self.drain(sink)
+    }
+
+    /// The stream configuration.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Bits of an incomplete byte held back until the next token or `finish()`.
+    #[cfg(feature = "rust-backend")]
+    pub(crate) fn pending_bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Pads the last byte with zeros and writes it, ending the stream.
+    pub fn finish(&mut self, sink: &mut impl Sink) -> Result<(), Error> {
+        let padding = (8 - self.bits % 8) % 8;
+        if ((self.bits + padding) / 8) as usize > sink.remaining() {
+            return Err(Error::OutputFull);
+        }
+        self.acc <<= padding;
+        self.bits += padding;
+        self.drain(sink)
+    }
+
+    /// Writes the complete bytes of the bit buffer, after `remaining()` was checked.
+    fn drain(&mut self, sink: &mut impl Sink) -> Result<(), Error> {
+        let mut bytes = [0u8; 4];
+        let n = (self.bits / 8) as usize;
+        for byte in &mut bytes[..n] {
+            self.bits -= 8;
+            *byte = (self.acc >> self.bits) as u8;
+        }
+        sink.push(&bytes[..n]).map_err(|_| Error::OutputFull)
+    }
+}
+
+#[cfg(all(test, feature = "compressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::Compressor;
+    use crate::SliceSink;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_tokens_replay() {
+        let data: Vec<u8> = (0..2000usize)
+            .map(|i| b"temp=21.4;hum=40;"[i % 17] ^ (i / 500) as u8)
+            .collect();
+        let config = Config::new().window_bits(8).unwrap();
+        let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+        let mut compressed = std::vec![0u8; 4096];
+        let (_, mut len) = compressor.compress_chunk(&data[..1000], &mut compressed).unwrap();
+        len += compressor.flush(&mut compressed[len..], true).unwrap();
+        let (_, n) = compressor.compress_chunk(&data[1000..], &mut compressed[len..]).unwrap();
+        len += n;
+        len += compressor.flush(&mut compressed[len..], false).unwrap();
+
+        let tokens = Tokens::new(&compressed[..len]).unwrap();
+        assert_eq!(tokens.config().window_bits, 8);
+        let mut window = [0u8; 256];
+        unsafe { tamp_sys::tamp_initialize_dictionary(window.as_mut_ptr(), window.len()) };
+        let (mut pos, mut output, mut flushes) = (0, Vec::new(), 0);
+        for token in tokens {
+            let bytes: Vec<u8> = match token {
+                Token::Literal(byte) => std::vec![byte],
+                Token::Match { offset, len } => (0..len as usize)
+                    .map(|i| window[(offset as usize + i) % 256])
+                    .collect(),
+                Token::FlushMarker => {
+                    flushes += 1;
+                    continue;
+                }
+            };
+            for byte in bytes {
+                window[pos] = byte;
+                pos = (pos + 1) % 256;
+                output.push(byte);
+            }
+        }
+        // None if the first part happened to end on a byte boundary
+        assert!(flushes <= 1);
+        assert_eq!(output, data);
+
+        // Re-encoding the tokens reproduces the stream bit for bit
+        let mut encoder = TokenEncoder::new(config).unwrap();
+        let mut encoded = [0u8; 4096];
+        let mut sink = SliceSink::new(&mut encoded);
+        for token in Tokens::new(&compressed[..len]).unwrap() {
+            encoder.push(token, &mut sink).unwrap();
+        }
+        encoder.finish(&mut sink).unwrap();
+        assert_eq!(sink.written(), &compressed[..len]);
+    }
+
+    #[cfg(all(feature = "decompressor", feature = "dictionary"))]
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_token_encoder() {
+        let config = Config::new().window_bits(8).unwrap().custom_dictionary(true);
+        let mut encoder = TokenEncoder::new(config.clone()).unwrap();
+        let mut buf = [0u8; 64];
+        let mut sink = SliceSink::new(&mut buf);
+        for &byte in b"abcd" {
+            encoder.push(Token::Literal(byte), &mut sink).unwrap();
+        }
+        encoder.push(Token::FlushMarker, &mut sink).unwrap();
+        let repeat = Token::Match { offset: 100, len: 12 };
+        encoder.push(repeat, &mut sink).unwrap();
+        encoder.finish(&mut sink).unwrap();
+        let stream = sink.written();
+
+        let mut dictionary = [0u8; 256];
+        dictionary[100..112].copy_from_slice(b"hello world!");
+        let (config, header) = Config::from_header(stream).unwrap();
+        let mut decompressor =
+            crate::Decompressor::<256>::with_dictionary(config, Some(&dictionary)).unwrap();
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor.decompress_chunk(&stream[header..], &mut output).unwrap();
+        assert_eq!(&output[..n], b"abcdhello world!");
+
+        let mut spare = [0u8; 64];
+        let mut sink = SliceSink::new(&mut spare);
+        let mut narrow = TokenEncoder::new(Config::new().literal_bits(7).unwrap()).unwrap();
+        assert!(matches!(narrow.push(Token::Literal(0x80), &mut sink), Err(Error::ExcessBits)));
+        let past_end = Token::Match { offset: 250, len: 8 };
+        assert!(encoder.push(past_end, &mut sink).is_err());
+        assert!(encoder.push(Token::Match { offset: 0, len: 1 }, &mut sink).is_err());
+        let mut full = SliceSink::new(&mut spare[..0]);
+        assert!(matches!(encoder.push(repeat, &mut full), Err(Error::OutputFull)));
+    }
+}
@@ -0,0 +1,15 @@
+//! Trace-level instrumentation, sent to `log` and/or `tracing` when those features are on.
+//!
+//! Events use the `tamp` target so they can be filtered as a group. With neither feature
+//! enabled the macro expands to nothing and the arguments are not evaluated. The logger calls
+//! can panic, so these features do not combine with `no-panic`.
+
+/// Emits a trace-level event with a format string, like `log::trace!`.
+macro_rules! trace_event {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        ::log::trace!(target: "tamp", $($arg)+);
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!(target: "tamp", $($arg)+);
+    };
+}
@@ -0,0 +1,98 @@
+use ufmt_write::uWrite;
+use crate::staging::Staging;
+use crate::{Compressor, Error};
+
+/// `ufmt::uWrite` adapter that compresses formatted text on the fly.
+///
+/// Mirrors `CompressingFmtWriter` for projects that avoid `core::fmt`.
+/// Call `finish()` to terminate the stream.
+pub struct CompressingUWriter<'a, const N: usize, F: FnMut(&[u8])> {
+    compressor: &'a mut Compressor<N>,
+    sink: F,
+    staging: Staging,
+}
+
+impl<'a, const N: usize, F: FnMut(&[u8])> CompressingUWriter<'a, N, F> {
+    /// Creates a writer feeding `compressor`, with compressed output passed to `sink`.
+    pub fn new(compressor: &'a mut Compressor<N>, sink: F) -> Self {
+        Self {
+            compressor,
+            sink,
+            staging: Staging::new(),
+        }
+    }
+
+    /// Pushes all text written so far to the sink, keeping the stream open.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.staging.flush(self.compressor, true, &mut self.sink)
+    }
+
+    /// Performs the final flush, terminating the compressed stream.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.staging.flush(self.compressor, false, &mut self.sink)
+    }
+}
+
+impl<const N: usize, F: FnMut(&[u8])> uWrite for CompressingUWriter<'_, N, F> {
+    type Error = Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.staging
+            .write(self.compressor, s.as_bytes(), &mut self.sink)
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{Config, Decompressor};
+    use std::string::String;
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_uwriter_round_trip() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let mut writer =
+            CompressingUWriter::new(&mut compressor, |b| compressed.extend_from_slice(b));
+        let mut expected = String::new();
+        for t in 0..200 {
+            let line = std::format!("t={},v={}\n", t, t * 3);
+            writer.write_str(&line).unwrap();
+            expected.push_str(&line);
+            if t == 100 {
+                writer.flush().unwrap();
+            }
+        }
+        writer.finish().unwrap();
+        assert!(compressed.len() < expected.len());
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = std::vec![0u8; expected.len()];
+        decompressor
+            .decompress_exact(&mut &compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(output, expected.as_bytes());
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_uwriter_flush_keeps_stream_open() {
+        let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut compressed = Vec::new();
+        let mut writer =
+            CompressingUWriter::new(&mut compressor, |b| compressed.extend_from_slice(b));
+        writer.write_str("flushed ").unwrap();
+        writer.flush().unwrap();
+        compressor.discard();
+
+        let (mut decompressor, header) = Decompressor::<256>::from_header(&compressed).unwrap();
+        let mut output = [0u8; 8];
+        decompressor
+            .decompress_exact(&mut &compressed[header..], &mut output)
+            .unwrap();
+        assert_eq!(&output, b"flushed ");
+    }
+}
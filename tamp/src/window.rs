@@ -0,0 +1,103 @@
+//! Where a codec keeps its window.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+/// Contiguous memory for an `N` byte codec window.
+///
+/// The window is by far the largest part of a codec. By default it is an array inside the
+/// `Compressor` / `Decompressor` struct, but on parts with CCM, DTCM or external SRAM it pays
+/// to route it into that memory while the codec state stays wherever it is used:
+///
+/// ```ignore
+/// #[link_section = ".ccmram"]
+/// static mut WINDOW: [u8; 4096] = [0; 4096];
+/// let window = unsafe { &mut *core::ptr::addr_of_mut!(WINDOW) };
+/// let compressor = Compressor::with_storage(config, window)?;
+/// ```
+///
+/// Board crates can implement it for their own memory regions. The codecs hand the C library
+/// a pointer from `window_mut()` before every call, so the storage may move between calls,
+/// but must keep its contents.
+pub trait WindowStorage<const N: usize> {
+    /// The window contents.
+    fn window(&self) -> &[u8; N];
+
+    /// The window contents, for the codec to update.
+    fn window_mut(&mut self) -> &mut [u8; N];
+}
+
+/// A window inline in the codec, the default.
+impl<const N: usize> WindowStorage<N> for [u8; N] {
+    fn window(&self) -> &[u8; N] {
+        self
+    }
+
+    fn window_mut(&mut self) -> &mut [u8; N] {
+        self
+    }
+}
+
+/// A borrowed window, e.g. a `static` placed with `#[link_section]`, one taken from a
+/// `StaticCell`, or the caller's buffer behind a `DynCompressor`.
+impl<const N: usize> WindowStorage<N> for &mut [u8; N] {
+    fn window(&self) -> &[u8; N] {
+        self
+    }
+
+    fn window_mut(&mut self) -> &mut [u8; N] {
+        self
+    }
+}
+
+/// A window on the heap, keeping the codec struct small.
+#[cfg(feature = "alloc")]
+impl<const N: usize> WindowStorage<N> for Box<[u8; N]> {
+    fn window(&self) -> &[u8; N] {
+        self
+    }
+
+    fn window_mut(&mut self) -> &mut [u8; N] {
+        self
+    }
+}
+
+/// Writes `data` into `window` as if it had just passed through a codec at position `pos`:
+/// only the last `N` bytes are copied, but the position advances by all of it. Returns the
+/// new position.
+#[cfg(any(feature = "compressor", feature = "decompressor"))]
+pub(crate) fn prime<const N: usize>(window: &mut [u8; N], pos: usize, data: &[u8]) -> usize {
+    let skip = data.len().saturating_sub(N);
+    let pos = (pos + skip) % N;
+    let data = &data[skip..];
+    let (head, tail) = data.split_at(data.len().min(N - pos));
+    window[pos..pos + head.len()].copy_from_slice(head);
+    window[..tail.len()].copy_from_slice(tail);
+    (pos + data.len()) % N
+}
+
+#[cfg(all(test, feature = "compressor", feature = "decompressor"))]
+mod tests {
+    extern crate std;
+    use crate::{Compressor, Config, Decompressor};
+    use std::boxed::Box;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_window_storage() {
+        let config = Config::new().window_bits(8).unwrap();
+        let input = b"placed window placed window placed";
+        let mut stream = [0u8; 64];
+        let mut compressor = Compressor::<256>::new(config.clone()).unwrap();
+        let len = compressor.compress_all(input, &mut stream).unwrap();
+
+        let window: &'static mut [u8; 256] = Box::leak(Box::new([0x55; 256]));
+        let mut decompressor = Decompressor::with_storage(config, window).unwrap();
+        assert!(core::mem::size_of_val(&decompressor) < Decompressor::<256>::MEMORY_BYTES - 200);
+        let mut output = [0u8; 64];
+        let (_, n) = decompressor
+            .decompress_chunk(&stream[1..len], &mut output)
+            .unwrap();
+        assert_eq!(&output[..n], input);
+    }
+}
@@ -0,0 +1,95 @@
+use std::io;
+use crate::Compressor;
+use crate::compressor::MAX_FLUSH;
+
+/// `std::io::Write` adapter that compresses everything written to it into `W`.
+///
+/// Host-side counterpart to `bufread::DecompressReader`. Call `finish()` to terminate the
+/// stream; dropping the writer loses the buffered tail.
+pub struct CompressWriter<W: io::Write, const N: usize> {
+    compressor: Compressor<N>,
+    inner: W,
+}
+
+impl<W: io::Write, const N: usize> CompressWriter<W, N> {
+    /// Creates a writer compressing with `compressor` into `inner`.
+    pub fn new(compressor: Compressor<N>, inner: W) -> Self {
+        Self { compressor, inner }
+    }
+
+    /// Performs the final flush and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut out = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut out, false)?;
+        self.inner.write_all(&out[..n])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: io::Write, const N: usize> io::Write for CompressWriter<W, N> {
+    /// Compresses all of `buf`, passing the output to `W` in stack-sized pieces.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out = [0u8; 256];
+        let mut consumed = 0;
+        while consumed < buf.len() {
+            let (c, written) = self.compressor.compress_chunk(&buf[consumed..], &mut out)?;
+            self.inner.write_all(&out[..written])?;
+            consumed += c;
+        }
+        Ok(consumed)
+    }
+
+    /// Writes everything compressed so far to `W` and flushes it, keeping the stream open.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut out = [0u8; MAX_FLUSH];
+        let n = self.compressor.flush(&mut out, true)?;
+        self.inner.write_all(&out[..n])?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "decompressor"))]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::bufread::DecompressReader;
+    use std::io::{Read, Write};
+    use std::vec::Vec;
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_io_round_trip() {
+        let data: Vec<u8> = (0..5000u32).map(|i| ((i % 97) ^ (i / 300)) as u8).collect();
+        let compressor = Compressor::<1024>::new(Config::new()).unwrap();
+        let mut writer = CompressWriter::new(compressor, Vec::new());
+        for chunk in data.chunks(333) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.len() < data.len());
+
+        let mut reader = DecompressReader::<_, 1024, 64>::new(&compressed[..]).unwrap();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+    }
+
+    #[cfg_attr(miri, ignore = "calls into the C library")]
+    #[test]
+    fn test_io_incompressible() {
+        let noise: Vec<u8> = (0..3000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+        let mut writer = CompressWriter::new(compressor, Vec::new());
+        assert_eq!(writer.write(&noise).unwrap(), noise.len());
+        let compressed = writer.finish().unwrap();
+
+        let mut reader = DecompressReader::<_, 256, 64>::new(&compressed[..]).unwrap();
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, noise);
+    }
+}
@@ -0,0 +1,77 @@
+//! Round trip on an ESP32-S3 with the `esp32` feature, which swaps in upstream's ESP32 code
+//! for the generic C paths. It prints `esp32s3: ok` or the panic over the UART; flash a board
+//! and watch it with:
+//!
+//! ```text
+//! CARGO_TARGET_XTENSA_ESP32S3_NONE_ELF_RUNNER="espflash flash --monitor" \
+//!     cargo +esp test -p tamp --test esp32s3 --release --features esp32 \
+//!     --target xtensa-esp32s3-none-elf -Zbuild-std=core
+//! ```
+//!
+//! On every other target the test is an empty binary, so `--all-features` builds stay green.
+#![cfg_attr(target_arch = "xtensa", no_std, no_main)]
+
+#[cfg(target_arch = "xtensa")]
+esp_bootloader_esp_idf::esp_app_desc!();
+
+#[cfg(target_arch = "xtensa")]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    esp_println::println!("esp32s3: {}", info);
+    loop {}
+}
+
+#[cfg(target_arch = "xtensa")]
+fn round_trip<const N: usize>(input: &[u8]) {
+    use tamp::{Compressor, Config, Decompressor};
+
+    let config = Config::new().window_bits(N.trailing_zeros() as u8).unwrap();
+    let mut compressor = Compressor::<N>::new(config).unwrap();
+    let mut stream = [0u8; 4096];
+    let (mut read, mut len) = (0, 0);
+    while read < input.len() {
+        let (consumed, n) = compressor
+            .compress_chunk(&input[read..], &mut stream[len..])
+            .unwrap();
+        read += consumed;
+        len += n;
+    }
+    len += compressor.flush(&mut stream[len..], false).unwrap();
+
+    let (mut decompressor, header) = Decompressor::<N>::from_header(&stream[..len]).unwrap();
+    let mut output = [0u8; 3000];
+    let (mut read, mut written) = (header, 0);
+    while read < len {
+        let (consumed, n) = decompressor
+            .decompress_chunk(&stream[read..len], &mut output[written..])
+            .unwrap();
+        read += consumed;
+        written += n;
+        if consumed == 0 && n == 0 {
+            break;
+        }
+    }
+    assert_eq!(&output[..written], input, "{} byte window", N);
+}
+
+#[cfg(target_arch = "xtensa")]
+#[esp_hal::main]
+fn main() -> ! {
+    let _peripherals = esp_hal::init(esp_hal::Config::default());
+    let mut input = [0u8; 3000];
+    for (i, byte) in input.iter_mut().enumerate() {
+        // Repeats for the match search, with a noisy stretch for the literal path
+        *byte = if (1000..1500).contains(&i) {
+            ((i as u32).wrapping_mul(2654435761) >> 13) as u8
+        } else {
+            b"esp32s3 match search "[i % 21]
+        };
+    }
+    round_trip::<256>(&input);
+    round_trip::<1024>(&input);
+    esp_println::println!("esp32s3: ok");
+    loop {}
+}
+
+#[cfg(not(target_arch = "xtensa"))]
+fn main() {}
@@ -0,0 +1,62 @@
+//! Links every function marked `#[no_panic]`, so an optimized build with the feature fails
+//! to link if any of them can panic. CI runs:
+//!
+//! ```text
+//! cargo test -p tamp --release --features no-panic --test no_panic
+//! ```
+#![cfg(all(feature = "no-panic", not(debug_assertions)))]
+
+use core::mem::MaybeUninit;
+use tamp::{Compressor, Config, Decompressor};
+
+#[test]
+fn test_no_panic_round_trip() {
+    let input = b"no panic, no panic, no panic at all";
+    let mut stream = [0u8; 128];
+
+    let mut compressor = Compressor::<256>::new(Config::new().window_bits(8).unwrap()).unwrap();
+    let (consumed, mut len) = compressor
+        .compress_chunk(&input[..10], &mut stream)
+        .unwrap();
+    assert_eq!(consumed, 10);
+    let mut uninit = [MaybeUninit::uninit(); 64];
+    let (consumed, written) = compressor
+        .compress_chunk_uninit(&input[10..20], &mut uninit)
+        .unwrap();
+    assert_eq!(consumed, 10);
+    stream[len..len + written.len()].copy_from_slice(written);
+    len += written.len();
+    let mut rest = &input[20..];
+    while !rest.is_empty() {
+        if compressor.is_full() {
+            len += compressor.poll(&mut stream[len..]).unwrap();
+        }
+        rest = &rest[compressor.sink(rest)..];
+    }
+    len += compressor.flush(&mut stream[len..], false).unwrap();
+
+    // Each of decompress_chunk, decompress_chunk_uninit and sink/poll gets a third
+    let (mut decompressor, mut read) = Decompressor::<256>::from_header(&stream).unwrap();
+    let third = (len - read) / 3;
+    let mut output = [0u8; 64];
+    let (c, mut n) = decompressor
+        .decompress_chunk(&stream[read..read + third], &mut output)
+        .unwrap();
+    read += c;
+    let (c, written) = decompressor
+        .decompress_chunk_uninit(&stream[read..read + third], &mut uninit)
+        .unwrap();
+    output[n..n + written.len()].copy_from_slice(written);
+    n += written.len();
+    read += c;
+    let mut rest = &stream[read..len];
+    loop {
+        rest = &rest[decompressor.sink(rest)..];
+        let written = decompressor.poll(&mut output[n..]).unwrap();
+        n += written;
+        if rest.is_empty() && written == 0 {
+            break;
+        }
+    }
+    assert_eq!(&output[..n], input);
+}